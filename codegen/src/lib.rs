@@ -1,6 +1,7 @@
 #![feature(proc_macro_diagnostic)]
 use darling::FromMeta;
 use proc_macro::TokenStream;
+use quote::__private::TokenStream as TokenStream2;
 use service::Service;
 use syn::spanned::Spanned;
 use syn::{parse_macro_input, AttributeArgs};
@@ -31,6 +32,27 @@ mod util;
 /// }
 /// ```
 ///
+/// Services can reply with a stream of responses by returning `impl Stream`. Each item is sent as
+/// a separate frame (see `micro_tower::api::stream`).
+///
+/// ```rust
+/// #[micro_tower::codegen::service]
+/// async fn service_stream(request: usize) -> impl Stream<Item = Result<usize, Infallible>> {
+/// 	futures::stream::iter((0..request).map(Ok))
+/// }
+/// ```
+///
+/// Services can receive a stream of requests by taking `impl Stream` as request argument. The
+/// request type of these services is `micro_tower::api::stream::Requests` and they always reply
+/// with a stream of responses (a single response is sent as stream with one item).
+///
+/// ```rust
+/// #[micro_tower::codegen::service]
+/// async fn service_sum(requests: impl Stream<Item = usize>) -> usize {
+/// 	requests.fold(0, |sum, n| async move { sum + n }).await
+/// }
+/// ```
+///
 /// It is possible to use other services (inner service) in a service by putting an argument with
 /// the type of the service.
 ///
@@ -56,12 +78,73 @@ mod util;
 ///
 /// the setter for inner services will always be named the same as the service argument.
 ///
+/// Inner services of type `Service<S>` are looked up in the registry by the name of `S` (see
+/// `micro_tower::runtime::registry::Key`), so the argument may be named freely. Any other inner
+/// service is looked up by argument name.
+///
+/// Objects registered with `Builder::manage` are passed to a service by putting an argument of
+/// type `Managed<T>`. The object is looked up by the argument name first and by type otherwise.
+/// Managed objects are not listed as dependencies of the service, so a missing object fails the
+/// creation of the service instead of the validation of the runtime.
+///
+/// ```rust
+/// #[micro_tower::codegen::service]
+/// async fn service_db(request: Query, db: Managed<Pool>) -> Result<Rows, BoxError> {
+/// 	db.query(request).await
+/// }
+/// ```
+///
+/// A typed client named `<name>Client` is generated as well. It connects to a remote session of
+/// the service and implements `tower::Service` with the same request and response type.
+///
+/// ```rust
+/// let mut client = service_nameClient::<Json>::connect(addr).await?;
+/// let response = client.ready().await?.call(()).await?;
+/// ```
+///
+/// Lifecycle hooks receive the runtime registry and may fail with any error convertible into
+/// `BoxError`. Hooks are called in dependency order at startup and in reverse order at shutdown.
+///
+/// ```rust
+/// async fn open(registry: Arc<RwLock<registry::Type>>) -> Result<(), BoxError> {
+/// 	Ok(())
+/// }
+///
+/// #[micro_tower::codegen::service(on_start = "open")]
+/// async fn service_db(request: Query) -> Rows {
+/// 	todo!()
+/// }
+/// ```
+///
+/// Inner services may also run in another process. Registering them with
+/// `Builder::remote::<service_name, Json>(addr)` provides dependent services with a client instead
+/// of a local instance.
+///
 /// # Attributes
 ///
 /// - `crate = "<path>"`: Use to specify a crate path different from `::micro_tower`.
 /// - `name = "<name>"`: Change log name of service to `<name>`.
 /// - `extend`: Specifies that the service already exists and only `tower::Service` should be
 ///   implemented.
+/// - `buffer = <size>`: Number of requests buffered in front of the service.
+/// - `pool = <size>`: Create a pool of `<size>` services instead of a single one.
+/// - `pool = "<min>..<max>"`: Create a pool which scales between `<min>` and `<max>` services by
+///   its load (see `micro_tower::service::pool::Autoscale`).
+/// - `balance = "<strategy>"`: Balance requests between the services of a pool by `p2c`
+///   (default), `round_robin`, `least_pending` or `hash` (see
+///   `micro_tower::service::pool::Strategy`).
+/// - `hash_key = "<path>"`: Function which returns the key of a request used by `balance = "hash"`.
+///   Defaults to the whole request.
+/// - `eject = "<when>"`: Replace members of a pool if their `poll_ready` failed (`unready`,
+///   default), if their `poll_ready` or requests failed (`errors`) or `never` (see
+///   `micro_tower::service::pool::Ejection`).
+/// - `on_start = "<path>"`: Async function called once before the service is registered (see
+///   `micro_tower::service::Create::on_start`).
+/// - `on_stop = "<path>"`: Async function called once after the runtime was shut down (see
+///   `micro_tower::service::Create::on_stop`).
+///
+/// Buffer and pool size as well as a request timeout can be overridden at runtime by service name
+/// (see `micro_tower::runtime::config`). Pools follow changes of their configured size on reload.
 ///
 /// # Caveats / Notes
 ///
@@ -80,11 +163,34 @@ pub fn service(args: TokenStream, items: TokenStream) -> TokenStream {
 	Service::new(&args, decl).generate().into()
 }
 
-/// TODO
+/// Proc macro attribute to generate `main` from an `async` function which returns the runtime to
+/// run. Installs a global tracing subscriber (see `micro_tower::telemetry`) before the function
+/// is called.
+///
+/// ```rust
+/// #[micro_tower::codegen::main(filter = "info,micro_tower=debug", format = "json")]
+/// async fn tower() -> _ {
+/// 	Runtime::builder().service::<service_name>().build().await.unwrap()
+/// }
+/// ```
+///
+/// # Attributes
+///
+/// - `filter = "<directives>"`: Log filter in `RUST_LOG` syntax (default: `info`). Overridden by
+///   the `RUST_LOG` environment variable. Only target and level directives are supported (e.g.
+///   `warn,micro_tower=debug`). Span and field directives (e.g. `app[request]=trace`) are
+///   rejected, also if set by `RUST_LOG`.
+/// - `format = "<format>"`: Output format of log events. One of `full` (default), `pretty`,
+///   `compact` or `json` (JSON lines).
+/// - `subscriber = false`: Do not install a subscriber. Use if the application sets up tracing
+///   itself.
+///
+/// All other attributes are passed to `tokio::main` (e.g. `flavor = "current_thread"`).
 #[proc_macro_attribute]
 pub fn main(args: TokenStream, items: TokenStream) -> TokenStream {
 	let decl = parse_macro_input!(items as syn::ItemFn);
 	let args = parse_macro_input!(args as syn::AttributeArgs);
+	let (telemetry, args) = main_args(args);
 
 	let attrs = decl.attrs;
 	let vis = decl.vis;
@@ -120,13 +226,68 @@ pub fn main(args: TokenStream, items: TokenStream) -> TokenStream {
 
 		#[::micro_tower::export::tokio::main( #( #args ),* )]
 		async fn main() {
-			let subscriber = ::micro_tower::export::tracing_subscriber::FmtSubscriber::builder()
-				.with_max_level(::micro_tower::export::tracing::Level::TRACE)
-				.finish();
-			::micro_tower::export::tracing::subscriber::set_global_default(subscriber).unwrap();
+			#telemetry
 			let rt = #ident().await;
 			rt.run().await;
 		}
 	)
 	.into()
 }
+
+/// Splits telemetry options from arguments passed to `tokio::main` and returns the code to
+/// install the subscriber.
+fn main_args(args: AttributeArgs) -> (TokenStream2, AttributeArgs) {
+	let mut filter = None;
+	let mut format = quote::quote!(Full);
+	let mut subscriber = true;
+	let mut rest = Vec::new();
+	for arg in args {
+		let syn::NestedMeta::Meta(syn::Meta::NameValue(meta)) = &arg else {
+			rest.push(arg);
+			continue;
+		};
+		match (
+			meta.path.get_ident().map(ToString::to_string).as_deref(),
+			&meta.lit,
+		) {
+			(Some("filter"), syn::Lit::Str(lit)) => filter = Some(lit.value()),
+			(Some("format"), syn::Lit::Str(lit)) => {
+				format = match lit.value().as_str() {
+					"full" => quote::quote!(Full),
+					"pretty" => quote::quote!(Pretty),
+					"compact" => quote::quote!(Compact),
+					"json" => quote::quote!(Json),
+					other => {
+						util::diagnostic::emit_error(
+							lit.span(),
+							format!("unknown log format `{other}` (expected `full`, `pretty`, `compact` or `json`)"),
+						);
+						quote::quote!(Full)
+					}
+				}
+			}
+			(Some("subscriber"), syn::Lit::Bool(lit)) => subscriber = lit.value,
+			(Some("filter" | "format"), lit) => {
+				util::diagnostic::emit_error(lit.span(), "expected string literal");
+			}
+			(Some("subscriber"), lit) => {
+				util::diagnostic::emit_error(lit.span(), "expected `true` or `false`");
+			}
+			_ => rest.push(arg),
+		}
+	}
+	if !subscriber {
+		return (TokenStream2::new(), rest);
+	}
+	let filter = filter.map_or_else(
+		|| quote::quote!(::micro_tower::telemetry::DEFAULT_FILTER),
+		|filter| quote::quote!(#filter),
+	);
+	let telemetry = quote::quote!(
+		if let Err(err) = ::micro_tower::telemetry::init(#filter, ::micro_tower::telemetry::Format::#format) {
+			::std::eprintln!("{}", ::micro_tower::report!(err));
+			::std::process::exit(1);
+		}
+	);
+	(telemetry, rest)
+}
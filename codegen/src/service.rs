@@ -10,21 +10,33 @@ pub struct Service {
 	vis: syn::Visibility,
 	asyncness: Option<syn::token::Async>,
 	buffer_size: syn::LitInt,
-	pool_size: Option<syn::LitInt>,
+	/// Minimum and maximum number of pool members.
+	pool_size: Option<(usize, usize)>,
+	balance: Option<args::Balance>,
+	eject: Option<args::Eject>,
+	on_start: Option<syn::Path>,
+	on_stop: Option<syn::Path>,
 	// Names
 	name: syn::Ident,
 	name_str: syn::LitStr,
 	name_builder: syn::Ident,
+	name_client: syn::Ident,
 	// Request/Response
 	request_arg: syn::PatType,
 	output: syn::ReturnType,
 	response: syn::Type,
 	failable: bool,
+	streaming: bool,
+	request_streaming: bool,
 	// Services
 	inner_srv: Vec<syn::Ident>,
 	inner_srv_m: Vec<Option<syn::token::Mut>>,
 	inner_srv_b: Vec<syn::Ident>,
 	inner_srv_t: Vec<syn::Type>,
+	// Managed objects
+	managed: Vec<syn::Ident>,
+	managed_m: Vec<Option<syn::token::Mut>>,
+	managed_t: Vec<syn::Type>,
 	// Attributes
 	doc_attrs: Vec<syn::Attribute>,
 	// Block
@@ -47,6 +59,58 @@ fn default_pat_type() -> syn::PatType {
 	}
 }
 
+/// Returns the type `T` if `ty` is of the form `Result<T, ...>`.
+fn result_type(ty: &syn::Type) -> Option<syn::Type> {
+	let syn::Type::Path(p) = ty else {
+		return None;
+	};
+	p.path
+		.segments
+		.last()
+		.filter(|seg| seg.ident == "Result")
+		.and_then(|seg| match &seg.arguments {
+			syn::PathArguments::AngleBracketed(args) => args.args.first(),
+			_ => None,
+		})
+		.and_then(|arg| match arg {
+			syn::GenericArgument::Type(ty) => Some(ty.clone()),
+			_ => None,
+		})
+}
+
+/// Returns `true` if `ty` is a path whose last segment is named `ident` (e.g. `Managed<T>`).
+fn is_type(ty: &syn::Type, ident: &str) -> bool {
+	let syn::Type::Path(p) = ty else {
+		return false;
+	};
+	p.path.segments.last().is_some_and(|seg| seg.ident == ident)
+}
+
+/// Returns the item type `T` if `ty` is of the form `impl Stream<Item = T>`.
+fn stream_item_type(ty: &syn::Type) -> Option<syn::Type> {
+	let syn::Type::ImplTrait(it) = ty else {
+		return None;
+	};
+	it.bounds
+		.iter()
+		.filter_map(|bound| match bound {
+			syn::TypeParamBound::Trait(bound) => bound.path.segments.last(),
+			syn::TypeParamBound::Lifetime(_) => None,
+		})
+		.filter(|seg| seg.ident == "Stream")
+		.find_map(|seg| match &seg.arguments {
+			syn::PathArguments::AngleBracketed(args) => {
+				args.args.iter().find_map(|arg| match arg {
+					syn::GenericArgument::Binding(binding) if binding.ident == "Item" => {
+						Some(binding.ty.clone())
+					}
+					_ => None,
+				})
+			}
+			_ => None,
+		})
+}
+
 impl Service {
 	pub fn new(args: &args::Args, decl: syn::ItemFn) -> Self {
 		if let Some(tk) = decl.sig.constness {
@@ -98,98 +162,115 @@ impl Service {
 				syn::FnArg::Typed(ty) => Some(ty),
 			})
 			.collect::<Vec<_>>();
-		let request_arg = inputs.first().map_or_else(
+		let mut request_arg = inputs.first().map_or_else(
 			|| {
 				diagnostic::emit_error(decl.sig.paren_token.span, "Missing request parameter");
 				default_pat_type()
 			},
 			syn::PatType::clone,
 		);
-		let inner_srv = inputs
-			.iter()
+		let crate_path = args.crate_path();
+		let request_streaming = match stream_item_type(&request_arg.ty) {
+			Some(item) => {
+				request_arg.ty =
+					Box::new(syn::parse_quote!(#crate_path::api::stream::Requests<#item>));
+				true
+			}
+			None => false,
+		};
+		let (managed_args, srv_args): (Vec<_>, Vec<_>) = inputs
+			.into_iter()
 			.skip(1)
-			.filter_map(|arg| match arg.pat.as_ref() {
-				syn::Pat::Ident(id) => Some(id.ident.clone()),
-				syn::Pat::Wild(_) => Some(syn::parse_str("_").unwrap()),
-				_ => {
-					diagnostic::emit_error(arg.span(), "Invalid argument declaration");
-					None
-				}
-			})
-			.collect::<Vec<_>>();
+			.partition(|arg| is_type(&arg.ty, "Managed"));
+		let arg_ident = |arg: &syn::PatType| match arg.pat.as_ref() {
+			syn::Pat::Ident(id) => Some((id.ident.clone(), id.mutability)),
+			syn::Pat::Wild(_) => Some((syn::parse_str("_").unwrap(), None)),
+			_ => {
+				diagnostic::emit_error(arg.span(), "Invalid argument declaration");
+				None
+			}
+		};
+		let (inner_srv, inner_srv_m): (Vec<_>, Vec<_>) =
+			srv_args.iter().filter_map(arg_ident).unzip();
 		let inner_srv_b = inner_srv
 			.iter()
 			.map(|srv| syn::Ident::new(&format!("__borrowed_{srv}"), Span::call_site()))
 			.collect();
-		let inner_srv_m = inputs
-			.iter()
-			.skip(1)
-			.filter_map(|arg| match arg.pat.as_ref() {
-				syn::Pat::Ident(id) => Some(id.mutability),
-				syn::Pat::Wild(_) => Some(None),
-				_ => None,
-			})
+		let inner_srv_t = srv_args
+			.into_iter()
+			.map(|input| (*input.ty).clone())
 			.collect();
-		let inner_srv_t = inputs
+		let (managed, managed_m): (Vec<_>, Vec<_>) =
+			managed_args.iter().filter_map(arg_ident).unzip();
+		let managed_t = managed_args
 			.into_iter()
-			.skip(1)
 			.map(|input| (*input.ty).clone())
 			.collect();
 
 		let output = decl.sig.output;
-		let (failable, response) = match output {
+		let (streaming, ty) = match output {
 			syn::ReturnType::Default => (false, syn::parse_str("()").unwrap()),
-			syn::ReturnType::Type(_, ref ty) => match **ty {
-				syn::Type::Path(ref p) => {
-					if let Some(ty) = p
-						.path
-						.segments
-						.last()
-						.and_then(|seg| {
-							if seg.ident == "Result" {
-								return Some(seg);
-							}
-							None
-						})
-						.and_then(|seg| match &seg.arguments {
-							syn::PathArguments::AngleBracketed(args) => args.args.first(),
-							_ => None,
-						})
-						.and_then(|arg| match arg {
-							syn::GenericArgument::Type(ty) => Some(ty.clone()),
-							_ => None,
-						}) {
-						(true, ty)
-					} else {
-						(false, *ty.clone())
-					}
-				}
-				_ => (false, *ty.clone()),
+			syn::ReturnType::Type(_, ref ty) => match stream_item_type(ty) {
+				Some(item) => (true, item),
+				None => (false, *ty.clone()),
 			},
 		};
+		let (failable, response) = match result_type(&ty) {
+			Some(ty) => (true, ty),
+			None => (false, ty),
+		};
 
 		Self {
-			crate_path: args.crate_path(),
+			crate_path,
 			buffer_size: args.buffer_size(),
 			pool_size: args.pool_size(),
+			balance: args.balance(),
+			eject: args.eject(),
+			on_start: args.on_start(),
+			on_stop: args.on_stop(),
 			vis: decl.vis,
 			asyncness: decl.sig.asyncness,
 			name_builder: syn::Ident::new(&format!("{}Builder", decl.sig.ident), Span::call_site()),
+			name_client: syn::Ident::new(&format!("{}Client", decl.sig.ident), Span::call_site()),
 			name: decl.sig.ident,
 			name_str,
 			request_arg,
 			output,
 			response,
 			failable,
+			streaming,
+			request_streaming,
 			inner_srv,
 			inner_srv_b,
 			inner_srv_t,
 			inner_srv_m,
+			managed,
+			managed_m,
+			managed_t,
 			doc_attrs,
 			block: decl.block,
 		}
 	}
 
+	/// Returns the registry key and name of each inner service. Services of type `Service<S>` are
+	/// looked up by the name of `S`, all other inner services by argument name.
+	fn inner_srv_keys(&self) -> (Vec<TokenStream>, Vec<TokenStream>) {
+		let crate_path = &self.crate_path;
+		self.inner_srv
+			.iter()
+			.zip(&self.inner_srv_t)
+			.map(|(name, ty)| {
+				let key_name = if is_type(ty, "Service") {
+					quote::quote!(<#ty as #crate_path::service::Info>::name())
+				} else {
+					quote::quote!(::std::stringify!(#name))
+				};
+				let key = quote::quote!(#crate_path::runtime::registry::Key::<#ty>::new(#key_name));
+				(key, key_name)
+			})
+			.unzip()
+	}
+
 	pub fn gen_service_decl(&self) -> TokenStream {
 		let crate_path = &self.crate_path;
 		let name = &self.name;
@@ -198,10 +279,13 @@ impl Service {
 		let srv_names = &self.inner_srv;
 		let srv_names_b = &self.inner_srv_b;
 		let srv_ty = &self.inner_srv_t;
+		let managed = &self.managed;
+		let managed_t = &self.managed_t;
 		quote::quote!(
 			#[allow(non_camel_case_types)]
 			#vis struct #name {
 				#( #srv_names: #crate_path::util::borrow::Cell<#srv_ty>, )*
+				#( #managed: #managed_t, )*
 				#( #srv_names_b: Option<#crate_path::util::borrow::Borrowed<#srv_ty>> ),*
 			}
 
@@ -214,6 +298,19 @@ impl Service {
 		)
 	}
 
+	pub fn gen_client_decl(&self) -> TokenStream {
+		let crate_path = &self.crate_path;
+		let name = &self.name;
+		let name_client = &self.name_client;
+		let vis = &self.vis;
+		let doc = format!("Client to call [`{name}`] of a remote session.");
+		quote::quote!(
+			#[doc = #doc]
+			#[allow(non_camel_case_types)]
+			#vis type #name_client<C = #crate_path::api::codec::Json> = #crate_path::client::Client<#name, C>;
+		)
+	}
+
 	pub fn gen_service_builder(&self) -> TokenStream {
 		let crate_path = &self.crate_path;
 		let name = &self.name;
@@ -222,11 +319,14 @@ impl Service {
 		let srv_names = &self.inner_srv;
 		let srv_names_b = &self.inner_srv_b;
 		let srv_ty = &self.inner_srv_t;
+		let managed = &self.managed;
+		let managed_t = &self.managed_t;
 		quote::quote!(
 			#[derive(Default)]
 			#[allow(non_camel_case_types)]
 			#vis struct #name_builder {
-				#( #srv_names: Option<#srv_ty> ),*
+				#( #srv_names: Option<#srv_ty>, )*
+				#( #managed: Option<#managed_t> ),*
 			}
 
 			impl #name_builder {
@@ -238,6 +338,14 @@ impl Service {
 					}
 				)*
 
+				#(
+					#[must_use]
+					pub fn #managed(mut self, object: #managed_t) -> Self {
+						self.#managed = Some(object);
+						self
+					}
+				)*
+
 				#[must_use]
 				pub fn build(mut self) -> #name {
 					#(
@@ -246,9 +354,16 @@ impl Service {
 							None => panic!("service `{}` is not set for `{}`", ::std::stringify!(#srv_names), ::std::stringify!(#name))
 						};
 					)*
+					#(
+						let #managed = match self.#managed.take() {
+							Some(object) => object,
+							None => panic!("object `{}` is not set for `{}`", ::std::stringify!(#managed), ::std::stringify!(#name))
+						};
+					)*
 
 					#name {
 						#( #srv_names: #crate_path::util::borrow::Cell::new(#srv_names), )*
+						#( #managed, )*
 						#( #srv_names_b: None ),*
 					}
 				}
@@ -257,6 +372,9 @@ impl Service {
 	}
 
 	pub fn gen_service_block(&self) -> TokenStream {
+		if self.streaming {
+			return self.gen_stream_block();
+		}
 		let crate_path = &self.crate_path;
 		let name_str = &self.name_str;
 		let block = self.block.as_ref();
@@ -294,38 +412,204 @@ impl Service {
 		}
 	}
 
+	/// Same as [`Self::gen_service_block`] but for services which return `impl Stream`. The
+	/// returned stream is mapped to a stream of `Result`s and boxed.
+	pub fn gen_stream_block(&self) -> TokenStream {
+		let crate_path = &self.crate_path;
+		let name_str = &self.name_str;
+		let block = self.block.as_ref();
+		let response_ty = self.response_ty();
+		let item = if self.failable {
+			quote::quote!(Ok::<_, #crate_path::util::BoxError>(item?))
+		} else {
+			quote::quote!(Ok::<_, #crate_path::util::BoxError>(item))
+		};
+		if self.asyncness.is_some() {
+			quote::quote!(
+				use #crate_path::prelude::Instrument;
+				use #crate_path::export::futures::StreamExt;
+				let fut = async move {
+					let stream = async move #block.await;
+					let stream: #response_ty = Box::pin(stream.map(|item| #item));
+					Ok(stream)
+				};
+				let fut = fut.instrument(#crate_path::export::tracing::trace_span!(#name_str));
+				Box::pin(fut)
+			)
+		} else {
+			quote::quote!(
+				use #crate_path::export::futures::StreamExt;
+				Box::pin(async move {
+					let stream = #crate_path::export::tokio::task::spawn_blocking(move || {
+						let _span_ = #crate_path::export::tracing::trace_span!(#name_str).entered();
+						#block
+					}).await?;
+					let stream: #response_ty = Box::pin(stream.map(|item| #item));
+					Ok(stream)
+				})
+			)
+		}
+	}
+
+	/// Wraps the block generated by [`Self::gen_service_block`] to respond with a stream of a
+	/// single response. Used by services which receive a stream of requests but return a single
+	/// response.
+	pub fn gen_single_stream_block(&self) -> TokenStream {
+		let crate_path = &self.crate_path;
+		let response = &self.response;
+		let response_ty = self.response_ty();
+		let block = self.gen_service_block();
+		quote::quote!(
+			let fut: #crate_path::util::BoxFuture<
+				::std::result::Result<#response, #crate_path::util::BoxError>
+			> = { #block };
+			Box::pin(async move {
+				let result = fut.await;
+				let stream: #response_ty = Box::pin(#crate_path::export::futures::stream::once(async move { result }));
+				Ok(stream)
+			})
+		)
+	}
+
+	/// Returns the response type of the generated service. Streaming services and services which
+	/// receive a stream of requests respond with a boxed stream of results.
+	pub fn response_ty(&self) -> TokenStream {
+		let crate_path = &self.crate_path;
+		let response = &self.response;
+		if self.streaming || self.request_streaming {
+			quote::quote!(
+				#crate_path::util::BoxStream<::std::result::Result<#response, #crate_path::util::BoxError>>
+			)
+		} else {
+			quote::quote!(#response)
+		}
+	}
+
+	/// Returns the implementation of the lifecycle hooks `on_start` and `on_stop` of
+	/// `service::Create` which call the functions set by the corresponding attributes.
+	fn gen_lifecycle_hooks(&self) -> TokenStream {
+		let crate_path = &self.crate_path;
+		let hook = |name: &str, path: Option<&syn::Path>| {
+			let name = syn::Ident::new(name, Span::call_site());
+			path.map(|path| {
+				quote::quote!(
+					fn #name(
+						registry: ::std::sync::Arc<::std::sync::RwLock<#crate_path::runtime::registry::Type>>
+					) -> #crate_path::util::BoxFuture<::std::result::Result<(), #crate_path::util::BoxError>> {
+						Box::pin(async move { #path(registry).await.map_err(::std::convert::Into::into) })
+					}
+				)
+			})
+		};
+		let on_start = hook("on_start", self.on_start.as_ref());
+		let on_stop = hook("on_stop", self.on_stop.as_ref());
+		quote::quote!( #on_start #on_stop )
+	}
+
 	pub fn gen_create_impl(&self) -> TokenStream {
 		let crate_path = &self.crate_path;
 		let name = &self.name;
 		let srv_names = &self.inner_srv;
 		let srv_ty = &self.inner_srv_t;
+		let (srv_keys, _) = self.inner_srv_keys();
+		let managed = &self.managed;
+		let managed_t = &self.managed_t;
 		let buffer_size = &self.buffer_size;
+		let hooks = self.gen_lifecycle_hooks();
+		let get_managed = quote::quote!(
+			#(
+				let #managed: #managed_t = match registry.get::<_, #managed_t>(::std::stringify!(#managed))? {
+					Some(object) => object.clone(),
+					None => match registry.find::<#managed_t>() {
+						Some(object) => object.clone(),
+						None => Err(#crate_path::runtime::registry::Error::Missing(::std::stringify!(#managed).to_string()))?
+					}
+				};
+			)*
+		);
 
-		if let Some(pool_size) = self.pool_size.as_ref() {
-			quote::quote!(
-				impl #crate_path::service::Create for #name {
-					type Error = ::std::convert::Infallible;
+		let pool_size = self.pool_size.as_ref().map_or_else(
+			|| quote::quote!(::std::option::Option::None),
+			|(min, max)| {
+				quote::quote!(::std::option::Option::Some(#crate_path::service::pool::Size { min: #min, max: #max }))
+			},
+		);
+		let strategy = match &self.balance {
+			None | Some(args::Balance::PowerOfTwoChoices) => quote::quote!(PowerOfTwoChoices),
+			Some(args::Balance::RoundRobin) => quote::quote!(RoundRobin),
+			Some(args::Balance::LeastPending) => quote::quote!(LeastPending),
+			Some(args::Balance::Hash(None)) => quote::quote!(hashed_request()),
+			Some(args::Balance::Hash(Some(key))) => quote::quote!(hashed(#key)),
+		};
+		let ejection = match &self.eject {
+			None | Some(args::Eject::Unready) => quote::quote!(::std::option::Option::Some(
+				#crate_path::service::pool::Ejection::unready()
+			)),
+			Some(args::Eject::Errors) => quote::quote!(::std::option::Option::Some(
+				<#crate_path::service::pool::Ejection as ::std::default::Default>::default()
+			)),
+			Some(args::Eject::Never) => quote::quote!(::std::option::Option::None),
+		};
+		quote::quote!(
+			impl #crate_path::service::Create for #name {
+				type Error = #crate_path::runtime::registry::Error;
 
-					fn with_registry(
-						registry: ::std::sync::Arc<::std::sync::RwLock<#crate_path::runtime::registry::Type>>
-					) -> ::std::result::Result<::std::option::Option<#crate_path::service::Service<Self>>, Self::Error> {
-						use #crate_path::prelude::{ServiceBuilderExt, ServicePoolBuilderExt};
+				fn with_registry(
+					registry: ::std::sync::Arc<::std::sync::RwLock<#crate_path::runtime::registry::Type>>
+				) -> ::std::result::Result<::std::option::Option<#crate_path::service::Service<Self>>, Self::Error> {
+					use #crate_path::prelude::ServiceBuilderExt;
+					let name = <Self as #crate_path::service::Info>::name();
+					let (watch, monitor) = {
+						let registry = registry
+							.read()
+							.map_err(|_| #crate_path::runtime::registry::Error::Poisoned)?;
+						let watch = registry.get_key(&#crate_path::runtime::config::KEY)?.cloned();
+						let monitor = registry.get_key(&#crate_path::runtime::health::KEY)?.cloned();
+						(watch, monitor)
+					};
+					let config = watch
+						.as_ref()
+						.map(|watch| watch.service(name))
+						.unwrap_or_default();
+					let buffer_size: usize = config.buffer.unwrap_or(#buffer_size);
+					let timeout = config.timeout.map(#crate_path::export::tower::timeout::TimeoutLayer::new);
+
+					if let Some(pool_size) = config.pool.or(#pool_size) {
+						let pool = #crate_path::service::pool::Layer::<_, Self::Request>::with_size(pool_size, registry)
+							.named(name)
+							.strategy(#crate_path::service::pool::Strategy::#strategy)
+							.ejection(#ejection);
+						let pool = match monitor {
+							Some(monitor) => pool.monitored(monitor),
+							None => pool,
+						};
+						let pool = match watch {
+							Some(watch) => pool.configured(watch, #pool_size.unwrap_or(pool_size)),
+							None => pool,
+						};
 						let service = #crate_path::ServiceBuilder::new()
 							.boxed_future()
-							.buffer(#buffer_size)
-							.pooled::<_, Self::Request>(#pool_size, registry)
-							.service_fn(|registry| async move {
+							.layer(#crate_path::metrics::Layer::new(name))
+							.option_layer(timeout)
+							.buffer(buffer_size)
+							.layer(pool)
+							.service_fn(move |registry: ::std::sync::Arc<::std::sync::RwLock<#crate_path::runtime::registry::Type>>| async move {
+								let registry = registry
+									.read()
+									.map_err(|_| #crate_path::runtime::registry::Error::Poisoned)?;
 								#(
-									let #srv_names: #srv_ty = match registry.get::<_, #srv_ty>(::std::stringify!(#srv_names))? {
+									let #srv_names: #srv_ty = match registry.get_key(&#srv_keys)? {
 										Some(srv) => srv.clone(),
 										None => Err(#crate_path::service::NotReady(::std::stringify!(#srv_names)))?
 									};
 								)*
+								#get_managed
 								let service = Self::builder()
 									#( .#srv_names(#srv_names) )*
+									#( .#managed(#managed) )*
 									.build();
 								let service = #crate_path::ServiceBuilder::new()
-									.buffer(#buffer_size)
+									.buffer(buffer_size)
 									.service(service);
 								let service = #crate_path::service::load::PendingRequests::new(
 									service,
@@ -333,38 +617,35 @@ impl Service {
 								);
 								Ok::<_, #crate_path::util::BoxError>(service)
 							});
-						Ok(Some(#crate_path::service::Service::from(Box::new(service))))
+						return Ok(Some(#crate_path::service::Service::from(Box::new(service))));
 					}
-				}
-			)
-		} else {
-			quote::quote!(
-				impl #crate_path::service::Create for #name {
-					type Error = #crate_path::runtime::registry::Error;
 
-					fn with_registry(
-						registry: ::std::sync::Arc<::std::sync::RwLock<#crate_path::runtime::registry::Type>>
-					) -> ::std::result::Result<::std::option::Option<#crate_path::service::Service<Self>>, Self::Error> {
-						use #crate_path::prelude::ServiceBuilderExt;
-						let registry = registry.read().unwrap();
-						#(
-							let #srv_names: #srv_ty = match registry.get::<_, #srv_ty>(::std::stringify!(#srv_names))? {
-								Some(srv) => srv.clone(),
-								None => return Ok(None)
-							};
-						)*
-						let service = Self::builder()
-							#( .#srv_names(#srv_names) )*
-							.build();
-						let service = #crate_path::ServiceBuilder::new()
-							.boxed_future()
-							.buffer(#buffer_size)
-							.service(service);
-						Ok(Some(#crate_path::service::Service::from(Box::new(service))))
-					}
+					let registry = registry
+						.read()
+						.map_err(|_| #crate_path::runtime::registry::Error::Poisoned)?;
+					#(
+						let #srv_names: #srv_ty = match registry.get_key(&#srv_keys)? {
+							Some(srv) => srv.clone(),
+							None => return Ok(None)
+						};
+					)*
+					#get_managed
+					let service = Self::builder()
+						#( .#srv_names(#srv_names) )*
+						#( .#managed(#managed) )*
+						.build();
+					let service = #crate_path::ServiceBuilder::new()
+						.boxed_future()
+						.layer(#crate_path::metrics::Layer::new(name))
+						.option_layer(timeout)
+						.buffer(buffer_size)
+						.service(service);
+					Ok(Some(#crate_path::service::Service::from(Box::new(service))))
 				}
-			)
-		}
+
+				#hooks
+			}
+		)
 	}
 
 	pub fn gen_service_impl(&self) -> TokenStream {
@@ -374,11 +655,18 @@ impl Service {
 		let docs = &self.doc_attrs;
 		let request_ty = self.request_arg.ty.as_ref();
 		let request_arg = &self.request_arg;
-		let response_ty = &self.response;
+		let response_ty = self.response_ty();
 		let srv_names_b = &self.inner_srv_b;
 		let srv_names = &self.inner_srv;
 		let srv_mut = &self.inner_srv_m;
-		let block = self.gen_service_block();
+		let managed = &self.managed;
+		let managed_m = &self.managed_m;
+		let (_, srv_deps) = self.inner_srv_keys();
+		let block = if self.request_streaming && !self.streaming {
+			self.gen_single_stream_block()
+		} else {
+			self.gen_service_block()
+		};
 		quote::quote!(
 			#( #docs )*
 			impl #crate_path::Service<#request_ty> for #name {
@@ -408,6 +696,7 @@ impl Service {
 							})
 						};
 					)*
+					#( let #managed_m #managed = ::std::clone::Clone::clone(&self.#managed); )*
 					#block
 				}
 			}
@@ -419,6 +708,10 @@ impl Service {
 				fn name() -> &'static str {
 					#name_str
 				}
+
+				fn dependencies() -> ::std::vec::Vec<&'static str> {
+					::std::vec![ #( #srv_deps ),* ]
+				}
 			}
 		)
 	}
@@ -428,10 +721,12 @@ impl Service {
 		let builder = self.gen_service_builder();
 		let create_impl = self.gen_create_impl();
 		let service_impl = self.gen_service_impl();
+		let client = self.gen_client_decl();
 
 		quote::quote!(
 			#decl
 			#builder
+			#client
 			#create_impl
 
 			#service_impl
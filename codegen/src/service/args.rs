@@ -1,6 +1,25 @@
 use crate::util::diagnostic;
 use darling::FromMeta;
 use quote::__private::Span;
+use syn::spanned::Spanned;
+
+/// Strategy to balance requests between the members of a pool.
+pub enum Balance {
+	PowerOfTwoChoices,
+	RoundRobin,
+	LeastPending,
+	/// Consistent hashing on the key returned by the function at the path or the whole request.
+	Hash(Option<syn::Path>),
+}
+
+/// When members of a pool are ejected.
+pub enum Eject {
+	/// Only if their `poll_ready` failed.
+	Unready,
+	/// If their `poll_ready` or requests failed.
+	Errors,
+	Never,
+}
 
 #[derive(FromMeta)]
 pub struct Args {
@@ -10,7 +29,12 @@ pub struct Args {
 	#[darling(rename = "buffer")]
 	buffer_size: syn::LitInt,
 	#[darling(rename = "pool")]
-	pool_size: Option<syn::LitInt>,
+	pool_size: Option<syn::Lit>,
+	balance: Option<syn::LitStr>,
+	hash_key: Option<syn::Path>,
+	eject: Option<syn::LitStr>,
+	on_start: Option<syn::Path>,
+	on_stop: Option<syn::Path>,
 }
 
 impl Args {
@@ -33,9 +57,85 @@ impl Args {
 		self.buffer_size.clone()
 	}
 
-	/// Returns a literal of `self.pool_size`
-	pub fn pool_size(&self) -> Option<syn::LitInt> {
-		self.pool_size.clone()
+	/// Returns the minimum and maximum pool size. Accepts an integer (e.g. `pool = 4`) or a range
+	/// (e.g. `pool = "2..16"`).
+	pub fn pool_size(&self) -> Option<(usize, usize)> {
+		let (span, size) = match self.pool_size.as_ref()? {
+			syn::Lit::Int(size) => (size.span(), size.base10_digits().to_string()),
+			syn::Lit::Str(size) => (size.span(), size.value()),
+			lit => {
+				diagnostic::emit_error(lit.span(), "expected integer or string literal");
+				return None;
+			}
+		};
+		let parse = |size: &str| size.trim().parse::<usize>().ok();
+		let bounds = match size.split_once("..") {
+			Some((min, max)) => parse(min).zip(parse(max.strip_prefix('=').unwrap_or(max))),
+			None => parse(&size).map(|size| (size, size)),
+		};
+		match bounds {
+			Some((min, max)) if min > 0 && min <= max => Some((min, max)),
+			_ => {
+				diagnostic::emit_error(
+					span,
+					"expected pool size `<size>` or `\"<min>..<max>\"` with 0 < min <= max",
+				);
+				None
+			}
+		}
+	}
+
+	/// Returns the strategy to balance requests between pool members. Accepts `"p2c"`,
+	/// `"round_robin"`, `"least_pending"` or `"hash"` (optionally with `hash_key`).
+	pub fn balance(&self) -> Option<Balance> {
+		let value = self.balance.as_ref().map(syn::LitStr::value);
+		if let (Some(path), false) = (&self.hash_key, value.as_deref() == Some("hash")) {
+			diagnostic::emit_error(path.span(), "`hash_key` requires `balance = \"hash\"`");
+		}
+		let balance = self.balance.as_ref()?;
+		if self.pool_size.is_none() {
+			diagnostic::emit_error(balance.span(), "`balance` requires `pool`");
+		}
+		match value.as_deref() {
+			Some("p2c") => Some(Balance::PowerOfTwoChoices),
+			Some("round_robin") => Some(Balance::RoundRobin),
+			Some("least_pending") => Some(Balance::LeastPending),
+			Some("hash") => Some(Balance::Hash(self.hash_key.clone())),
+			_ => {
+				diagnostic::emit_error(
+					balance.span(),
+					"expected `p2c`, `round_robin`, `least_pending` or `hash`",
+				);
+				None
+			}
+		}
+	}
+
+	/// Returns when members of a pool are ejected. Accepts `"unready"`, `"errors"` or `"never"`.
+	pub fn eject(&self) -> Option<Eject> {
+		let eject = self.eject.as_ref()?;
+		if self.pool_size.is_none() {
+			diagnostic::emit_error(eject.span(), "`eject` requires `pool`");
+		}
+		match eject.value().as_str() {
+			"unready" => Some(Eject::Unready),
+			"errors" => Some(Eject::Errors),
+			"never" => Some(Eject::Never),
+			_ => {
+				diagnostic::emit_error(eject.span(), "expected `unready`, `errors` or `never`");
+				None
+			}
+		}
+	}
+
+	/// Returns the path of the function called at service startup.
+	pub fn on_start(&self) -> Option<syn::Path> {
+		self.on_start.clone()
+	}
+
+	/// Returns the path of the function called at service shutdown.
+	pub fn on_stop(&self) -> Option<syn::Path> {
+		self.on_stop.clone()
 	}
 
 	/// Will return the service name as string literal. If option `name` is set will return this
@@ -11,7 +11,7 @@ pub async fn parse_str(request: String) -> Result<i32, ParseIntError> {
 	request.parse()
 }
 
-#[micro_tower::codegen::main]
+#[micro_tower::codegen::main(filter = "info,micro_tower=debug")]
 async fn tower() -> _ {
 	let addr = "127.0.0.1:4000".parse().unwrap();
 	let session = tcp::Session::<codec::Json, _>::with_addr(addr)
@@ -22,4 +22,5 @@ async fn tower() -> _ {
 		.bind_service::<parse_str, _>(session)
 		.build()
 		.await
+		.expect("failed to build runtime")
 }
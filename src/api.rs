@@ -1,15 +1,35 @@
 //! Utilities to translate request and replies.
+//!
+//! # Protocol
+//!
+//! Sessions (see [`crate::session`]) exchange newline terminated frames. A request frame holds
+//! the encoded request, optionally preceded by a trace context line (see [`TRACEPARENT`]). The
+//! reply is a single frame holding an encoded [`Message`], or a sequence of frames closed by
+//! [`Message::End`] for streaming services (see [`stream`]). Encoded messages must therefore not
+//! contain line breaks (e.g. [`codec::Json`] writes compact json).
+//!
+//! Earlier versions neither terminated replies nor requests and told requests apart by the size
+//! of a single read. Clients must terminate each request with a newline and read replies up to
+//! the next newline.
 
 use serde::{Deserialize, Serialize};
 
 pub mod codec;
 pub mod layer;
 pub mod service;
+pub mod stream;
 
+use crate::telemetry::Context;
 use crate::util::BoxError;
+use bytes::{BufMut, BytesMut};
 pub use layer::Layer;
 pub use service::Service;
 
+/// Prefix of the optional first line of a request frame which carries the trace context of the
+/// caller (e.g. `traceparent: 00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01\n`). The
+/// encoded request follows after the line break.
+pub const TRACEPARENT: &[u8] = b"traceparent: ";
+
 /// TODO
 #[derive(Deserialize, Serialize)]
 #[serde(tag = "type")]
@@ -20,6 +40,9 @@ pub enum Message<T> {
 	BadRequest,
 	#[serde(rename = "500")]
 	InternalServerError,
+	/// Marks the end of a stream of responses (see [`stream::Service`]).
+	#[serde(rename = "end")]
+	End,
 }
 
 /// TODO
@@ -40,3 +63,34 @@ impl std::error::Error for Error {
 		Some(self.err.as_ref())
 	}
 }
+
+/// Writes the trace context header of `context` to request frame `buf`. Must be called before
+/// the request is encoded.
+pub fn write_traceparent(buf: &mut BytesMut, context: &Context) {
+	buf.put_slice(TRACEPARENT);
+	buf.put_slice(context.to_string().as_bytes());
+	buf.put_u8(b'\n');
+}
+
+/// Removes the trace context header from request frame `buf` and returns a span which continues
+/// the trace of the caller (see [`crate::telemetry::propagation`]). Returns a disabled span if
+/// `buf` has no valid header.
+pub(crate) fn request_span(buf: &mut BytesMut) -> tracing::Span {
+	if !buf.starts_with(TRACEPARENT) {
+		return tracing::Span::none();
+	}
+	let end = buf
+		.iter()
+		.position(|b| *b == b'\n')
+		.map_or(buf.len(), |i| i + 1);
+	let line = buf.split_to(end);
+	let value = String::from_utf8_lossy(&line[TRACEPARENT.len()..]);
+	match value.trim().parse::<Context>() {
+		Ok(context) => tracing::info_span!("request", traceparent = %context),
+		Err(err) => {
+			let report = crate::report!(err);
+			tracing::warn!("{report:?}");
+			tracing::Span::none()
+		}
+	}
+}
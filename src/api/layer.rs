@@ -4,12 +4,28 @@ use std::marker::PhantomData;
 /// Creates a layer which wraps a given service inside an api translation layer (see [`Service`]).
 /// The api layer will translate requests of type `R` with codec `C`.
 pub struct Layer<R, C = super::codec::Json> {
+	name: Option<&'static str>,
 	_p: PhantomData<(R, C)>,
 }
 
 impl<R, C> Default for Layer<R, C> {
 	fn default() -> Self {
-		Self { _p: PhantomData }
+		Self {
+			name: None,
+			_p: PhantomData,
+		}
+	}
+}
+
+impl<R, C> Layer<R, C> {
+	/// Same as [`Layer::default`] but counts bad requests to service `name` (see
+	/// [`crate::metrics`]).
+	#[must_use]
+	pub fn named(name: &'static str) -> Self {
+		Self {
+			name: Some(name),
+			_p: PhantomData,
+		}
 	}
 }
 
@@ -17,6 +33,10 @@ impl<R, C, S> tower::Layer<S> for Layer<R, C> {
 	type Service = Service<R, C, S>;
 
 	fn layer(&self, inner: S) -> Self::Service {
-		Service::from_service(inner)
+		let service = Service::from_service(inner);
+		match self.name {
+			Some(name) => service.with_bad_requests(crate::metrics::errors(name, "400")),
+			None => service,
+		}
 	}
 }
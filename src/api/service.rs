@@ -1,13 +1,16 @@
 use super::codec::{Decode, Encode};
 use super::{Error, Message};
+use crate::metrics::Counter;
 use crate::util::{BoxError, BoxFuture};
 use bytes::{Buf, BufMut, BytesMut};
 use std::marker::PhantomData;
 use std::task::{Context, Poll};
+use tracing::Instrument;
 
 /// API service which translates bytes to requests of type `T` and response to bytes.
 pub struct Service<R, C, S> {
 	inner: S,
+	bad_requests: Option<Counter>,
 	_p: PhantomData<(C, R)>,
 }
 
@@ -19,9 +22,17 @@ impl<R, C, S> Service<R, C, S> {
 	pub fn from_service(inner: S) -> Self {
 		Self {
 			inner,
+			bad_requests: None,
 			_p: PhantomData,
 		}
 	}
+
+	/// Increment `counter` for each request which failed to be decoded.
+	#[must_use]
+	pub fn with_bad_requests(mut self, counter: Counter) -> Self {
+		self.bad_requests = Some(counter);
+		self
+	}
 }
 
 impl<R, C, S> tower::Service<BytesMut> for Service<R, C, S>
@@ -44,9 +55,11 @@ where
 		}
 	}
 
-	fn call(&mut self, buf: BytesMut) -> Self::Future {
+	fn call(&mut self, mut buf: BytesMut) -> Self::Future {
+		let span = super::request_span(&mut buf);
+		let _guard = span.enter();
 		let mut reader = buf.reader();
-		match C::decode(&mut reader) {
+		let fut: Self::Future = match C::decode(&mut reader) {
 			Ok(request) => {
 				let buf = reader.into_inner();
 				let fut = self.inner.call(request);
@@ -78,6 +91,9 @@ where
 				})
 			}
 			Err(err) => {
+				if let Some(counter) = &self.bad_requests {
+					counter.inc();
+				}
 				let message = Message::BadRequest;
 				let mut buf = reader.into_inner();
 				buf.clear();
@@ -90,7 +106,8 @@ where
 				};
 				Box::pin(async move { Err(err) })
 			}
-		}
+		};
+		Box::pin(fut.instrument(span.clone()))
 	}
 }
 
@@ -98,6 +115,7 @@ impl<R, C, S: Clone> Clone for Service<R, C, S> {
 	fn clone(&self) -> Self {
 		Self {
 			inner: self.inner.clone(),
+			bad_requests: self.bad_requests.clone(),
 			_p: PhantomData,
 		}
 	}
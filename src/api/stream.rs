@@ -0,0 +1,338 @@
+//! API translation for services which receive or reply with a stream of messages.
+
+use super::codec::{Decode, Encode};
+use super::{Error, Message};
+use crate::metrics::Counter;
+use crate::util::{BoxError, BoxFuture, BoxStream};
+use bytes::{Buf, BufMut, BytesMut};
+use futures::{Stream, StreamExt};
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tracing::Instrument;
+
+/// Creates a layer which wraps a given service inside a streaming api translation layer (see
+/// [`Service`]). The api layer will translate requests of type `R` with codec `C`.
+pub struct Layer<R, C = super::codec::Json> {
+	name: Option<&'static str>,
+	_p: PhantomData<(R, C)>,
+}
+
+/// API service which translates bytes to requests of type `R` and the returned stream of
+/// responses to a stream of frames. Each response is encoded as [`Message::Ok`] and the stream is
+/// terminated by [`Message::End`]. A failed response is encoded as
+/// [`Message::InternalServerError`] and ends the stream.
+pub struct Service<R, C, S> {
+	inner: S,
+	bad_requests: Option<Counter>,
+	_p: PhantomData<(C, R)>,
+}
+
+impl<R, C> Default for Layer<R, C> {
+	fn default() -> Self {
+		Self {
+			name: None,
+			_p: PhantomData,
+		}
+	}
+}
+
+impl<R, C> Layer<R, C> {
+	/// Same as [`Layer::default`] but counts bad requests to service `name` (see
+	/// [`crate::metrics`]).
+	#[must_use]
+	pub fn named(name: &'static str) -> Self {
+		Self {
+			name: Some(name),
+			_p: PhantomData,
+		}
+	}
+}
+
+impl<R, C, S> tower::Layer<S> for Layer<R, C> {
+	type Service = Service<R, C, S>;
+
+	fn layer(&self, inner: S) -> Self::Service {
+		let service = Service::from_service(inner);
+		match self.name {
+			Some(name) => service.with_bad_requests(crate::metrics::errors(name, "400")),
+			None => service,
+		}
+	}
+}
+
+impl<R, C, S> Service<R, C, S> {
+	/// Creates new streaming api layer by wrapping inner service
+	///
+	/// # Parameters
+	/// - `inner` Service wrapped by API layer.
+	pub fn from_service(inner: S) -> Self {
+		Self {
+			inner,
+			bad_requests: None,
+			_p: PhantomData,
+		}
+	}
+
+	/// Increment `counter` for each request which failed to be decoded.
+	#[must_use]
+	pub fn with_bad_requests(mut self, counter: Counter) -> Self {
+		self.bad_requests = Some(counter);
+		self
+	}
+}
+
+/// Stream of requests received by a service (see [`DuplexService`]). Used as request type of
+/// client-streaming and bidirectional-streaming services.
+pub struct Requests<T> {
+	inner: BoxStream<T>,
+}
+
+/// Creates a layer which wraps a given service inside a bidirectional streaming api translation
+/// layer (see [`DuplexService`]). The api layer will translate requests of type `R` with codec
+/// `C`.
+pub struct DuplexLayer<R, C = super::codec::Json> {
+	_p: PhantomData<(R, C)>,
+}
+
+/// API service which translates a stream of frames (see [`Requests`]) to a stream of requests and
+/// the returned stream of responses to a stream of frames. Each request frame must be encoded as
+/// [`Message::Ok`] and the request stream is terminated by [`Message::End`]. Frames which cannot be
+/// decoded will terminate the request stream as well. Responses are encoded the same way as in
+/// [`Service`].
+pub struct DuplexService<R, C, S> {
+	inner: S,
+	_p: PhantomData<(C, R)>,
+}
+
+impl<T> Requests<T> {
+	/// Creates a new request stream from `stream`.
+	pub fn new(stream: impl Stream<Item = T> + Send + 'static) -> Self {
+		Self {
+			inner: Box::pin(stream),
+		}
+	}
+}
+
+impl<T> Stream for Requests<T> {
+	type Item = T;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		self.inner.poll_next_unpin(cx)
+	}
+}
+
+impl<R, C> Default for DuplexLayer<R, C> {
+	fn default() -> Self {
+		Self { _p: PhantomData }
+	}
+}
+
+impl<R, C, S> tower::Layer<S> for DuplexLayer<R, C> {
+	type Service = DuplexService<R, C, S>;
+
+	fn layer(&self, inner: S) -> Self::Service {
+		DuplexService::from_service(inner)
+	}
+}
+
+impl<R, C, S> DuplexService<R, C, S> {
+	/// Creates new bidirectional streaming api layer by wrapping inner service
+	///
+	/// # Parameters
+	/// - `inner` Service wrapped by API layer.
+	pub fn from_service(inner: S) -> Self {
+		Self {
+			inner,
+			_p: PhantomData,
+		}
+	}
+}
+
+/// Encodes `message` into a new frame.
+fn encode<C, T>(message: Message<T>) -> Result<BytesMut, C::Error>
+where
+	C: Encode<Message<T>>,
+{
+	let mut writer = BytesMut::new().writer();
+	C::encode(&mut writer, message)?;
+	Ok(writer.into_inner())
+}
+
+/// Encodes each response of `responses` into a separate frame and terminates the stream of frames
+/// with [`Message::End`]. A failed response will end the stream with
+/// [`Message::InternalServerError`].
+fn encode_responses<C, T>(responses: BoxStream<Result<T, BoxError>>) -> BoxStream<BytesMut>
+where
+	T: Send + 'static,
+	C: Encode<Message<T>> + 'static,
+	C::Error: std::error::Error + Send + Sync + 'static,
+{
+	let frames = futures::stream::unfold(Some(responses), |responses| async move {
+		let mut responses = responses?;
+		let message = match responses.next().await? {
+			Ok(data) => Message::Ok { data },
+			Err(err) => {
+				let report = crate::report!(err.as_ref());
+				tracing::error!("{report:?}");
+				let buf = encode::<C, T>(Message::InternalServerError).unwrap();
+				return Some((buf, None));
+			}
+		};
+		match encode::<C, T>(message) {
+			Ok(buf) => Some((buf, Some(responses))),
+			Err(err) => {
+				let report = crate::report!(err);
+				tracing::error!("{report:?}");
+				let buf = encode::<C, T>(Message::InternalServerError).unwrap();
+				Some((buf, None))
+			}
+		}
+	});
+	let end = futures::stream::once(async { encode::<C, T>(Message::End).unwrap() });
+	Box::pin(frames.chain(end))
+}
+
+/// Decodes each frame of `frames` as [`Message`] and returns the contained requests. The stream of
+/// requests ends with the first frame which is not [`Message::Ok`].
+fn decode_requests<C, R>(frames: Requests<BytesMut>) -> Requests<R>
+where
+	R: Send + 'static,
+	C: Decode<Message<R>> + 'static,
+	C::Error: std::error::Error + Send + Sync + 'static,
+{
+	let requests = frames.scan((), |(), frame| {
+		let request = match C::decode(&mut frame.reader()) {
+			Ok(Message::Ok { data }) => Some(data),
+			Ok(Message::End) => None,
+			Ok(_) => {
+				tracing::warn!("unexpected message in request stream");
+				None
+			}
+			Err(err) => {
+				let report = crate::report!(err);
+				tracing::error!("{report:?}");
+				None
+			}
+		};
+		futures::future::ready(request)
+	});
+	Requests::new(requests)
+}
+
+impl<R, C, S, T> tower::Service<BytesMut> for Service<R, C, S>
+where
+	S: tower::Service<R, Response = BoxStream<Result<T, BoxError>>, Error = BoxError>,
+	S::Future: Send + 'static,
+	T: Send + 'static,
+	C: Decode<R> + Encode<Message<T>> + 'static,
+	<C as Encode<Message<T>>>::Error: std::error::Error + Send + Sync + 'static,
+	<C as Decode<R>>::Error: Unpin + std::error::Error + Send + Sync + 'static,
+{
+	type Response = BoxStream<BytesMut>;
+	type Error = Error;
+	type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		match self.inner.poll_ready(cx) {
+			Poll::Ready(Ok(())) => Poll::Ready(Ok(())),
+			Poll::Ready(Err(err)) => {
+				let buf = encode::<C, T>(Message::InternalServerError).unwrap();
+				Poll::Ready(Err(Error { buf, err }))
+			}
+			Poll::Pending => Poll::Pending,
+		}
+	}
+
+	fn call(&mut self, mut buf: BytesMut) -> Self::Future {
+		let span = super::request_span(&mut buf);
+		let _guard = span.enter();
+		let mut reader = buf.reader();
+		let fut: Self::Future = match C::decode(&mut reader) {
+			Ok(request) => {
+				let fut = self.inner.call(request);
+				Box::pin(async move {
+					let responses = match fut.await {
+						Ok(responses) => responses,
+						Err(err) => {
+							let buf = encode::<C, T>(Message::InternalServerError).unwrap();
+							return Err(Error { buf, err });
+						}
+					};
+					Ok(encode_responses::<C, T>(responses))
+				})
+			}
+			Err(err) => {
+				if let Some(counter) = &self.bad_requests {
+					counter.inc();
+				}
+				let buf = encode::<C, T>(Message::BadRequest).unwrap();
+				let err = Error {
+					buf,
+					err: Box::new(err),
+				};
+				Box::pin(async move { Err(err) })
+			}
+		};
+		Box::pin(fut.instrument(span.clone()))
+	}
+}
+
+impl<R, C, S, T> tower::Service<Requests<BytesMut>> for DuplexService<R, C, S>
+where
+	S: tower::Service<Requests<R>, Response = BoxStream<Result<T, BoxError>>, Error = BoxError>,
+	S::Future: Send + 'static,
+	R: Send + 'static,
+	T: Send + 'static,
+	C: Decode<Message<R>> + Encode<Message<T>> + 'static,
+	<C as Encode<Message<T>>>::Error: std::error::Error + Send + Sync + 'static,
+	<C as Decode<Message<R>>>::Error: std::error::Error + Send + Sync + 'static,
+{
+	type Response = BoxStream<BytesMut>;
+	type Error = Error;
+	type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		match self.inner.poll_ready(cx) {
+			Poll::Ready(Ok(())) => Poll::Ready(Ok(())),
+			Poll::Ready(Err(err)) => {
+				let buf = encode::<C, T>(Message::InternalServerError).unwrap();
+				Poll::Ready(Err(Error { buf, err }))
+			}
+			Poll::Pending => Poll::Pending,
+		}
+	}
+
+	fn call(&mut self, frames: Requests<BytesMut>) -> Self::Future {
+		let requests = decode_requests::<C, R>(frames);
+		let fut = self.inner.call(requests);
+		Box::pin(async move {
+			match fut.await {
+				Ok(responses) => Ok(encode_responses::<C, T>(responses)),
+				Err(err) => {
+					let buf = encode::<C, T>(Message::InternalServerError).unwrap();
+					Err(Error { buf, err })
+				}
+			}
+		})
+	}
+}
+
+impl<R, C, S: Clone> Clone for Service<R, C, S> {
+	fn clone(&self) -> Self {
+		Self {
+			inner: self.inner.clone(),
+			bad_requests: self.bad_requests.clone(),
+			_p: PhantomData,
+		}
+	}
+}
+
+impl<R, C, S: Clone> Clone for DuplexService<R, C, S> {
+	fn clone(&self) -> Self {
+		Self {
+			inner: self.inner.clone(),
+			_p: PhantomData,
+		}
+	}
+}
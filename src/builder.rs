@@ -10,6 +10,14 @@ pub trait ServiceBuilderExt<L> {
 	/// [`crate::session::tcp::spawn`]).
 	fn api<R, C>(self) -> ServiceBuilder<Stack<api::Layer<R, C>, L>>;
 
+	/// Wrap service in [`api::stream::Layer`]. Same as [`ServiceBuilderExt::api`] but for services
+	/// which reply with a stream of responses.
+	fn api_stream<R, C>(self) -> ServiceBuilder<Stack<api::stream::Layer<R, C>, L>>;
+
+	/// Wrap service in [`api::stream::DuplexLayer`]. Same as [`ServiceBuilderExt::api`] but for
+	/// services which receive a stream of requests.
+	fn api_duplex<R, C>(self) -> ServiceBuilder<Stack<api::stream::DuplexLayer<R, C>, L>>;
+
 	/// Wrap service in [`layer::future::BoxLayer`].
 	fn boxed_future(self) -> ServiceBuilder<Stack<layer::future::BoxLayer, L>>;
 }
@@ -21,6 +29,14 @@ pub trait ServicePoolBuilderExt<L> {
 		count: usize,
 		target: T,
 	) -> ServiceBuilder<Stack<pool::Layer<T, Req>, L>>;
+
+	/// Same as [`ServicePoolBuilderExt::pooled`] but the pool balances requests by `strategy`.
+	fn pooled_with<T, Req>(
+		self,
+		count: usize,
+		target: T,
+		strategy: pool::Strategy<Req>,
+	) -> ServiceBuilder<Stack<pool::Layer<T, Req>, L>>;
 }
 
 impl<L> ServiceBuilderExt<L> for ServiceBuilder<L> {
@@ -28,6 +44,14 @@ impl<L> ServiceBuilderExt<L> for ServiceBuilder<L> {
 		self.layer(api::Layer::default())
 	}
 
+	fn api_stream<R, C>(self) -> ServiceBuilder<Stack<api::stream::Layer<R, C>, L>> {
+		self.layer(api::stream::Layer::default())
+	}
+
+	fn api_duplex<R, C>(self) -> ServiceBuilder<Stack<api::stream::DuplexLayer<R, C>, L>> {
+		self.layer(api::stream::DuplexLayer::default())
+	}
+
 	fn boxed_future(self) -> ServiceBuilder<Stack<layer::future::BoxLayer, L>> {
 		self.layer(layer::future::BoxLayer::default())
 	}
@@ -41,4 +65,13 @@ impl<L> ServicePoolBuilderExt<L> for ServiceBuilder<L> {
 	) -> ServiceBuilder<Stack<pool::Layer<T, Req>, L>> {
 		self.layer(pool::Layer::with_size(count, target))
 	}
+
+	fn pooled_with<T, Req>(
+		self,
+		count: usize,
+		target: T,
+		strategy: pool::Strategy<Req>,
+	) -> ServiceBuilder<Stack<pool::Layer<T, Req>, L>> {
+		self.layer(pool::Layer::with_size(count, target).strategy(strategy))
+	}
 }
@@ -0,0 +1,176 @@
+//! Clients to call services of remote sessions (e.g. [`crate::session::tcp::Session`]).
+
+pub mod pool;
+
+use crate::api::codec::{Decode, Encode};
+use crate::api::Message;
+use crate::service::Info;
+use crate::util::{BoxError, BoxFuture};
+use bytes::{Buf, BufMut, BytesMut};
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::{Mutex, MutexGuard};
+
+const BUF_SIZE: usize = 1024;
+/// Terminates each request and response frame of a session.
+const FRAME_DELIMITER: u8 = b'\n';
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error("remote service rejected request")]
+	BadRequest,
+	#[error("remote service failed")]
+	InternalServerError,
+	#[error("remote service sent unexpected message")]
+	Unexpected,
+	#[error("connection closed by remote service")]
+	Closed,
+}
+
+/// Client used to call service `S` of a remote tcp session. Requests are encoded and replies are
+/// decoded with codec `C`. The trace context of the calling span is sent along with each request
+/// (see [`crate::api::TRACEPARENT`]). The client holds a single connection which is established on first use
+/// and re-established after it failed or a call was cancelled before its reply was read. Calls of
+/// cloned clients share the same connection and are sent one after another.
+pub struct Client<S, C = crate::api::codec::Json> {
+	addr: SocketAddr,
+	stream: Arc<Mutex<Option<TcpStream>>>,
+	_p: PhantomData<fn() -> (S, C)>,
+}
+
+impl<S, C> Client<S, C> {
+	/// Creates a new client of the remote service at `addr`. Will connect on first call.
+	#[must_use]
+	pub fn new(addr: SocketAddr) -> Self {
+		Self {
+			addr,
+			stream: Arc::default(),
+			_p: PhantomData,
+		}
+	}
+
+	/// Creates a new client and connects to the remote service at `addr`.
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if failed to connect to `addr`.
+	pub async fn connect(addr: SocketAddr) -> std::io::Result<Self> {
+		let stream = TcpStream::connect(addr).await?;
+		Ok(Self {
+			addr,
+			stream: Arc::new(Mutex::new(Some(stream))),
+			_p: PhantomData,
+		})
+	}
+
+	/// Returns the address of the remote service.
+	#[must_use]
+	pub fn addr(&self) -> SocketAddr {
+		self.addr
+	}
+}
+
+impl<S, C> Clone for Client<S, C> {
+	fn clone(&self) -> Self {
+		Self {
+			addr: self.addr,
+			stream: Arc::clone(&self.stream),
+			_p: PhantomData,
+		}
+	}
+}
+
+/// Connection used by a single call. The connection is closed unless the call completed, so
+/// replies of cancelled calls are never read by the next call.
+struct InFlight<'a> {
+	stream: MutexGuard<'a, Option<TcpStream>>,
+	completed: bool,
+}
+
+impl Drop for InFlight<'_> {
+	fn drop(&mut self) {
+		if !self.completed {
+			self.stream.take();
+		}
+	}
+}
+
+/// Sends request frame `buf` to `stream` and reads the reply frame into `buf`.
+async fn send(stream: &mut TcpStream, buf: &mut BytesMut) -> Result<(), BoxError> {
+	buf.put_u8(FRAME_DELIMITER);
+	stream.write_all_buf(buf).await?;
+	buf.clear();
+	let mut local_buf = [0_u8; BUF_SIZE];
+	loop {
+		let num = stream.read(&mut local_buf).await?;
+		if num == 0 {
+			return Err(Error::Closed.into());
+		}
+		let start = buf.len();
+		buf.extend_from_slice(&local_buf[..num]);
+		if let Some(pos) = buf[start..].iter().position(|b| *b == FRAME_DELIMITER) {
+			buf.truncate(start + pos);
+			return Ok(());
+		}
+	}
+}
+
+impl<S, C> tower::Service<S::Request> for Client<S, C>
+where
+	S: Info + 'static,
+	S::Request: Send + 'static,
+	S::Response: Send + 'static,
+	C: Encode<S::Request> + Decode<Message<S::Response>> + 'static,
+	<C as Encode<S::Request>>::Error: std::error::Error + Send + Sync + 'static,
+	<C as Decode<Message<S::Response>>>::Error: std::error::Error + Send + Sync + 'static,
+{
+	type Response = S::Response;
+	type Error = BoxError;
+	type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+	fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn call(&mut self, request: S::Request) -> Self::Future {
+		let addr = self.addr;
+		let stream = Arc::clone(&self.stream);
+		let context = crate::telemetry::Context::current();
+		Box::pin(async move {
+			let mut buf = BytesMut::new();
+			if let Some(context) = context {
+				crate::api::write_traceparent(&mut buf, &context);
+			}
+			let mut writer = buf.writer();
+			C::encode(&mut writer, request)?;
+			let mut buf = writer.into_inner();
+
+			let mut call = InFlight {
+				stream: stream.lock().await,
+				completed: false,
+			};
+			if call.stream.is_none() {
+				tracing::debug!(
+					message = "connect to remote service",
+					addr = format!("{addr}")
+				);
+				*call.stream = Some(TcpStream::connect(addr).await?);
+			}
+			let stream = call.stream.as_mut().expect("connected");
+			send(stream, &mut buf).await?;
+			call.completed = true;
+			drop(call);
+
+			match C::decode(&mut buf.reader())? {
+				Message::Ok { data } => Ok(data),
+				Message::BadRequest => Err(Error::BadRequest.into()),
+				Message::InternalServerError => Err(Error::InternalServerError.into()),
+				Message::End => Err(Error::Unexpected.into()),
+			}
+		})
+	}
+}
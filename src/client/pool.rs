@@ -0,0 +1,408 @@
+//! Pools of clients to balance requests between replicas of a remote service.
+
+use super::{Client, Error as ClientError};
+use crate::service::{Health, Info, Status};
+use crate::util::{BoxError, BoxFuture};
+use futures::Stream;
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tower::balance::p2c::Balance;
+use tower::buffer::Buffer;
+use tower::discover::{Change, Discover};
+use tower::load::{CompleteOnResponse, PendingRequests};
+
+type Key = (SocketAddr, usize);
+type Replica<S, C> = PendingRequests<Endpoint<S, C>, CompleteOnResponse>;
+type ReplicaChange<S, C> = Change<Key, Replica<S, C>>;
+type Balanced<S, C> = Balance<Replicas<S, C>, <S as Info>::Request>;
+
+/// Configuration of a [`Pool`].
+#[derive(Clone, Debug)]
+pub struct Config {
+	/// Number of connections opened to each replica.
+	pub connections: usize,
+	/// Number of consecutive failed calls after which a replica is ejected from the pool.
+	pub max_failures: usize,
+	/// Duration an ejected replica is kept out of the pool before it is used again.
+	pub cooldown: Duration,
+	/// Number of requests which can wait for a ready replica.
+	pub buffer: usize,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Self {
+			connections: 2,
+			max_failures: 3,
+			cooldown: Duration::from_secs(5),
+			buffer: 64,
+		}
+	}
+}
+
+/// Used to add and remove replicas of a [`Pool`].
+pub struct Handle<S, C> {
+	config: Arc<Config>,
+	replicas: Arc<Mutex<HashSet<SocketAddr>>>,
+	ejected: Arc<Mutex<HashSet<SocketAddr>>>,
+	tx: UnboundedSender<ReplicaChange<S, C>>,
+}
+
+/// Client connection to a single replica. Counts consecutive failed calls and ejects the replica
+/// from the pool if too many calls failed.
+pub struct Endpoint<S, C> {
+	addr: SocketAddr,
+	client: Client<S, C>,
+	failures: Arc<AtomicUsize>,
+	handle: Handle<S, C>,
+}
+
+/// Stream of replica changes used to discover the services of a [`Pool`].
+pub struct Replicas<S, C> {
+	rx: UnboundedReceiver<ReplicaChange<S, C>>,
+}
+
+/// Balances requests between multiple replicas of remote service `S` using
+/// [`tower::balance::p2c`]. Replicas which failed to handle too many requests in a row are removed
+/// from the pool and added again after a cooldown (see [`Config`]). The last available replica is
+/// never ejected.
+pub struct Pool<S, C>
+where
+	S: Info,
+	Endpoint<S, C>: tower::Service<
+		S::Request,
+		Response = S::Response,
+		Error = BoxError,
+		Future = BoxFuture<Result<S::Response, BoxError>>,
+	>,
+{
+	inner: Buffer<Balanced<S, C>, S::Request>,
+	handle: Handle<S, C>,
+}
+
+impl<S, C> Clone for Handle<S, C> {
+	fn clone(&self) -> Self {
+		Self {
+			config: Arc::clone(&self.config),
+			replicas: Arc::clone(&self.replicas),
+			ejected: Arc::clone(&self.ejected),
+			tx: self.tx.clone(),
+		}
+	}
+}
+
+impl<S, C> Handle<S, C> {
+	/// Adds replica at `addr` to the pool. Does nothing if the replica is already part of the pool.
+	///
+	/// # Panics
+	///
+	/// Will panic if internal mutex failed to lock replica set.
+	pub fn insert(&self, addr: SocketAddr) {
+		if self.replicas.lock().unwrap().insert(addr) {
+			tracing::info!(message = "add replica", addr = format!("{addr}"));
+			self.connect(addr);
+		}
+	}
+
+	/// Removes replica at `addr` from the pool.
+	///
+	/// # Panics
+	///
+	/// Will panic if internal mutex failed to lock replica set.
+	pub fn remove(&self, addr: SocketAddr) {
+		if self.replicas.lock().unwrap().remove(&addr) {
+			tracing::info!(message = "remove replica", addr = format!("{addr}"));
+			self.ejected.lock().unwrap().remove(&addr);
+			self.disconnect(addr);
+		}
+	}
+
+	/// Returns the addresses of all replicas of the pool (including ejected replicas).
+	///
+	/// # Panics
+	///
+	/// Will panic if internal mutex failed to lock replica set.
+	#[must_use]
+	pub fn replicas(&self) -> Vec<SocketAddr> {
+		self.replicas.lock().unwrap().iter().copied().collect()
+	}
+
+	/// Returns the addresses of all replicas which are currently ejected from the pool.
+	///
+	/// # Panics
+	///
+	/// Will panic if internal mutex failed to lock replica set.
+	#[must_use]
+	pub fn ejected(&self) -> Vec<SocketAddr> {
+		self.ejected.lock().unwrap().iter().copied().collect()
+	}
+
+	/// Spawns a task which adds and removes replicas as reported by `discover` (e.g.
+	/// [`crate::discovery::Changes`]). Only the keys of discovered changes are used.
+	pub fn follow<D>(&self, discover: D)
+	where
+		S: 'static,
+		C: 'static,
+		D: Discover<Key = SocketAddr> + Send + 'static,
+		D::Error: Into<BoxError>,
+	{
+		let handle = self.clone();
+		tokio::spawn(async move {
+			let mut discover = Box::pin(discover);
+			while let Some(change) =
+				futures::future::poll_fn(|cx| discover.as_mut().poll_discover(cx)).await
+			{
+				match change {
+					Ok(Change::Insert(addr, _)) => handle.insert(addr),
+					Ok(Change::Remove(addr)) => handle.remove(addr),
+					Err(err) => {
+						let err: BoxError = err.into();
+						let report = crate::report!(err.as_ref());
+						tracing::error!("{report:?}");
+					}
+				}
+			}
+		});
+	}
+
+	fn connect(&self, addr: SocketAddr) {
+		let failures = Arc::new(AtomicUsize::new(0));
+		for i in 0..self.config.connections {
+			let endpoint = Endpoint {
+				addr,
+				client: Client::new(addr),
+				failures: Arc::clone(&failures),
+				handle: self.clone(),
+			};
+			let replica = PendingRequests::new(endpoint, CompleteOnResponse::default());
+			// Receiver is only dropped together with the pool.
+			let _ = self.tx.send(Change::Insert((addr, i), replica));
+		}
+	}
+
+	fn disconnect(&self, addr: SocketAddr) {
+		for i in 0..self.config.connections {
+			let _ = self.tx.send(Change::Remove((addr, i)));
+		}
+	}
+
+	/// Removes all connections to `addr` from the pool. The replica will be connected again after
+	/// cooldown if it was not removed in the meantime. The last available replica is never ejected
+	/// so the pool keeps trying instead of waiting for the cooldown. Will return `true` if the
+	/// replica was ejected.
+	fn eject(&self, addr: SocketAddr) -> bool
+	where
+		S: 'static,
+		C: 'static,
+	{
+		{
+			let replicas = self.replicas.lock().unwrap();
+			let mut ejected = self.ejected.lock().unwrap();
+			let available = replicas
+				.iter()
+				.filter(|replica| **replica != addr && !ejected.contains(*replica))
+				.count();
+			if available == 0 {
+				tracing::warn!(message = "keep last replica", addr = format!("{addr}"));
+				return false;
+			}
+			ejected.insert(addr);
+		}
+		tracing::warn!(
+			message = "eject replica",
+			addr = format!("{addr}"),
+			cooldown = format!("{:?}", self.config.cooldown)
+		);
+		self.disconnect(addr);
+		let handle = self.clone();
+		tokio::spawn(async move {
+			tokio::time::sleep(handle.config.cooldown).await;
+			handle.ejected.lock().unwrap().remove(&addr);
+			if handle.replicas.lock().unwrap().contains(&addr) {
+				tracing::info!(message = "reconnect replica", addr = format!("{addr}"));
+				handle.connect(addr);
+			}
+		});
+		true
+	}
+}
+
+/// A pool is healthy if all replicas are part of the pool, degraded if some replicas are ejected
+/// and unhealthy if no replica is available.
+impl<S, C> Health for Handle<S, C> {
+	fn health(&self) -> Status {
+		let replicas = self.replicas.lock().unwrap();
+		let ejected = self.ejected.lock().unwrap();
+		let available = replicas.difference(&ejected).count();
+		if available == 0 {
+			Status::Unhealthy
+		} else if available < replicas.len() {
+			Status::Degraded
+		} else {
+			Status::Healthy
+		}
+	}
+
+	fn members(&self) -> Option<usize> {
+		Some(self.replicas.lock().unwrap().len())
+	}
+}
+
+/// Returns `true` if `err` was caused by the replica or the connection to it and not by the
+/// request itself.
+fn is_failure(err: &BoxError) -> bool {
+	!matches!(
+		err.downcast_ref::<ClientError>(),
+		Some(ClientError::BadRequest | ClientError::InternalServerError)
+	)
+}
+
+impl<S, C> tower::Service<S::Request> for Endpoint<S, C>
+where
+	S: Info + 'static,
+	C: 'static,
+	Client<S, C>: tower::Service<
+		S::Request,
+		Response = S::Response,
+		Error = BoxError,
+		Future = BoxFuture<Result<S::Response, BoxError>>,
+	>,
+{
+	type Response = S::Response;
+	type Error = BoxError;
+	type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.client.poll_ready(cx)
+	}
+
+	fn call(&mut self, req: S::Request) -> Self::Future {
+		let fut = self.client.call(req);
+		let addr = self.addr;
+		let failures = Arc::clone(&self.failures);
+		let handle = self.handle.clone();
+		Box::pin(async move {
+			let result = fut.await;
+			match &result {
+				Err(err) if is_failure(err) => {
+					let count = failures.fetch_add(1, Ordering::SeqCst) + 1;
+					tracing::debug!(message = "replica failed", addr = format!("{addr}"), count);
+					if count == handle.config.max_failures && !handle.eject(addr) {
+						failures.store(0, Ordering::SeqCst);
+					}
+				}
+				_ => failures.store(0, Ordering::SeqCst),
+			}
+			result
+		})
+	}
+}
+
+impl<S, C> Stream for Replicas<S, C> {
+	type Item = Result<ReplicaChange<S, C>, Infallible>;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		self.rx.poll_recv(cx).map(|change| change.map(Ok))
+	}
+}
+
+impl<S, C> Pool<S, C>
+where
+	S: Info + Send + 'static,
+	S::Request: Send + 'static,
+	S::Response: Send + 'static,
+	C: Send + 'static,
+	Client<S, C>: tower::Service<
+		S::Request,
+		Response = S::Response,
+		Error = BoxError,
+		Future = BoxFuture<Result<S::Response, BoxError>>,
+	>,
+{
+	/// Creates a new pool of clients connected to the replicas at `addrs`. Must be called from
+	/// within a tokio runtime.
+	pub fn new(addrs: impl IntoIterator<Item = SocketAddr>, config: Config) -> Self {
+		let (tx, rx) = mpsc::unbounded_channel();
+		let buffer = config.buffer;
+		let handle = Handle {
+			config: Arc::new(config),
+			replicas: Arc::default(),
+			ejected: Arc::default(),
+			tx,
+		};
+		for addr in addrs {
+			handle.insert(addr);
+		}
+		let balance = Balance::new(Replicas { rx });
+		Self {
+			inner: Buffer::new(balance, buffer),
+			handle,
+		}
+	}
+}
+
+impl<S, C> Pool<S, C>
+where
+	S: Info,
+	Endpoint<S, C>: tower::Service<
+		S::Request,
+		Response = S::Response,
+		Error = BoxError,
+		Future = BoxFuture<Result<S::Response, BoxError>>,
+	>,
+{
+	/// Returns handle to add and remove replicas.
+	#[must_use]
+	pub fn handle(&self) -> &Handle<S, C> {
+		&self.handle
+	}
+}
+
+impl<S, C> Clone for Pool<S, C>
+where
+	S: Info,
+	Endpoint<S, C>: tower::Service<
+		S::Request,
+		Response = S::Response,
+		Error = BoxError,
+		Future = BoxFuture<Result<S::Response, BoxError>>,
+	>,
+{
+	fn clone(&self) -> Self {
+		Self {
+			inner: self.inner.clone(),
+			handle: self.handle.clone(),
+		}
+	}
+}
+
+impl<S, C> tower::Service<S::Request> for Pool<S, C>
+where
+	S: Info,
+	S::Response: Send + 'static,
+	Endpoint<S, C>: tower::Service<
+		S::Request,
+		Response = S::Response,
+		Error = BoxError,
+		Future = BoxFuture<Result<S::Response, BoxError>>,
+	>,
+{
+	type Response = S::Response;
+	type Error = BoxError;
+	type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.inner.poll_ready(cx)
+	}
+
+	fn call(&mut self, req: S::Request) -> Self::Future {
+		Box::pin(self.inner.call(req))
+	}
+}
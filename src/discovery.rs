@@ -0,0 +1,67 @@
+//! Sources to discover addresses of remote services (see [`file::Source`]).
+
+pub mod file;
+
+use crate::util::BoxStream;
+use futures::{Stream, StreamExt};
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::watch;
+use tower::discover::Change;
+
+/// Maps service names to the addresses of their replicas.
+pub type Services = HashMap<String, Vec<SocketAddr>>;
+
+type AddrChange = Change<SocketAddr, SocketAddr>;
+
+/// Implements [`tower::discover::Discover`] for the replicas of a remote service. Emits each new
+/// address and removes addresses which are no longer listed. Connecting to the replicas is left to
+/// the consumer (e.g. [`crate::client::pool::Handle::follow`]). The stream ends if the discovery
+/// source stopped.
+pub struct Changes {
+	inner: BoxStream<Result<AddrChange, Infallible>>,
+}
+
+impl Changes {
+	/// Creates a stream of changes of the addresses listed for service `name` in `services`.
+	#[must_use]
+	pub fn new(name: &'static str, services: watch::Receiver<Services>) -> Self {
+		let state = (services, HashSet::new(), true);
+		let changes =
+			futures::stream::unfold(state, move |(mut services, known, first)| async move {
+				if !first && services.changed().await.is_err() {
+					return None;
+				}
+				let addrs: HashSet<SocketAddr> = services
+					.borrow()
+					.get(name)
+					.map(|addrs| addrs.iter().copied().collect())
+					.unwrap_or_default();
+				let mut changes: Vec<_> = known
+					.difference(&addrs)
+					.map(|addr| Change::Remove(*addr))
+					.collect();
+				changes.extend(
+					addrs
+						.difference(&known)
+						.map(|addr| Change::Insert(*addr, *addr)),
+				);
+				tracing::debug!(message = "discovered replicas", name, count = addrs.len());
+				Some((futures::stream::iter(changes), (services, addrs, false)))
+			});
+		Self {
+			inner: Box::pin(changes.flatten().map(Ok)),
+		}
+	}
+}
+
+impl Stream for Changes {
+	type Item = Result<AddrChange, Infallible>;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		self.inner.as_mut().poll_next(cx)
+	}
+}
@@ -0,0 +1,194 @@
+//! Static service discovery using a TOML or JSON file which maps service names to addresses.
+//!
+//! ```toml
+//! # service = "<addr>" or service = ["<addr>", ...]
+//! parse = ["127.0.0.1:8080", "127.0.0.1:8081"]
+//! double = "127.0.0.1:9000"
+//! ```
+//!
+//! TOML files must have the same structure as JSON files.
+//! The file is polled for changes and reloaded if it was modified.
+
+use super::{Changes, Services};
+use crate::service::Info;
+use crate::shutdown::Controller;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error("failed to read discovery file")]
+	Io(
+		#[from]
+		#[source]
+		std::io::Error,
+	),
+	#[error("failed to decode discovery file")]
+	Json(
+		#[from]
+		#[source]
+		serde_json::Error,
+	),
+	#[error("failed to parse toml discovery file")]
+	Toml(
+		#[from]
+		#[source]
+		toml::de::Error,
+	),
+}
+
+/// Format of a discovery file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+	Json,
+	Toml,
+}
+
+/// Discovery source which reads service addresses from a file.
+pub struct Source {
+	path: PathBuf,
+	format: Format,
+	interval: Duration,
+}
+
+/// Handle to a watched discovery file. Can be used to create [`Changes`] for remote services.
+#[derive(Clone)]
+pub struct Watcher {
+	services: watch::Receiver<Services>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Addrs {
+	One(SocketAddr),
+	Many(Vec<SocketAddr>),
+}
+
+impl Source {
+	/// Creates a new source reading from `path`. The format is detected by the file extension
+	/// (`.toml` or JSON otherwise).
+	#[must_use]
+	pub fn new(path: impl Into<PathBuf>) -> Self {
+		let path = path.into();
+		let format = match path.extension() {
+			Some(ext) if ext == "toml" => Format::Toml,
+			_ => Format::Json,
+		};
+		Self {
+			path,
+			format,
+			interval: Duration::from_secs(1),
+		}
+	}
+
+	/// Overwrites the detected file format.
+	#[must_use]
+	pub fn with_format(mut self, format: Format) -> Self {
+		self.format = format;
+		self
+	}
+
+	/// Sets the interval to poll the file for changes. Defaults to one second.
+	#[must_use]
+	pub fn with_interval(mut self, interval: Duration) -> Self {
+		self.interval = interval;
+		self
+	}
+
+	/// Reads and parses the discovery file.
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if failed to read or parse the file.
+	pub async fn load(&self) -> Result<Services, Error> {
+		let content = tokio::fs::read_to_string(&self.path).await?;
+		let services: HashMap<String, Addrs> = match self.format {
+			Format::Json => serde_json::from_str(&content)?,
+			Format::Toml => toml::from_str(&content)?,
+		};
+		Ok(services
+			.into_iter()
+			.map(|(name, addrs)| match addrs {
+				Addrs::One(addr) => (name, vec![addr]),
+				Addrs::Many(addrs) => (name, addrs),
+			})
+			.collect())
+	}
+
+	/// Loads the discovery file and spawns a task which reloads the file if it was modified. The
+	/// task stops if shutdown is requested. Failed reloads are logged and the last loaded services
+	/// are kept.
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if failed to load the file initially.
+	pub async fn watch(self, controller: Controller) -> Result<Watcher, Error> {
+		let services = self.load().await?;
+		let mut modified = modified_at(&self.path).await;
+		tracing::info!(
+			message = "discovery file loaded",
+			path = format!("{}", self.path.display()),
+			services = services.len()
+		);
+		let (tx, rx) = watch::channel(services);
+		tokio::spawn(async move {
+			let mut interval = tokio::time::interval(self.interval);
+			loop {
+				tokio::select! {
+					_ = interval.tick() => {},
+					() = controller.wait_for_shutdown() => return,
+				}
+				let current = modified_at(&self.path).await;
+				if current == modified {
+					continue;
+				}
+				modified = current;
+				match self.load().await {
+					Ok(services) => {
+						if *tx.borrow() != services {
+							tracing::info!(
+								message = "discovery file reloaded",
+								path = format!("{}", self.path.display()),
+								services = services.len()
+							);
+							tx.send_replace(services);
+						}
+					}
+					Err(err) => {
+						let report = crate::report!(err);
+						tracing::error!("{report:?}");
+					}
+				}
+			}
+		});
+		Ok(Watcher { services: rx })
+	}
+}
+
+impl Watcher {
+	/// Returns the addresses currently listed for service `name`.
+	#[must_use]
+	pub fn addrs(&self, name: &str) -> Vec<SocketAddr> {
+		self.services
+			.borrow()
+			.get(name)
+			.cloned()
+			.unwrap_or_default()
+	}
+
+	/// Returns a [`tower::discover::Discover`] of the addresses of the replicas of remote service
+	/// `S`.
+	#[must_use]
+	pub fn discover<S: Info>(&self) -> Changes {
+		Changes::new(S::name(), self.services.clone())
+	}
+}
+
+async fn modified_at(path: &Path) -> Option<SystemTime> {
+	let metadata = tokio::fs::metadata(path).await.ok()?;
+	metadata.modified().ok()
+}
@@ -2,10 +2,14 @@
 
 pub mod api;
 pub mod builder;
+pub mod client;
+pub mod discovery;
 pub mod layer;
+pub mod metrics;
 pub mod runtime;
 pub mod session;
 pub mod shutdown;
+pub mod telemetry;
 pub mod util;
 
 pub mod prelude {
@@ -15,7 +19,7 @@ pub mod prelude {
 }
 
 pub mod export {
-	pub use {derive_builder, tokio, tracing, tracing_subscriber};
+	pub use {derive_builder, futures, tokio, tower, tracing, tracing_subscriber};
 }
 
 pub mod service;
@@ -0,0 +1,446 @@
+//! Collects metrics of services, sessions and pools and encodes them in the Prometheus text
+//! format (see <https://prometheus.io/docs/instrumenting/exposition_formats/>).
+//!
+//! All metrics are stored in a process wide registry (see [`global`]). Services generated with
+//! `#[service]` are wrapped in [`Layer`] automatically.
+//!
+//! # Metrics
+//!
+//! - `micro_tower_requests_total{service}`: Number of requests handled by a service.
+//! - `micro_tower_errors_total{service, message}`: Number of failed requests by the
+//!   [`crate::api::Message`] variant sent in response (`400` or `500`).
+//! - `micro_tower_request_duration_seconds{service}`: Latency histogram of a service.
+//! - `micro_tower_connections_accepted_total{endpoint}`: Number of accepted connections of a
+//!   session.
+//! - `micro_tower_connections_active{endpoint}`: Number of open connections of a session.
+//! - `micro_tower_pool_member_load{service, member}`: Pending requests of a service pool member.
+//!   The series is removed once the member is removed from the pool.
+//! - `micro_tower_pool_member_ejections_total{service}`: Number of failing service pool members
+//!   which were replaced (see [`crate::service::pool::Ejection`]).
+//! - `micro_tower_service_restarts_total{service}`: Number of restart attempts of a failed service
+//!   (see [`crate::runtime::supervisor`]).
+
+use crate::util::BoxFuture;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// Upper bounds of the latency histogram buckets in seconds.
+const BUCKETS: [f64; 11] = [
+	0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+type Labels = Vec<(&'static str, String)>;
+
+/// Monotonically increasing counter. Clones share the same value.
+#[derive(Clone, Debug, Default)]
+pub struct Counter {
+	value: Arc<AtomicU64>,
+}
+
+/// Value which can go up and down. Clones share the same value.
+#[derive(Clone, Debug, Default)]
+pub struct Gauge {
+	value: Arc<AtomicI64>,
+}
+
+/// Counts observed durations in buckets (see [`BUCKETS`]). Clones share the same values.
+#[derive(Clone, Debug, Default)]
+pub struct Histogram {
+	inner: Arc<HistogramInner>,
+}
+
+#[derive(Debug, Default)]
+struct HistogramInner {
+	buckets: [AtomicU64; BUCKETS.len()],
+	count: AtomicU64,
+	/// Sum of all observations in nanoseconds.
+	sum: AtomicU64,
+}
+
+#[derive(Clone)]
+enum Metric {
+	Counter(Counter),
+	Gauge(Gauge),
+	Histogram(Histogram),
+}
+
+struct Family {
+	help: &'static str,
+	series: BTreeMap<Labels, Metric>,
+}
+
+/// Stores all metrics by name and labels.
+#[derive(Default)]
+pub struct Registry {
+	families: Mutex<BTreeMap<&'static str, Family>>,
+}
+
+/// Wraps services to record request counts, errors and latency (see [`Service`]).
+#[derive(Clone, Debug)]
+pub struct Layer {
+	name: &'static str,
+}
+
+/// Records metrics of every request handled by the inner service. Failed requests are counted
+/// as [`crate::api::Message::InternalServerError`].
+#[derive(Clone, Debug)]
+pub struct Service<S> {
+	inner: S,
+	requests: Counter,
+	errors: Counter,
+	latency: Histogram,
+}
+
+/// Returns the process wide metrics registry.
+pub fn global() -> &'static Registry {
+	static REGISTRY: OnceLock<Registry> = OnceLock::new();
+	REGISTRY.get_or_init(Registry::default)
+}
+
+impl Counter {
+	pub fn inc(&self) {
+		self.value.fetch_add(1, Ordering::Relaxed);
+	}
+
+	#[must_use]
+	pub fn get(&self) -> u64 {
+		self.value.load(Ordering::Relaxed)
+	}
+}
+
+impl Gauge {
+	pub fn inc(&self) {
+		self.value.fetch_add(1, Ordering::Relaxed);
+	}
+
+	pub fn dec(&self) {
+		self.value.fetch_sub(1, Ordering::Relaxed);
+	}
+
+	#[must_use]
+	pub fn get(&self) -> i64 {
+		self.value.load(Ordering::Relaxed)
+	}
+}
+
+impl Histogram {
+	/// Records a single observation.
+	pub fn observe(&self, duration: Duration) {
+		let secs = duration.as_secs_f64();
+		if let Some(i) = BUCKETS.iter().position(|bound| secs <= *bound) {
+			self.inner.buckets[i].fetch_add(1, Ordering::Relaxed);
+		}
+		self.inner.count.fetch_add(1, Ordering::Relaxed);
+		let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+		self.inner.sum.fetch_add(nanos, Ordering::Relaxed);
+	}
+
+	/// Returns the number of observations.
+	#[must_use]
+	pub fn count(&self) -> u64 {
+		self.inner.count.load(Ordering::Relaxed)
+	}
+}
+
+impl Registry {
+	/// Returns the counter `name` with `labels`. Creates the counter if not exists.
+	///
+	/// # Panics
+	///
+	/// Will panic if `name` was already registered with another type or the internal mutex is
+	/// poisoned.
+	pub fn counter(
+		&self,
+		name: &'static str,
+		help: &'static str,
+		labels: &[(&'static str, &str)],
+	) -> Counter {
+		match self.metric(name, help, labels, || Metric::Counter(Counter::default())) {
+			Metric::Counter(counter) => counter,
+			_ => panic!("metric `{name}` is not a counter"),
+		}
+	}
+
+	/// Returns the gauge `name` with `labels`. Creates the gauge if not exists.
+	///
+	/// # Panics
+	///
+	/// Will panic if `name` was already registered with another type or the internal mutex is
+	/// poisoned.
+	pub fn gauge(
+		&self,
+		name: &'static str,
+		help: &'static str,
+		labels: &[(&'static str, &str)],
+	) -> Gauge {
+		match self.metric(name, help, labels, || Metric::Gauge(Gauge::default())) {
+			Metric::Gauge(gauge) => gauge,
+			_ => panic!("metric `{name}` is not a gauge"),
+		}
+	}
+
+	/// Returns the histogram `name` with `labels`. Creates the histogram if not exists.
+	///
+	/// # Panics
+	///
+	/// Will panic if `name` was already registered with another type or the internal mutex is
+	/// poisoned.
+	pub fn histogram(
+		&self,
+		name: &'static str,
+		help: &'static str,
+		labels: &[(&'static str, &str)],
+	) -> Histogram {
+		match self.metric(name, help, labels, || {
+			Metric::Histogram(Histogram::default())
+		}) {
+			Metric::Histogram(histogram) => histogram,
+			_ => panic!("metric `{name}` is not a histogram"),
+		}
+	}
+
+	/// Removes the series of metric `name` with `labels` (e.g. of a removed pool member). Existing
+	/// handles of the series stay usable but are no longer encoded. Requesting the series again
+	/// creates a new one.
+	///
+	/// # Panics
+	///
+	/// Will panic if the internal mutex is poisoned.
+	pub fn remove(&self, name: &'static str, labels: &[(&'static str, &str)]) {
+		let labels: Labels = labels
+			.iter()
+			.map(|(key, value)| (*key, (*value).to_string()))
+			.collect();
+		let mut families = self.families.lock().unwrap();
+		if let Some(family) = families.get_mut(name) {
+			family.series.remove(&labels);
+			if family.series.is_empty() {
+				families.remove(name);
+			}
+		}
+	}
+
+	fn metric(
+		&self,
+		name: &'static str,
+		help: &'static str,
+		labels: &[(&'static str, &str)],
+		create: impl FnOnce() -> Metric,
+	) -> Metric {
+		let labels = labels
+			.iter()
+			.map(|(key, value)| (*key, (*value).to_string()))
+			.collect();
+		let mut families = self.families.lock().unwrap();
+		let family = families.entry(name).or_insert_with(|| Family {
+			help,
+			series: BTreeMap::new(),
+		});
+		family.series.entry(labels).or_insert_with(create).clone()
+	}
+
+	/// Returns all metrics in Prometheus text format.
+	///
+	/// # Panics
+	///
+	/// Will panic if internal mutex is poisoned.
+	#[must_use]
+	pub fn encode(&self) -> String {
+		let families = self.families.lock().unwrap();
+		let mut out = String::new();
+		for (name, family) in families.iter() {
+			let Some(metric) = family.series.values().next() else {
+				continue;
+			};
+			let ty = match metric {
+				Metric::Counter(_) => "counter",
+				Metric::Gauge(_) => "gauge",
+				Metric::Histogram(_) => "histogram",
+			};
+			let _ = writeln!(out, "# HELP {name} {}", family.help);
+			let _ = writeln!(out, "# TYPE {name} {ty}");
+			for (labels, metric) in &family.series {
+				match metric {
+					Metric::Counter(counter) => {
+						let _ = writeln!(
+							out,
+							"{name}{} {}",
+							encode_labels(labels, None),
+							counter.get()
+						);
+					}
+					Metric::Gauge(gauge) => {
+						let _ =
+							writeln!(out, "{name}{} {}", encode_labels(labels, None), gauge.get());
+					}
+					Metric::Histogram(histogram) => {
+						encode_histogram(&mut out, name, labels, histogram);
+					}
+				}
+			}
+		}
+		out
+	}
+}
+
+fn encode_histogram(out: &mut String, name: &str, labels: &Labels, histogram: &Histogram) {
+	let mut cumulative = 0;
+	for (bound, bucket) in BUCKETS.iter().zip(&histogram.inner.buckets) {
+		cumulative += bucket.load(Ordering::Relaxed);
+		let le = bound.to_string();
+		let _ = writeln!(
+			out,
+			"{name}_bucket{} {cumulative}",
+			encode_labels(labels, Some(&le))
+		);
+	}
+	let count = histogram.count();
+	let _ = writeln!(
+		out,
+		"{name}_bucket{} {count}",
+		encode_labels(labels, Some("+Inf"))
+	);
+	#[allow(clippy::cast_precision_loss)]
+	let sum = histogram.inner.sum.load(Ordering::Relaxed) as f64 / 1e9;
+	let _ = writeln!(out, "{name}_sum{} {sum}", encode_labels(labels, None));
+	let _ = writeln!(out, "{name}_count{} {count}", encode_labels(labels, None));
+}
+
+fn encode_labels(labels: &Labels, le: Option<&str>) -> String {
+	let mut pairs: Vec<_> = labels
+		.iter()
+		.map(|(key, value)| format!("{key}=\"{}\"", escape(value)))
+		.collect();
+	if let Some(le) = le {
+		pairs.push(format!("le=\"{le}\""));
+	}
+	if pairs.is_empty() {
+		String::new()
+	} else {
+		format!("{{{}}}", pairs.join(","))
+	}
+}
+
+fn escape(value: &str) -> String {
+	value
+		.replace('\\', "\\\\")
+		.replace('"', "\\\"")
+		.replace('\n', "\\n")
+}
+
+/// Returns the counter of failed requests of `service` which were answered with `message`.
+pub(crate) fn errors(service: &str, message: &str) -> Counter {
+	global().counter(
+		"micro_tower_errors_total",
+		"Number of failed requests by response message.",
+		&[("service", service), ("message", message)],
+	)
+}
+
+impl Layer {
+	/// Creates a layer which records metrics labelled with service `name`.
+	#[must_use]
+	pub fn new(name: &'static str) -> Self {
+		Self { name }
+	}
+}
+
+impl<S> tower::Layer<S> for Layer {
+	type Service = Service<S>;
+
+	fn layer(&self, inner: S) -> Self::Service {
+		let labels = [("service", self.name)];
+		Service {
+			inner,
+			requests: global().counter(
+				"micro_tower_requests_total",
+				"Number of requests handled by a service.",
+				&labels,
+			),
+			errors: errors(self.name, "500"),
+			latency: global().histogram(
+				"micro_tower_request_duration_seconds",
+				"Time spent handling a request.",
+				&labels,
+			),
+		}
+	}
+}
+
+impl<S, Req> tower::Service<Req> for Service<S>
+where
+	S: tower::Service<Req>,
+	S::Future: Send + 'static,
+{
+	type Response = S::Response;
+	type Error = S::Error;
+	type Future = BoxFuture<Result<S::Response, S::Error>>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.inner.poll_ready(cx)
+	}
+
+	fn call(&mut self, req: Req) -> Self::Future {
+		let start = Instant::now();
+		let fut = self.inner.call(req);
+		let requests = self.requests.clone();
+		let errors = self.errors.clone();
+		let latency = self.latency.clone();
+		Box::pin(async move {
+			let result = fut.await;
+			requests.inc();
+			if result.is_err() {
+				errors.inc();
+			}
+			latency.observe(start.elapsed());
+			result
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::Registry;
+	use std::time::Duration;
+
+	#[test]
+	pub fn encode() {
+		let registry = Registry::default();
+		registry
+			.counter("requests", "Requests.", &[("service", "a\"b")])
+			.inc();
+		registry.gauge("active", "Active.", &[]).dec();
+		registry
+			.histogram("latency", "Latency.", &[])
+			.observe(Duration::from_millis(20));
+
+		let text = registry.encode();
+		assert!(text.contains("# TYPE requests counter\nrequests{service=\"a\\\"b\"} 1\n"));
+		assert!(text.contains("active -1\n"));
+		assert!(text.contains("latency_bucket{le=\"0.01\"} 0\n"));
+		assert!(text.contains("latency_bucket{le=\"0.025\"} 1\n"));
+		assert!(text.contains("latency_bucket{le=\"+Inf\"} 1\n"));
+		assert!(text.contains("latency_count 1\n"));
+	}
+
+	#[test]
+	pub fn remove() {
+		let registry = Registry::default();
+		let gauge = registry.gauge("load", "Load.", &[("member", "0")]);
+		registry.gauge("load", "Load.", &[("member", "1")]).inc();
+		registry.remove("load", &[("member", "0")]);
+		gauge.inc();
+		assert_eq!(
+			registry.encode(),
+			"# HELP load Load.\n# TYPE load gauge\nload{member=\"1\"} 1\n"
+		);
+
+		registry.remove("load", &[("member", "1")]);
+		assert_eq!(registry.encode(), "");
+		assert_eq!(registry.gauge("load", "Load.", &[("member", "0")]).get(), 0);
+	}
+}
@@ -1,14 +1,31 @@
+use crate::service::Health;
 use crate::shutdown::Controller;
+use std::sync::Arc;
 use tokio::task::JoinHandle;
 use tower::BoxError;
 
+pub mod admin;
 pub mod builder;
+pub mod config;
+pub mod graph;
+pub mod health;
 pub mod registry;
+pub mod services;
+pub mod supervisor;
 
 /// Used to manage and maintain services.
 pub struct Runtime {
+	/// Reads the configuration file again on reload (see [`Runtime::reload`]).
+	reloader: Arc<config::Reloader>,
 	controller: Controller,
 	session_handles: Vec<JoinHandle<Result<(), BoxError>>>,
+	/// Registered services and their lifecycle hooks.
+	services: services::Services,
+	graph: graph::Graph,
+	monitor: health::Monitor,
+	errors: admin::ErrorLog,
+	/// Keeps components registered at `monitor` alive.
+	_health: Vec<Arc<dyn Health + Send + Sync>>,
 }
 
 impl Runtime {
@@ -18,8 +35,56 @@ impl Runtime {
 		builder::Builder::default()
 	}
 
-	/// Start runtime and wait for shutdown signal. Will register SIGTERM and SIGQUIT signal.
+	/// Returns the dependency graph of all services of this runtime.
+	#[must_use]
+	pub fn graph(&self) -> &graph::Graph {
+		&self.graph
+	}
+
+	/// Returns a handle to add, replace or remove services while the runtime is running (see
+	/// [`services`]).
+	#[must_use]
+	pub fn services(&self) -> services::Services {
+		self.services.clone()
+	}
+
+	/// Returns the most recent errors reported by services and sessions of this runtime.
+	#[must_use]
+	pub fn errors(&self) -> &admin::ErrorLog {
+		&self.errors
+	}
+
+	/// Returns the health monitor of this runtime (see [`health::Monitor::is_ready`] and
+	/// [`health::Monitor::is_live`]).
+	#[must_use]
+	pub fn monitor(&self) -> &health::Monitor {
+		&self.monitor
+	}
+
+	/// Reads the configuration file again (see [`builder::Builder::config_file`]) and applies the
+	/// new configuration. Pool sizes and the log filter are changed live, all other changes are
+	/// only logged as they require a restart. On failure the current configuration is kept.
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if the configuration file could not be read or is invalid.
+	pub fn reload(&self) -> Result<config::Changes, config::Error> {
+		self.reloader.reload()
+	}
+
+	/// Start runtime and wait for shutdown signal. Will register SIGTERM and SIGQUIT signal and
+	/// reload the configuration on SIGHUP (see [`Runtime::reload`]). Once all sessions are shut
+	/// down, services are stopped in reverse dependency order (see
+	/// [`crate::service::Create::on_stop`]).
 	pub async fn run(self) {
+		let mut reloads = self.controller.subscribe_reload();
+		let reloader = Arc::clone(&self.reloader);
+		let reload_handle = tokio::spawn(async move {
+			while reloads.changed().await.is_ok() {
+				// errors are logged by the reloader
+				let _ = reloader.reload();
+			}
+		});
 		match self.controller.spawn_handler() {
 			Ok(handler) => {
 				if let Err(err) = handler.await {
@@ -32,6 +97,7 @@ impl Runtime {
 				tracing::error!("Failed to register signal handler. Reason: {report:?}");
 			}
 		}
+		reload_handle.abort();
 		tracing::info!("waiting for shutdown");
 
 		for (i, session) in self.session_handles.into_iter().enumerate() {
@@ -41,5 +107,21 @@ impl Runtime {
 				tracing::error!("{report:?}");
 			}
 		}
+		stop(self.services.take_stop_hooks(), &self.errors).await;
+	}
+}
+
+/// Calls the stop hooks of all services in reverse order. Failures are logged only.
+async fn stop(hooks: Vec<(&'static str, builder::StopFn)>, errors: &admin::ErrorLog) {
+	for (name, stop) in hooks.into_iter().rev() {
+		tracing::trace!(message = "stopping service", name);
+		if let Err(err) = stop().await {
+			tracing::error!(
+				message = "failed to stop service",
+				name,
+				reason = format!("{}", std::error::Report::new(&*err))
+			);
+			errors.push(name, &*err);
+		}
 	}
 }
@@ -0,0 +1,341 @@
+//! Optional admin session which exposes the live state of a runtime via HTTP. Can be bound to a
+//! separate tcp port or a unix socket (see [`super::builder::Builder::admin`]).
+//!
+//! # Routes
+//!
+//! - `GET /health`: Health of all services, pools and sessions (see [`Monitor::checks`]).
+//! - `GET /ready`: Readiness probe (see [`Monitor::is_ready`]).
+//! - `GET /live`: Liveness probe (see [`Monitor::is_live`]).
+//! - `GET /state`: Registry entries, services, sessions with open connections, pool sizes and
+//!   recent errors.
+//! - `GET /metrics`: All metrics in Prometheus text format (see [`crate::metrics`]).
+//! - `POST /shutdown`: Shuts down the runtime. Only routed if enabled by [`Admin::with_shutdown`].
+//!
+//! Probes respond with `503 Service Unavailable` if the probe failed.
+
+use super::graph::Graph;
+use super::health::Monitor;
+use super::registry;
+use crate::service::Status;
+use crate::session::Connections;
+use crate::shutdown::Controller;
+use crate::util::BoxFuture;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, UnixListener};
+use tower::BoxError;
+
+/// Maximum number of errors kept by an [`ErrorLog`].
+const ERROR_LOG_SIZE: usize = 64;
+
+/// Maximum size of a request header.
+const MAX_REQUEST_SIZE: usize = 8192;
+
+enum Listener {
+	Tcp(TcpListener),
+	Unix(UnixListener, PathBuf),
+}
+
+/// Admin session bound to a tcp port or unix socket.
+pub struct Admin {
+	listener: Listener,
+	shutdown: bool,
+}
+
+/// Error reported by a service or session of the runtime.
+#[derive(Clone, Debug, Serialize)]
+pub struct ErrorEntry {
+	/// Seconds since unix epoch.
+	pub time: u64,
+	/// Name of the service or session which reported the error.
+	pub source: String,
+	pub message: String,
+}
+
+/// Keeps the most recent errors of a runtime. Clones share the same log.
+#[derive(Clone, Default)]
+pub struct ErrorLog {
+	entries: Arc<Mutex<VecDeque<ErrorEntry>>>,
+}
+
+/// Runtime state exposed by the admin session.
+pub(crate) struct State {
+	pub registry: Arc<RwLock<registry::Type>>,
+	pub graph: Graph,
+	pub monitor: Monitor,
+	/// Open connections of each session in the same order as [`Graph::sessions`].
+	pub connections: Vec<Option<Connections>>,
+	pub errors: ErrorLog,
+	/// Shares the shutdown signal with the runtime.
+	pub controller: Controller,
+	/// Routes `POST /shutdown` if enabled (see [`Admin::with_shutdown`]).
+	pub shutdown: bool,
+}
+
+impl Admin {
+	/// Create admin session that binds to tcp address `addr`.
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if failed to create tcp listener.
+	pub async fn with_addr(addr: SocketAddr) -> std::io::Result<Self> {
+		let listener = TcpListener::bind(&addr).await?;
+		Ok(Self {
+			listener: Listener::Tcp(listener),
+			shutdown: false,
+		})
+	}
+
+	/// Create admin session that binds to unix socket at `path`. The socket file is removed on
+	/// shutdown.
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if failed to create unix listener (e.g. `path` already exists).
+	pub fn with_path(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+		let path = path.into();
+		let listener = UnixListener::bind(&path)?;
+		Ok(Self {
+			listener: Listener::Unix(listener, path),
+			shutdown: false,
+		})
+	}
+
+	/// Allows clients of the session to shut down the runtime with `POST /shutdown`. Disabled by
+	/// default because requests are not authenticated, so the session should only be reachable
+	/// by trusted clients (e.g. bound to loopback or a unix socket with restricted permissions).
+	#[must_use]
+	pub fn with_shutdown(mut self) -> Self {
+		self.shutdown = true;
+		self
+	}
+
+	/// Returns the local address if bound to a tcp port.
+	#[must_use]
+	pub fn local_addr(&self) -> Option<SocketAddr> {
+		match &self.listener {
+			Listener::Tcp(listener) => listener.local_addr().ok(),
+			Listener::Unix(..) => None,
+		}
+	}
+
+	/// Returns a description of the endpoint the session listens on (e.g.
+	/// `tcp://127.0.0.1:9000` or `unix:///run/admin.sock`).
+	#[must_use]
+	pub fn endpoint(&self) -> Option<String> {
+		match &self.listener {
+			Listener::Tcp(listener) => {
+				let addr = listener.local_addr().ok()?;
+				Some(format!("tcp://{addr}"))
+			}
+			Listener::Unix(_, path) => Some(format!("unix://{}", path.display())),
+		}
+	}
+
+	/// Accepts connections until shutdown is requested.
+	pub(crate) fn run(self, mut state: State) -> BoxFuture<Result<(), BoxError>> {
+		let endpoint = self.endpoint().unwrap_or_default();
+		state.shutdown = self.shutdown;
+		let state = Arc::new(state);
+		Box::pin(async move {
+			tracing::info!(message = "admin session listening on", endpoint);
+			let controller = state.controller.clone();
+			loop {
+				let state = Arc::clone(&state);
+				tokio::select! {
+					result = accept(&self.listener, state) => result?,
+					() = controller.wait_for_shutdown() => break,
+				}
+			}
+			if let Listener::Unix(_, path) = &self.listener {
+				let _ = std::fs::remove_file(path);
+			}
+			Ok(())
+		})
+	}
+}
+
+/// Accepts a single connection and spawns a task to handle it. Errors of the connection are
+/// logged.
+async fn accept(listener: &Listener, state: Arc<State>) -> std::io::Result<()> {
+	match listener {
+		Listener::Tcp(listener) => {
+			let (stream, _) = listener.accept().await?;
+			tokio::spawn(async move { log_error(handle(stream, &state).await) });
+		}
+		Listener::Unix(listener, _) => {
+			let (stream, _) = listener.accept().await?;
+			tokio::spawn(async move { log_error(handle(stream, &state).await) });
+		}
+	}
+	Ok(())
+}
+
+/// Logs the error of a failed connection.
+fn log_error(result: std::io::Result<()>) {
+	if let Err(err) = result {
+		let report = crate::report!(err);
+		tracing::warn!("admin connection failed: {report:?}");
+	}
+}
+
+/// Reads a single HTTP request from `io` and responds with JSON.
+async fn handle<IO>(mut io: IO, state: &State) -> std::io::Result<()>
+where
+	IO: AsyncRead + AsyncWrite + Unpin,
+{
+	let mut buf = Vec::with_capacity(1024);
+	let mut chunk = [0; 1024];
+	while !buf.windows(4).any(|w| w == b"\r\n\r\n") && buf.len() < MAX_REQUEST_SIZE {
+		let n = io.read(&mut chunk).await?;
+		if n == 0 {
+			break;
+		}
+		buf.extend_from_slice(&chunk[..n]);
+	}
+	let request = String::from_utf8_lossy(&buf);
+	let mut parts = request
+		.lines()
+		.next()
+		.unwrap_or_default()
+		.split_whitespace();
+	let method = parts.next().unwrap_or_default();
+	let path = parts.next().unwrap_or_default();
+	let path = path.split('?').next().unwrap_or_default();
+	tracing::debug!(message = "admin request", method, path);
+
+	let (code, content_type, body) = if (method, path) == ("GET", "/metrics") {
+		let body = crate::metrics::global().encode();
+		(200, "text/plain; version=0.0.4", body)
+	} else {
+		let (code, body) = route(method, path, state);
+		(code, "application/json", body.to_string())
+	};
+	let reason = match code {
+		200 => "OK",
+		202 => "Accepted",
+		404 => "Not Found",
+		405 => "Method Not Allowed",
+		_ => "Service Unavailable",
+	};
+	let response = format!(
+		"HTTP/1.1 {code} {reason}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+		body.len()
+	);
+	io.write_all(response.as_bytes()).await?;
+	io.shutdown().await?;
+	if code == 202 {
+		tracing::info!("shutdown requested by admin session");
+		state.controller.shutdown();
+	}
+	Ok(())
+}
+
+/// Returns status code and body of the response to `method` `path`.
+fn route(method: &str, path: &str, state: &State) -> (u16, serde_json::Value) {
+	let probe = |ok: bool| if ok { 200 } else { 503 };
+	match (method, path) {
+		("GET", "/health") => {
+			let status = state.monitor.status();
+			let body = serde_json::json!({
+				"status": status,
+				"checks": state.monitor.checks(),
+			});
+			(probe(status != Status::Unhealthy), body)
+		}
+		("GET", "/ready") => {
+			let ready = state.monitor.is_ready();
+			(probe(ready), serde_json::json!({ "ready": ready }))
+		}
+		("GET", "/live") => {
+			let live = state.monitor.is_live();
+			(probe(live), serde_json::json!({ "live": live }))
+		}
+		("GET", "/state") => (200, runtime_state(state)),
+		("POST", "/shutdown") if state.shutdown => (202, serde_json::json!({ "shutdown": true })),
+		(_, "/health" | "/ready" | "/live" | "/state" | "/metrics") => {
+			(405, serde_json::json!({ "error": "method not allowed" }))
+		}
+		(_, "/shutdown") if state.shutdown => {
+			(405, serde_json::json!({ "error": "method not allowed" }))
+		}
+		_ => (404, serde_json::json!({ "error": "not found" })),
+	}
+}
+
+fn runtime_state(state: &State) -> serde_json::Value {
+	let mut registry: Vec<_> = match state.registry.read() {
+		Ok(registry) => registry
+			.entries()
+			.map(|(key, ty)| serde_json::json!({ "key": key, "type": ty }))
+			.collect(),
+		Err(_) => Vec::new(),
+	};
+	registry.sort_by(|a, b| a["key"].as_str().cmp(&b["key"].as_str()));
+	let sessions: Vec<_> = state
+		.graph
+		.sessions
+		.iter()
+		.zip(&state.connections)
+		.map(|(session, connections)| {
+			serde_json::json!({
+				"service": session.service,
+				"endpoint": session.endpoint,
+				"connections": connections.as_ref().map(Connections::count),
+			})
+		})
+		.collect();
+	let pools: Vec<_> = state
+		.monitor
+		.checks()
+		.into_iter()
+		.filter(|check| check.members.is_some())
+		.collect();
+	serde_json::json!({
+		"ready": state.monitor.is_ready(),
+		"live": state.monitor.is_live(),
+		"registry": registry,
+		"services": state.graph.nodes,
+		"sessions": sessions,
+		"pools": pools,
+		"errors": state.errors.recent(),
+	})
+}
+
+impl ErrorLog {
+	/// Adds `err` reported by `source` to the log. Drops the oldest entry if the log is full.
+	///
+	/// # Panics
+	///
+	/// Will panic if internal mutex is poisoned.
+	pub fn push(&self, source: impl Into<String>, err: &(dyn std::error::Error + 'static)) {
+		let time = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map_or(0, |time| time.as_secs());
+		let entry = ErrorEntry {
+			time,
+			source: source.into(),
+			message: format!("{}", std::error::Report::new(err)),
+		};
+		let mut entries = self.entries.lock().unwrap();
+		if entries.len() == ERROR_LOG_SIZE {
+			entries.pop_front();
+		}
+		entries.push_back(entry);
+	}
+
+	/// Returns all logged errors from oldest to newest.
+	///
+	/// # Panics
+	///
+	/// Will panic if internal mutex is poisoned.
+	#[must_use]
+	pub fn recent(&self) -> Vec<ErrorEntry> {
+		self.entries.lock().unwrap().iter().cloned().collect()
+	}
+}
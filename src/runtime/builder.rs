@@ -1,13 +1,105 @@
+use super::admin::{self, Admin};
+use super::config::{self, Config};
+use super::graph::{self, Graph};
+use super::health::{self, Indicator, Monitor};
+use super::services::Services;
+use super::supervisor::{self, Supervisor};
 use super::{registry, Runtime};
-use crate::service::{Create, Info, NotReady, Service};
-use crate::session::Session;
+use crate::client::{self, Client};
+use crate::discovery;
+use crate::service::{swap, Create, Health, Info, Managed, NotReady, Service, Status};
+use crate::session::{Bind, Connections, Session};
 use crate::shutdown::Controller;
+use crate::util::BoxFuture;
+use std::collections::HashMap;
 use std::net::SocketAddr;
-use std::sync::{Arc, RwLock};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::watch;
 use tokio::task::JoinHandle;
 use tower::util::BoxCloneService;
 use tower::{BoxError, ServiceBuilder};
 
+type SpawnFn = Box<dyn FnOnce(&Supervisor) -> JoinHandle<Result<(), BoxError>> + Send>;
+pub(crate) type StopFn = Box<dyn FnOnce() -> BoxFuture<Result<(), BoxError>> + Send>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error("service `{service}` depends on `{dependency}` which is not registered")]
+	Missing {
+		service: &'static str,
+		dependency: &'static str,
+	},
+	#[error("dependency cycle detected: {}", .0.join(" -> "))]
+	Cycle(Vec<&'static str>),
+	#[error("service or object `{0}` already registered")]
+	Duplicate(String),
+	#[error("failed to access service registry")]
+	Registry(
+		#[from]
+		#[source]
+		registry::Error,
+	),
+	#[error("failed to create {} service(s):\n{0}", .0.failed.len())]
+	Services(Report),
+	#[error("no session configured for service `{0}`")]
+	Unbound(&'static str),
+	#[error("failed to bind session of service `{service}` to {addr}")]
+	Bind {
+		service: &'static str,
+		addr: SocketAddr,
+		#[source]
+		source: std::io::Error,
+	},
+	#[error("failed to read runtime configuration")]
+	Config(
+		#[from]
+		#[source]
+		config::Error,
+	),
+	#[error("failed to configure runtime:\n{}", list(.0))]
+	Multiple(Vec<Error>),
+}
+
+/// Reason why a single service was not created at runtime build.
+#[derive(Debug, thiserror::Error)]
+pub enum ServiceError {
+	#[error("service was not created within {0:?}")]
+	Timeout(Duration),
+	#[error("failed to create service")]
+	Create(#[source] BoxError),
+	#[error("failed to start service")]
+	Start(#[source] BoxError),
+	#[error("dependency `{0}` failed to be created")]
+	Dependency(&'static str),
+}
+
+/// Lists which services were created and which failed to be created at runtime build.
+#[derive(Debug, Default)]
+pub struct Report {
+	pub created: Vec<&'static str>,
+	pub failed: Vec<(&'static str, ServiceError)>,
+}
+
+impl std::fmt::Display for Report {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		for (name, err) in &self.failed {
+			writeln!(f, "  {name}: {}", std::error::Report::new(err))?;
+		}
+		Ok(())
+	}
+}
+
+/// A service or session which is spawned once all dependencies were validated.
+struct Pending {
+	name: &'static str,
+	dependencies: Vec<&'static str>,
+	spawn: SpawnFn,
+	/// Lifecycle hook called at shutdown (see [`Create::on_stop`]).
+	stop: Option<StopFn>,
+}
+
 /// Implements builder pattern used to generate runtime.
 ///
 /// # Usage
@@ -17,54 +109,170 @@ use tower::{BoxError, ServiceBuilder};
 /// 	.service::<your_service>()
 /// 	.bind_service::<other_service>(8080)
 /// 	.build()
-/// 	.await;
+/// 	.await?;
 /// ```
-#[derive(Default)]
 pub struct Builder {
 	registry: Arc<RwLock<registry::Type>>,
-	handles: Vec<(&'static str, JoinHandle<Result<(), BoxError>>)>,
-	session_handles: Vec<JoinHandle<Result<(), BoxError>>>,
+	/// Swap handles of all services (see [`Services`]).
+	handles: Arc<RwLock<registry::Type>>,
+	services: Vec<Pending>,
+	sessions: Vec<Pending>,
 	controller: Controller,
+	startup_timeout: Duration,
+	supervision: Option<supervisor::Policy>,
+	/// Current configuration, observed by services through [`config::Watch`].
+	config: Arc<watch::Sender<Config>>,
+	/// File the configuration was read from (see [`Builder::config_file`]).
+	config_path: Option<PathBuf>,
+	errors: Vec<Error>,
+	/// Names of all services which failed to be created. Dependents fail as soon as one of their
+	/// dependencies is listed.
+	failed: Arc<watch::Sender<Vec<&'static str>>>,
+	graph: Graph,
+	monitor: Monitor,
+	/// Keeps components registered at `monitor` alive.
+	health: Vec<Arc<dyn Health + Send + Sync>>,
+	admin: Option<Admin>,
+	error_log: admin::ErrorLog,
+	/// Open connections of each session in the same order as [`Graph::sessions`].
+	connections: Vec<Option<Connections>>,
+}
+
+impl Default for Builder {
+	fn default() -> Self {
+		let monitor = Monitor::default();
+		let (config, rx) = watch::channel(Config::default());
+		let mut registry = registry::Type::default();
+		registry.insert_key(health::KEY, monitor.clone());
+		registry.insert_key(config::KEY, config::Watch::from(rx));
+		Self {
+			registry: Arc::new(RwLock::new(registry)),
+			handles: Arc::default(),
+			services: Vec::new(),
+			sessions: Vec::new(),
+			controller: Controller::default(),
+			startup_timeout: Duration::from_secs(30),
+			supervision: Some(supervisor::Policy::default()),
+			config: Arc::new(config),
+			config_path: None,
+			errors: Vec::new(),
+			failed: Arc::new(watch::channel(Vec::new()).0),
+			graph: Graph::default(),
+			monitor,
+			health: Vec::new(),
+			admin: None,
+			error_log: admin::ErrorLog::default(),
+			connections: Vec::new(),
+		}
+	}
 }
 
 impl Builder {
-	/// Register new service builder to runtime service registry.
-	///
-	/// # Panics
-	///
-	/// Will panic if internal mutex failed to lock registry.
+	/// Set the maximum duration to wait for all services to be created. Defaults to 30 seconds.
+	#[must_use]
+	pub fn startup_timeout(mut self, timeout: Duration) -> Self {
+		self.startup_timeout = timeout;
+		self
+	}
+
+	/// Set how failed services are restarted (see [`supervisor`]). Services are supervised with
+	/// [`supervisor::Policy::default`] by default, `None` disables supervision.
+	#[must_use]
+	pub fn supervision(mut self, policy: Option<supervisor::Policy>) -> Self {
+		self.supervision = policy;
+		self
+	}
+
+	/// Register new service builder to runtime service registry. The service is created after
+	/// all its dependencies (see [`Info::dependencies`]) were registered and started (see
+	/// [`Create::on_start`]). The service can be replaced while the runtime is running (see
+	/// [`Services::replace`]) and is restarted if it fails (see [`Builder::supervision`]).
 	#[must_use]
 	pub fn service<S: Create + 'static>(mut self) -> Self
 	where
 		S::Error: std::error::Error + Send + Sync + 'static,
 	{
 		let registry = Arc::clone(&self.registry);
-		let handle = tokio::spawn(async move {
-			loop {
-				let service = S::with_registry(registry.clone());
-				let service = match service {
-					Ok(Some(service)) => service,
-					Ok(None) => continue,
-					Err(err) => return Err(Box::new(err).into()),
-				};
-				let name = S::name();
-				{
-					let mut guard = registry.write().unwrap();
-					guard.insert(S::name(), Box::new(service));
+		let handles = Arc::clone(&self.handles);
+		let stop = {
+			let registry = Arc::clone(&registry);
+			move || S::on_stop(registry)
+		};
+		let indicator = self.indicator(S::name(), health::Kind::Service);
+		let failed = Arc::clone(&self.failed);
+		let spawn = move |supervisor: &Supervisor| {
+			let supervisor = supervisor.clone();
+			tokio::spawn(async move {
+				let mut failures = failed.subscribe();
+				let result = async {
+					let mut changes = registry
+						.read()
+						.map_err(|_| registry::Error::Poisoned)?
+						.subscribe();
+					loop {
+						changes.borrow_and_update();
+						if let Some(dependency) = failed_dependency::<S>(&mut failures) {
+							indicator.set(Status::Unhealthy);
+							return Err(ServiceError::Dependency(dependency).into());
+						}
+						let service = S::with_registry(registry.clone());
+						let service = match service {
+							Ok(Some(service)) => service,
+							Ok(None) => {
+								tracing::trace!(
+									message = "waiting for dependencies",
+									name = S::name()
+								);
+								tokio::select! {
+									result = changes.changed() => result?,
+									_ = failures.changed() => {}
+								}
+								continue;
+							}
+							Err(err) => {
+								indicator.set(Status::Unhealthy);
+								return Err(Box::new(err).into());
+							}
+						};
+						let name = S::name();
+						if let Err(err) = S::on_start(registry.clone()).await {
+							indicator.set(Status::Unhealthy);
+							return Err(ServiceError::Start(err).into());
+						}
+						let (service, handle) = swap::swappable(service);
+						{
+							let mut guard =
+								registry.write().map_err(|_| registry::Error::Poisoned)?;
+							guard.insert(S::name(), Box::new(service));
+						}
+						handles
+							.write()
+							.map_err(|_| registry::Error::Poisoned)?
+							.insert(S::name(), Box::new(handle));
+						tracing::info!(message = "service registered", name);
+						indicator.set(Status::Healthy);
+						supervisor.supervise::<S>(Some(indicator));
+						return Ok(());
+					}
 				}
-				tracing::info!(message = "service registered", name);
-				return Ok(());
-			}
+				.await;
+				if result.is_err() {
+					failed.send_modify(|failed| failed.push(S::name()));
+				}
+				result
+			})
+		};
+		self.add_node(S::name(), graph::Kind::Service, &S::dependencies());
+		self.services.push(Pending {
+			name: S::name(),
+			dependencies: S::dependencies(),
+			spawn: Box::new(spawn),
+			stop: Some(Box::new(stop)),
 		});
-		self.handles.push((S::name(), handle));
 		self
 	}
 
 	/// Register new service builder to port `port`.
-	///
-	/// # Panics
-	///
-	/// Will panic if internal mutex failed to lock registry.
 	#[must_use]
 	pub fn bind_service<S, T>(mut self, session: T) -> Self
 	where
@@ -72,66 +280,489 @@ impl Builder {
 		S::Error: std::error::Error + Send + Sync + 'static,
 		T: Session<BoxCloneService<SocketAddr, Service<S>, BoxError>> + Send + 'static,
 	{
+		if self.graph.node(S::name()).is_none() {
+			self.add_node(S::name(), graph::Kind::Service, &S::dependencies());
+		}
+		self.graph.sessions.push(graph::Binding {
+			service: S::name().to_string(),
+			endpoint: session.endpoint(),
+		});
+		self.connections.push(session.connections());
 		let controller = self.controller.clone();
 		let registry = Arc::clone(&self.registry);
-		let handle = tokio::spawn(async move {
-			let service =
-				ServiceBuilder::new()
-					.boxed_clone()
-					.service_fn(move |addr: SocketAddr| {
-						let registry = registry.clone();
-						async move {
-							tracing::info!(message = "new connection", addr = format!("{addr}"));
-							let service = S::with_registry(registry.clone());
-							let service = match service {
-								Ok(Some(service)) => service,
-								Ok(None) => return Err(Box::new(NotReady(S::name())).into()),
-								Err(err) => return Err(Box::new(err).into()),
-							};
-							Ok::<_, BoxError>(service)
-						}
-					});
+		let error_log = self.error_log.clone();
+		let indicator = self.indicator(S::name(), health::Kind::Session);
+		let spawn = move |_: &Supervisor| {
+			tokio::spawn(async move {
+				let session_log = error_log.clone();
+				let service =
+					ServiceBuilder::new()
+						.boxed_clone()
+						.service_fn(move |addr: SocketAddr| {
+							let registry = registry.clone();
+							let error_log = error_log.clone();
+							async move {
+								tracing::info!(
+									message = "new connection",
+									addr = format!("{addr}")
+								);
+								let service = S::with_registry(registry.clone());
+								let service = match service {
+									Ok(Some(service)) => service,
+									Ok(None) => {
+										error_log.push(S::name(), &NotReady(S::name()));
+										return Err(Box::new(NotReady(S::name())).into());
+									}
+									Err(err) => {
+										error_log.push(S::name(), &err);
+										return Err(Box::new(err).into());
+									}
+								};
+								Ok::<_, BoxError>(service)
+							}
+						});
 
-			session.run(service, controller).await
+				indicator.set(Status::Healthy);
+				let result = session.run(service, controller).await;
+				indicator.set(Status::Unhealthy);
+				if let Err(err) = &result {
+					session_log.push(S::name(), err.as_ref());
+				}
+				result
+			})
+		};
+		self.sessions.push(Pending {
+			name: S::name(),
+			dependencies: S::dependencies(),
+			spawn: Box::new(spawn),
+			stop: None,
 		});
-		self.session_handles.push(handle);
+		self
+	}
+
+	/// Same as [`Builder::bind_service`] but binds session `T` to the address configured for `S`
+	/// (see [`Builder::config`]). Errors are deferred until [`Builder::build`].
+	#[must_use]
+	pub fn expose<S, T>(mut self) -> Self
+	where
+		S: Info + Create,
+		S::Error: std::error::Error + Send + Sync + 'static,
+		T: Session<BoxCloneService<SocketAddr, Service<S>, BoxError>> + Bind + Send + 'static,
+	{
+		let Some(addr) = self.config.borrow().session(S::name()) else {
+			self.errors.push(Error::Unbound(S::name()));
+			return self;
+		};
+		match T::bind(addr) {
+			Ok(session) => self.bind_service::<S, T>(session),
+			Err(source) => {
+				self.errors.push(Error::Bind {
+					service: S::name(),
+					addr,
+					source,
+				});
+				self
+			}
+		}
+	}
+
+	/// Register a remote service `S` reachable at `addr`. Services which depend on `S` will be
+	/// provided with a [`Client`] which calls the remote service using codec `C` instead of a
+	/// local instance of `S`.
+	#[must_use]
+	pub fn remote<S, C>(mut self, addr: SocketAddr) -> Self
+	where
+		S: Info + 'static,
+		Client<S, C>: tower::Service<
+				S::Request,
+				Response = S::Response,
+				Error = BoxError,
+				Future = BoxFuture<Result<S::Response, BoxError>>,
+			> + Send
+			+ Sync
+			+ 'static,
+	{
+		let service = Service::<S>::from(Box::new(Client::<S, C>::new(addr)));
+		self.insert(S::name(), service);
+		self.add_node(S::name(), graph::Kind::Remote, &[]);
+		let name = S::name();
+		tracing::info!(
+			message = "remote service registered",
+			name,
+			addr = format!("{addr}")
+		);
+		self
+	}
+
+	/// Register a remote service `S` with multiple replicas at `addrs`. Services which depend on
+	/// `S` will be provided with a [`client::pool::Pool`] which balances requests between all
+	/// healthy replicas (see [`client::pool::Config`]).
+	#[must_use]
+	pub fn remote_replicas<S, C>(
+		mut self,
+		addrs: impl IntoIterator<Item = SocketAddr>,
+		config: client::pool::Config,
+	) -> Self
+	where
+		S: Info + Send + 'static,
+		S::Request: Send + 'static,
+		S::Response: Send + 'static,
+		C: Send + 'static,
+		Client<S, C>: tower::Service<
+			S::Request,
+			Response = S::Response,
+			Error = BoxError,
+			Future = BoxFuture<Result<S::Response, BoxError>>,
+		>,
+	{
+		let pool = client::pool::Pool::<S, C>::new(addrs, config);
+		let replicas = pool.handle().replicas().len();
+		self.monitored(S::name(), health::Kind::Remote, pool.handle().clone());
+		let service = Service::<S>::from(Box::new(pool));
+		self.insert(S::name(), service);
+		self.add_node(S::name(), graph::Kind::Remote, &[]);
+		let name = S::name();
+		tracing::info!(message = "remote service registered", name, replicas);
+		self
+	}
+
+	/// Same as [`Builder::remote_replicas`] but the replicas of `S` are read from a discovery
+	/// file. Replicas are added and removed as soon as the file changes.
+	#[must_use]
+	pub fn remote_discover<S, C>(
+		mut self,
+		watcher: &discovery::file::Watcher,
+		config: client::pool::Config,
+	) -> Self
+	where
+		S: Info + Send + 'static,
+		S::Request: Send + 'static,
+		S::Response: Send + 'static,
+		C: Send + 'static,
+		Client<S, C>: tower::Service<
+			S::Request,
+			Response = S::Response,
+			Error = BoxError,
+			Future = BoxFuture<Result<S::Response, BoxError>>,
+		>,
+	{
+		let pool = client::pool::Pool::<S, C>::new([], config);
+		pool.handle().follow(watcher.discover::<S>());
+		self.monitored(S::name(), health::Kind::Remote, pool.handle().clone());
+		let service = Service::<S>::from(Box::new(pool));
+		self.insert(S::name(), service);
+		self.add_node(S::name(), graph::Kind::Remote, &[]);
+		let name = S::name();
+		tracing::info!(message = "remote service registered", name);
 		self
 	}
 
 	/// Register an `object` immediately. Requires a unique identifier (including service names).
-	///
-	/// # Panics
-	///
-	/// Will panic service or object with name already registered.
+	/// Duplicates are reported by [`Builder::build`]. The object is stored as [`Managed<T>`] and
+	/// can be passed to services by name or type.
 	#[must_use]
-	pub fn manage<T: Send + Sync + 'static>(self, ident: impl Into<String>, object: T) -> Self {
-		{
-			let mut guard = self.registry.write().unwrap();
-			assert!(
-				guard.insert(ident, Box::new(object)).is_some(),
-				"Object already registerd"
-			);
+	pub fn manage<T: Send + Sync + 'static>(mut self, ident: impl Into<String>, object: T) -> Self {
+		let ident = ident.into();
+		self.add_node(&ident, graph::Kind::Object, &[]);
+		self.insert(ident, Managed::new(object));
+		self
+	}
+
+	/// Use `config` to override the settings of services (see [`config`]). Must be set before
+	/// sessions are exposed (see [`Builder::expose`]). Applies the log filter of `config` if the
+	/// subscriber was installed by [`crate::telemetry::init`].
+	#[must_use]
+	pub fn config(self, config: Config) -> Self {
+		config::apply_log_filter(&config);
+		self.config.send_replace(config);
+		self
+	}
+
+	/// Same as [`Builder::config`] but reads the configuration from the file at `path` with
+	/// environment overrides (see [`Config::load`]). The file is read again on SIGHUP (see
+	/// [`Runtime::reload`]). Errors are deferred until [`Builder::build`].
+	#[must_use]
+	pub fn config_file(mut self, path: impl Into<PathBuf>) -> Self {
+		let path = path.into();
+		match Config::load(&path) {
+			Ok(config) => {
+				self.config_path = Some(path);
+				self.config(config)
+			}
+			Err(err) => {
+				self.errors.push(err.into());
+				self
+			}
 		}
+	}
+
+	/// Returns the dependency graph of all services, objects and sessions registered so far.
+	#[must_use]
+	pub fn graph(&self) -> &Graph {
+		&self.graph
+	}
+
+	/// Serve the runtime state and health probes on `admin` (see [`admin`]). The admin session
+	/// is started before any service is created.
+	#[must_use]
+	pub fn admin(mut self, admin: Admin) -> Self {
+		self.admin = Some(admin);
 		self
 	}
 
-	/// Build service runtime. Can only build once.
+	/// Returns the health monitor of the runtime. Can be used to query readiness and liveness
+	/// while and after the runtime is built.
+	#[must_use]
+	pub fn monitor(&self) -> Monitor {
+		self.monitor.clone()
+	}
+
+	/// Registers `component` at the health monitor and keeps it alive as long as the runtime.
+	fn monitored<H: Health + Send + Sync + 'static>(
+		&mut self,
+		name: &str,
+		kind: health::Kind,
+		component: H,
+	) {
+		let component = Arc::new(component);
+		self.monitor.register(name, kind, &component);
+		self.health.push(component);
+	}
+
+	/// Returns a new indicator which reports [`Status::Degraded`] until set by its owner.
+	fn indicator(&mut self, name: &str, kind: health::Kind) -> Arc<Indicator> {
+		let indicator = Arc::new(Indicator::new(Status::Degraded));
+		self.monitor.register(name, kind, &indicator);
+		self.health.push(Arc::clone(&indicator) as _);
+		indicator
+	}
+
+	fn add_node(&mut self, name: &str, kind: graph::Kind, dependencies: &[&str]) {
+		self.graph.insert(graph::Node {
+			name: name.to_string(),
+			kind,
+			dependencies: dependencies.iter().map(ToString::to_string).collect(),
+		});
+	}
+
+	/// Inserts `object` into the registry. Errors are deferred until [`Builder::build`].
+	fn insert<T: Send + Sync + 'static>(&mut self, ident: impl Into<String>, object: T) {
+		let ident = ident.into();
+		let Ok(mut guard) = self.registry.write() else {
+			self.errors.push(registry::Error::Poisoned.into());
+			return;
+		};
+		if guard.contains_key(&ident) {
+			drop(guard);
+			self.errors.push(Error::Duplicate(ident));
+			return;
+		}
+		guard.insert(ident, Box::new(object));
+	}
+
+	/// Build service runtime. Can only build once. Validates the dependencies of all registered
+	/// services before any service or session is started. If any service failed to be created,
+	/// all sessions are shut down again and all services already started are stopped.
 	///
-	/// # Panics
+	/// # Errors
 	///
-	/// Will panic if failed to create any service.
-	pub async fn build(self) -> Runtime {
-		// TODO: Detect dependency cycles.
-		for (name, service) in self.handles {
-			if let Err(err) = service.await {
-				let report = crate::report!(err);
-				tracing::error!("failed to register service '{name}'. reason: {report:?}");
-				panic!("failed to register service '{name}'")
+	/// Will return `Err` if an object was registered twice, a dependency is not registered,
+	/// services depend on each other in a cycle or any service failed to be created (see
+	/// [`Report`]). Services fail without waiting for the startup timeout once one of their
+	/// dependencies failed. Errors deferred by the builder are returned together (see
+	/// [`Error::Multiple`]).
+	pub async fn build(mut self) -> Result<Runtime, Error> {
+		match self.errors.len() {
+			0 => {}
+			1 => return Err(self.errors.remove(0)),
+			_ => return Err(Error::Multiple(std::mem::take(&mut self.errors))),
+		}
+		let order = self.validate()?;
+		self.services
+			.sort_by_key(|service| order.iter().position(|name| *name == service.name));
+
+		let admin_handle = self.admin.take().map(|admin| {
+			let state = admin::State {
+				registry: Arc::clone(&self.registry),
+				graph: self.graph.clone(),
+				monitor: self.monitor.clone(),
+				connections: self.connections.clone(),
+				errors: self.error_log.clone(),
+				controller: self.controller.share(),
+				shutdown: false,
+			};
+			tokio::spawn(admin.run(state))
+		});
+		let supervisor = Supervisor {
+			policy: self.supervision,
+			registry: Arc::clone(&self.registry),
+			handles: Arc::clone(&self.handles),
+			controller: self.controller.clone(),
+			errors: self.error_log.clone(),
+			tasks: Arc::default(),
+		};
+		let handles: Vec<_> = self
+			.services
+			.into_iter()
+			.map(|service| (service.name, (service.spawn)(&supervisor), service.stop))
+			.collect();
+		let session_handles: Vec<_> = self
+			.sessions
+			.into_iter()
+			.map(|session| (session.spawn)(&supervisor))
+			.chain(admin_handle)
+			.collect();
+		let deadline = tokio::time::Instant::now() + self.startup_timeout;
+		let mut report = Report::default();
+		let mut stop_hooks = Vec::new();
+		for (name, mut handle, stop) in handles {
+			let result = match tokio::time::timeout_at(deadline, &mut handle).await {
+				Ok(Ok(result)) => result.map_err(|err| match err.downcast::<ServiceError>() {
+					Ok(err) => *err,
+					Err(err) => ServiceError::Create(err),
+				}),
+				Ok(Err(err)) => Err(ServiceError::Create(err.into())),
+				Err(_) => {
+					handle.abort();
+					Err(ServiceError::Timeout(self.startup_timeout))
+				}
+			};
+			match result {
+				Ok(()) => {
+					report.created.push(name);
+					stop_hooks.extend(stop.map(|stop| (name, stop)));
+				}
+				Err(err) => {
+					tracing::error!(
+						message = "failed to create service",
+						name,
+						reason = format!("{}", std::error::Report::new(&err))
+					);
+					self.error_log.push(name, &err);
+					report.failed.push((name, err));
+				}
 			}
 		}
-		Runtime {
+		if !report.failed.is_empty() {
+			self.controller.shutdown();
+			super::stop(stop_hooks, &self.error_log).await;
+			return Err(Error::Services(report));
+		}
+		self.monitor.set_ready();
+		Ok(Runtime {
+			reloader: Arc::new(config::Reloader {
+				path: self.config_path,
+				config: self.config,
+				errors: self.error_log.clone(),
+			}),
 			controller: self.controller,
-			session_handles: self.session_handles,
+			session_handles,
+			services: Services {
+				registry: self.registry,
+				handles: self.handles,
+				stop_hooks: Arc::new(Mutex::new(stop_hooks)),
+				supervisor,
+			},
+			graph: self.graph,
+			monitor: self.monitor,
+			errors: self.error_log,
+			_health: self.health,
+		})
+	}
+
+	/// Checks that all dependencies of services and sessions are registered and services do not
+	/// depend on each other in a cycle. Returns the names of all services in topological order
+	/// (dependencies first).
+	fn validate(&self) -> Result<Vec<&'static str>, Error> {
+		let graph: HashMap<_, _> = self
+			.services
+			.iter()
+			.map(|service| (service.name, service.dependencies.as_slice()))
+			.collect();
+		{
+			let registry = self
+				.registry
+				.read()
+				.map_err(|_| registry::Error::Poisoned)?;
+			if let Some(service) = self
+				.services
+				.iter()
+				.enumerate()
+				.find(|(i, service)| {
+					registry.contains_key(service.name)
+						|| self.services[..*i].iter().any(|s| s.name == service.name)
+				})
+				.map(|(_, service)| service)
+			{
+				return Err(Error::Duplicate(service.name.to_string()));
+			}
+			for pending in self.services.iter().chain(&self.sessions) {
+				let missing = pending
+					.dependencies
+					.iter()
+					.find(|dep| !graph.contains_key(*dep) && !registry.contains_key(**dep));
+				if let Some(dependency) = missing {
+					return Err(Error::Missing {
+						service: pending.name,
+						dependency,
+					});
+				}
+			}
+		}
+		let mut visited = Vec::new();
+		for service in &self.services {
+			let mut path = Vec::new();
+			if let Some(cycle) = find_cycle(&graph, service.name, &mut path, &mut visited) {
+				return Err(Error::Cycle(cycle));
+			}
+		}
+		Ok(visited)
+	}
+}
+
+/// Returns the first dependency of `S` which failed to be created.
+fn failed_dependency<S: Info>(
+	failed: &mut watch::Receiver<Vec<&'static str>>,
+) -> Option<&'static str> {
+	let failed = failed.borrow_and_update();
+	S::dependencies()
+		.into_iter()
+		.find(|dependency| failed.contains(dependency))
+}
+
+/// Formats each of `errors` on its own line.
+fn list(errors: &[Error]) -> String {
+	errors
+		.iter()
+		.map(|err| format!("  {}", std::error::Report::new(err)))
+		.collect::<Vec<_>>()
+		.join("\n")
+}
+
+/// Depth-first search for a dependency cycle starting at `name`. `path` contains the services
+/// currently visited and `visited` all services already known to be free of cycles in
+/// topological order.
+fn find_cycle(
+	graph: &HashMap<&'static str, &[&'static str]>,
+	name: &'static str,
+	path: &mut Vec<&'static str>,
+	visited: &mut Vec<&'static str>,
+) -> Option<Vec<&'static str>> {
+	if let Some(pos) = path.iter().position(|n| *n == name) {
+		let mut cycle = path[pos..].to_vec();
+		cycle.push(name);
+		return Some(cycle);
+	}
+	if visited.contains(&name) {
+		return None;
+	}
+	path.push(name);
+	for dep in graph.get(name).copied().unwrap_or_default() {
+		if let Some(cycle) = find_cycle(graph, dep, path, visited) {
+			return Some(cycle);
 		}
 	}
+	path.pop();
+	visited.push(name);
+	None
 }
@@ -0,0 +1,481 @@
+//! Runtime configuration read from a TOML file and environment variables. Overrides the buffer
+//! size, pool size and request timeout of services by name and defines session bindings (see
+//! [`super::builder::Builder::expose`]).
+//!
+//! # Format
+//!
+//! ```toml
+//! [services.parse_str]
+//! buffer = 64
+//! pool = 8
+//! timeout = "2s"
+//!
+//! [sessions.parse_str]
+//! addr = "0.0.0.0:4000"
+//!
+//! [log]
+//! filter = "info,micro_tower=debug"
+//! ```
+//!
+//! Pool sizes are either a positive integer or a range like `"2..16"` which scales the pool by its
+//! load (see [`crate::service::pool::Size`]). Durations are strings with one of the units `ms`,
+//! `s`, `m` or `h`.
+//!
+//! # Environment
+//!
+//! Variables of the form `MICRO_TOWER_<SECTION>__<NAME>__<KEY>` override values of the file (e.g.
+//! `MICRO_TOWER_SERVICES__PARSE_STR__POOL=16`). Names are case insensitive. Variables of other
+//! sections are ignored.
+//!
+//! # Reload
+//!
+//! A configuration read from a file (see [`super::builder::Builder::config_file`]) is read again
+//! on SIGHUP (see [`super::Runtime::reload`]). Pool sizes and the log filter are applied live,
+//! all other changes require a restart and are logged as such.
+
+use super::admin::ErrorLog;
+use super::registry::Key;
+use crate::service::pool;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+use std::collections::{BTreeSet, HashMap};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use toml::value::{Table, Value};
+
+/// Registry key of the runtime configuration. Used by services to look up their settings.
+pub const KEY: Key<Watch> = Key::new("micro_tower::config");
+
+/// Prefix of environment variables which override the configuration.
+pub const ENV_PREFIX: &str = "MICRO_TOWER_";
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error("failed to read config file `{}`", .path.display())]
+	Read {
+		path: PathBuf,
+		#[source]
+		source: std::io::Error,
+	},
+	#[error("invalid toml: {message}")]
+	Syntax { line: usize, message: String },
+	#[error("invalid value of `{key}`: {message}")]
+	Value { key: String, message: String },
+	#[error("unknown config key `{0}`")]
+	Unknown(String),
+}
+
+/// Settings of a single service. Unset values fall back to the arguments of `#[service]`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServiceConfig {
+	#[serde(deserialize_with = "size")]
+	pub buffer: Option<usize>,
+	#[serde(deserialize_with = "pool_size")]
+	pub pool: Option<pool::Size>,
+	/// Maximum duration of a single request (including time spent waiting in the buffer).
+	#[serde(deserialize_with = "duration")]
+	pub timeout: Option<Duration>,
+}
+
+/// Configuration of services and sessions by service name.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	services: HashMap<String, ServiceConfig>,
+	#[serde(deserialize_with = "sessions")]
+	sessions: HashMap<String, SocketAddr>,
+	log: LogConfig,
+}
+
+/// Settings of a session.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SessionConfig {
+	addr: SocketAddr,
+}
+
+/// Settings of the log subscriber.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LogConfig {
+	#[serde(deserialize_with = "filter")]
+	filter: Option<String>,
+}
+
+/// Current configuration of a runtime. Clones observe the same configuration, which is replaced
+/// on reload.
+#[derive(Clone, Debug)]
+pub struct Watch {
+	rx: watch::Receiver<Config>,
+}
+
+/// Settings changed by a reload (see [`Config::diff`]).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Changes {
+	/// Keys of settings which were applied to the running runtime.
+	pub live: Vec<String>,
+	/// Keys of settings which only take effect after a restart.
+	pub restart: Vec<String>,
+}
+
+/// Reads the configuration file again and publishes the result to all [`Watch`]es.
+pub(crate) struct Reloader {
+	pub path: Option<PathBuf>,
+	pub config: Arc<watch::Sender<Config>>,
+	pub errors: ErrorLog,
+}
+
+/// Value of a single key. Values of environment variables are always strings.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Scalar {
+	Integer(i64),
+	String(String),
+}
+
+impl Config {
+	/// Reads the configuration from the TOML file at `path` and applies overrides of environment
+	/// variables (see [`Config::with_env`]).
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if the file cannot be read or contains invalid or unknown keys.
+	pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+		Self::from_file(path)?.with_env()
+	}
+
+	/// Reads the configuration from the TOML file at `path`.
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if the file cannot be read or contains invalid or unknown keys.
+	pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+		let path = path.as_ref();
+		let text = std::fs::read_to_string(path).map_err(|source| Error::Read {
+			path: path.to_path_buf(),
+			source,
+		})?;
+		text.parse()
+	}
+
+	/// Applies all environment variables starting with [`ENV_PREFIX`].
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if a variable sets an invalid or unknown key.
+	pub fn with_env(self) -> Result<Self, Error> {
+		self.with_vars(std::env::vars())
+	}
+
+	/// Same as [`Config::with_env`] but reads variables from `vars`.
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if a variable sets an invalid or unknown key.
+	pub fn with_vars(
+		self,
+		vars: impl IntoIterator<Item = (String, String)>,
+	) -> Result<Self, Error> {
+		let mut overrides = Table::new();
+		for (name, value) in vars {
+			let Some(path) = name.strip_prefix(ENV_PREFIX) else {
+				continue;
+			};
+			let path: Vec<_> = path.split("__").map(str::to_lowercase).collect();
+			if !matches!(path[0].as_str(), "services" | "sessions" | "log") {
+				continue;
+			}
+			insert(&mut overrides, &path, Value::String(value));
+		}
+		Ok(self.merge(Self::deserialize_value(&Value::Table(overrides))?))
+	}
+
+	/// Returns the settings of service `name`.
+	#[must_use]
+	pub fn service(&self, name: &str) -> ServiceConfig {
+		self.services
+			.get(&name.to_lowercase())
+			.copied()
+			.unwrap_or_default()
+	}
+
+	/// Returns the address the session of service `name` should be bound to.
+	#[must_use]
+	pub fn session(&self, name: &str) -> Option<SocketAddr> {
+		self.sessions.get(&name.to_lowercase()).copied()
+	}
+
+	/// Returns the log filter (see [`crate::telemetry::set_filter`]).
+	#[must_use]
+	pub fn log_filter(&self) -> Option<&str> {
+		self.log.filter.as_deref()
+	}
+
+	/// Returns the keys of all settings which differ between `self` and `new`.
+	#[must_use]
+	pub fn diff(&self, new: &Self) -> Changes {
+		let mut changes = Changes::default();
+		let names: BTreeSet<_> = self.services.keys().chain(new.services.keys()).collect();
+		for name in names {
+			let (old, new) = (self.service(name), new.service(name));
+			let key = |field: &str| format!("services.{name}.{field}");
+			if old.pool != new.pool {
+				changes.live.push(key("pool"));
+			}
+			if old.buffer != new.buffer {
+				changes.restart.push(key("buffer"));
+			}
+			if old.timeout != new.timeout {
+				changes.restart.push(key("timeout"));
+			}
+		}
+		let names: BTreeSet<_> = self.sessions.keys().chain(new.sessions.keys()).collect();
+		for name in names {
+			if self.session(name) != new.session(name) {
+				changes.restart.push(format!("sessions.{name}.addr"));
+			}
+		}
+		if self.log.filter != new.log.filter {
+			changes.live.push("log.filter".to_string());
+		}
+		changes
+	}
+
+	/// Deserializes a configuration from `value`. Names of services and sessions are case
+	/// insensitive.
+	fn deserialize_value(value: &Value) -> Result<Self, Error> {
+		let mut config =
+			Self::deserialize(value.clone()).map_err(|err| locate(&mut Vec::new(), value, &err))?;
+		config.services = config
+			.services
+			.into_iter()
+			.map(|(name, service)| (name.to_lowercase(), service))
+			.collect();
+		config.sessions = config
+			.sessions
+			.into_iter()
+			.map(|(name, addr)| (name.to_lowercase(), addr))
+			.collect();
+		Ok(config)
+	}
+
+	/// Overrides the settings of `self` with all settings of `other`.
+	fn merge(mut self, other: Self) -> Self {
+		for (name, new) in other.services {
+			let service = self.services.entry(name).or_default();
+			service.buffer = new.buffer.or(service.buffer);
+			service.pool = new.pool.or(service.pool);
+			service.timeout = new.timeout.or(service.timeout);
+		}
+		self.sessions.extend(other.sessions);
+		self.log.filter = other.log.filter.or(self.log.filter);
+		self
+	}
+}
+
+impl Watch {
+	/// Returns a copy of the current configuration.
+	#[must_use]
+	pub fn get(&self) -> Config {
+		self.rx.borrow().clone()
+	}
+
+	/// Returns the current settings of service `name`.
+	#[must_use]
+	pub fn service(&self, name: &str) -> ServiceConfig {
+		self.rx.borrow().service(name)
+	}
+
+	/// Waits until the configuration was replaced. Returns `false` if the runtime was dropped.
+	pub async fn changed(&mut self) -> bool {
+		self.rx.changed().await.is_ok()
+	}
+}
+
+impl From<watch::Receiver<Config>> for Watch {
+	fn from(rx: watch::Receiver<Config>) -> Self {
+		Self { rx }
+	}
+}
+
+impl Reloader {
+	/// Reads the configuration file again and applies the new configuration. Changes and errors
+	/// are logged. Does nothing if the configuration was not read from a file.
+	pub fn reload(&self) -> Result<Changes, Error> {
+		let Some(path) = &self.path else {
+			tracing::warn!("configuration was not read from a file, nothing to reload");
+			return Ok(Changes::default());
+		};
+		let path = path.display().to_string();
+		let new = match Config::load(&path) {
+			Ok(config) => config,
+			Err(err) => {
+				tracing::error!(
+					message = "failed to reload configuration",
+					path,
+					reason = format!("{}", std::error::Report::new(&err))
+				);
+				self.errors.push("config", &err);
+				return Err(err);
+			}
+		};
+		let changes = self.config.borrow().diff(&new);
+		if changes.live.contains(&"log.filter".to_string()) {
+			apply_log_filter(&new);
+		}
+		self.config.send_replace(new);
+		for key in &changes.live {
+			tracing::info!(message = "applied configuration change", key);
+		}
+		for key in &changes.restart {
+			tracing::warn!(message = "configuration change requires restart", key);
+		}
+		tracing::info!(message = "configuration reloaded", path);
+		Ok(changes)
+	}
+}
+
+/// Replaces the log filter with the filter of `config` if set.
+pub(crate) fn apply_log_filter(config: &Config) {
+	let Some(filter) = config.log_filter() else {
+		return;
+	};
+	match crate::telemetry::set_filter(filter) {
+		Ok(()) => tracing::info!(message = "log filter changed", filter),
+		Err(crate::telemetry::Error::Unsupported) => {
+			tracing::debug!("log filter not changed, subscriber was installed by the application");
+		}
+		Err(err) => {
+			let report = crate::report!(err);
+			tracing::error!("{report:?}");
+		}
+	}
+}
+
+impl FromStr for Config {
+	type Err = Error;
+
+	/// Parses a configuration from TOML. Environment variables are not applied.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let value = s.parse::<Value>().map_err(|err| Error::Syntax {
+			line: err.line_col().map_or(0, |(line, _)| line + 1),
+			message: format!("{err}"),
+		})?;
+		Self::deserialize_value(&value)
+	}
+}
+
+/// Inserts `value` at `path` into `table`. Missing tables are created.
+fn insert(table: &mut Table, path: &[String], value: Value) {
+	match path {
+		[] => {}
+		[key] => {
+			table.insert(key.clone(), value);
+		}
+		[key, path @ ..] => {
+			let entry = table
+				.entry(key.clone())
+				.or_insert_with(|| Value::Table(Table::new()));
+			if !entry.is_table() {
+				*entry = Value::Table(Table::new());
+			}
+			if let Value::Table(table) = entry {
+				insert(table, path, value);
+			}
+		}
+	}
+}
+
+/// Narrows down the error `err` of deserializing `value` at `path` to the first key which fails
+/// on its own (e.g. `services.parse_str.pool`).
+fn locate(path: &mut Vec<String>, value: &Value, err: &toml::de::Error) -> Error {
+	if let Value::Table(table) = value {
+		for (key, value) in table {
+			path.push(key.clone());
+			let nested = path.iter().rev().fold(value.clone(), |value, key| {
+				Value::Table(Table::from_iter([(key.clone(), value)]))
+			});
+			if let Err(err) = Config::deserialize(nested) {
+				return locate(path, value, &err);
+			}
+			path.pop();
+		}
+	}
+	let key = path.join(".");
+	let message = format!("{err}");
+	if message.starts_with("unknown field") {
+		Error::Unknown(key)
+	} else {
+		Error::Value { key, message }
+	}
+}
+
+/// Returns `scalar` as positive size.
+fn positive(scalar: &Scalar) -> Option<usize> {
+	let size = match scalar {
+		Scalar::Integer(size) => usize::try_from(*size).ok(),
+		Scalar::String(size) => size.trim().parse().ok(),
+	};
+	size.filter(|size| *size > 0)
+}
+
+/// Parses a positive size.
+fn size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<usize>, D::Error> {
+	positive(&Scalar::deserialize(deserializer)?)
+		.map(Some)
+		.ok_or_else(|| D::Error::custom("expected positive integer"))
+}
+
+/// Parses a positive size or a range of sizes (e.g. `"2..16"`).
+fn pool_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<pool::Size>, D::Error> {
+	match Scalar::deserialize(deserializer)? {
+		Scalar::String(size) => size.parse().map(Some).map_err(D::Error::custom),
+		scalar @ Scalar::Integer(_) => positive(&scalar)
+			.map(|size| Some(pool::Size::fixed(size)))
+			.ok_or_else(|| D::Error::custom("expected positive integer")),
+	}
+}
+
+/// Parses a duration with unit (e.g. `250ms`).
+fn duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+	let err = || D::Error::custom("expected duration with unit `ms`, `s`, `m` or `h`");
+	let Scalar::String(value) = Scalar::deserialize(deserializer)? else {
+		return Err(err());
+	};
+	let value = value.trim();
+	let split = value.find(|c: char| !c.is_ascii_digit()).ok_or_else(err)?;
+	let (amount, unit) = value.split_at(split);
+	let amount: u64 = amount.parse().map_err(|_| err())?;
+	match unit {
+		"ms" => Ok(Some(Duration::from_millis(amount))),
+		"s" => Ok(Some(Duration::from_secs(amount))),
+		"m" => Ok(Some(Duration::from_secs(amount * 60))),
+		"h" => Ok(Some(Duration::from_secs(amount * 3600))),
+		_ => Err(err()),
+	}
+}
+
+/// Parses the `addr` of each session.
+fn sessions<'de, D: Deserializer<'de>>(
+	deserializer: D,
+) -> Result<HashMap<String, SocketAddr>, D::Error> {
+	let sessions = HashMap::<String, SessionConfig>::deserialize(deserializer)?;
+	Ok(sessions
+		.into_iter()
+		.map(|(name, session)| (name, session.addr))
+		.collect())
+}
+
+/// Parses a log filter (see [`crate::telemetry`]).
+fn filter<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+	let filter = String::deserialize(deserializer)?;
+	filter
+		.parse::<tracing_subscriber::filter::Targets>()
+		.map_err(D::Error::custom)?;
+	Ok(Some(filter))
+}
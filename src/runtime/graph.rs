@@ -0,0 +1,96 @@
+//! Dependency graph of a runtime. Lists all registered services, their inner services and the
+//! sessions which bind them. Can be exported in DOT and JSON format.
+
+use serde::Serialize;
+use std::fmt::Write;
+
+/// Kind of a node in the dependency graph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+	/// Service created and run by the runtime.
+	Service,
+	/// Service of another process called via a client.
+	Remote,
+	/// Managed object (see [`super::builder::Builder::manage`]).
+	Object,
+}
+
+/// Service or object registered at the runtime.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Node {
+	pub name: String,
+	pub kind: Kind,
+	/// Names of all inner services and objects required by this node.
+	pub dependencies: Vec<String>,
+}
+
+/// Session which binds a service.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Binding {
+	pub service: String,
+	/// Endpoint the session listens on (see [`crate::session::Session::endpoint`]).
+	pub endpoint: Option<String>,
+}
+
+/// Dependency graph of a runtime.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Graph {
+	pub nodes: Vec<Node>,
+	pub sessions: Vec<Binding>,
+}
+
+impl Graph {
+	/// Returns node with `name` if exists.
+	#[must_use]
+	pub fn node(&self, name: &str) -> Option<&Node> {
+		self.nodes.iter().find(|node| node.name == name)
+	}
+
+	/// Adds a node to the graph. Will replace an existing node with the same name.
+	pub(crate) fn insert(&mut self, node: Node) {
+		match self.nodes.iter_mut().find(|n| n.name == node.name) {
+			Some(n) => *n = node,
+			None => self.nodes.push(node),
+		}
+	}
+
+	/// Returns graph in DOT format (see <https://graphviz.org/doc/info/lang.html>).
+	#[must_use]
+	pub fn to_dot(&self) -> String {
+		let mut dot = String::from("digraph runtime {\n");
+		for node in &self.nodes {
+			let attrs = match node.kind {
+				Kind::Service => "shape=box",
+				Kind::Remote => "shape=box, style=dashed",
+				Kind::Object => "shape=note",
+			};
+			let _ = writeln!(dot, "\t{} [{attrs}];", quote(&node.name));
+		}
+		for node in &self.nodes {
+			for dep in &node.dependencies {
+				let _ = writeln!(dot, "\t{} -> {};", quote(&node.name), quote(dep));
+			}
+		}
+		for (i, session) in self.sessions.iter().enumerate() {
+			let label = session.endpoint.as_deref().unwrap_or("session");
+			let _ = writeln!(dot, "\tsession{i} [shape=ellipse, label={}];", quote(label));
+			let _ = writeln!(dot, "\tsession{i} -> {};", quote(&session.service));
+		}
+		dot.push_str("}\n");
+		dot
+	}
+
+	/// Returns graph in JSON format.
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if failed to serialize graph.
+	pub fn to_json(&self) -> serde_json::Result<String> {
+		serde_json::to_string_pretty(self)
+	}
+}
+
+fn quote(id: &str) -> String {
+	format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,205 @@
+//! Aggregates the health of all services, pools and sessions of a runtime. Provides readiness and
+//! liveness signals which can be used by orchestrators.
+
+use super::registry::Key;
+use crate::service::{Health, Status};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock, Weak};
+
+/// Registry key of the runtime [`Monitor`]. Used by services to register their own components
+/// (e.g. [`crate::service::pool::Pool`]).
+pub const KEY: Key<Monitor> = Key::new("micro_tower::health");
+
+/// Kind of a component registered at a [`Monitor`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+	/// Service created by the runtime (including service pools).
+	Service,
+	/// Pool of clients to replicas of a remote service.
+	Remote,
+	/// Session accepting connections.
+	Session,
+}
+
+/// Health status which is set by its owner.
+#[derive(Debug)]
+pub struct Indicator {
+	status: AtomicU8,
+	members: Option<AtomicUsize>,
+}
+
+/// Current health of a single component.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Check {
+	pub name: String,
+	pub kind: Kind,
+	pub status: Status,
+	/// Number of members if the component is a pool.
+	pub members: Option<usize>,
+}
+
+struct Component {
+	name: String,
+	kind: Kind,
+	health: Weak<dyn Health + Send + Sync>,
+}
+
+#[derive(Default)]
+struct Inner {
+	components: RwLock<Vec<Component>>,
+	ready: AtomicBool,
+}
+
+/// Aggregates the health of all registered components. Components are only referenced weakly and
+/// removed as soon as they are dropped.
+#[derive(Clone, Default)]
+pub struct Monitor {
+	inner: Arc<Inner>,
+}
+
+impl Indicator {
+	#[must_use]
+	pub fn new(status: Status) -> Self {
+		Self {
+			status: AtomicU8::new(status as u8),
+			members: None,
+		}
+	}
+
+	/// Same as [`Indicator::new`] but for a pool of `members` services.
+	#[must_use]
+	pub fn pool(status: Status, members: usize) -> Self {
+		Self {
+			status: AtomicU8::new(status as u8),
+			members: Some(AtomicUsize::new(members)),
+		}
+	}
+
+	/// Updates the reported status.
+	pub fn set(&self, status: Status) {
+		self.status.store(status as u8, Ordering::SeqCst);
+	}
+
+	/// Updates the reported number of members. Does nothing unless created by
+	/// [`Indicator::pool`].
+	pub fn set_members(&self, members: usize) {
+		if let Some(current) = &self.members {
+			current.store(members, Ordering::SeqCst);
+		}
+	}
+}
+
+impl Health for Indicator {
+	fn health(&self) -> Status {
+		match self.status.load(Ordering::SeqCst) {
+			0 => Status::Healthy,
+			1 => Status::Degraded,
+			_ => Status::Unhealthy,
+		}
+	}
+
+	fn members(&self) -> Option<usize> {
+		self.members
+			.as_ref()
+			.map(|members| members.load(Ordering::SeqCst))
+	}
+}
+
+impl Monitor {
+	/// Registers a component. Multiple components may be registered with the same name (e.g. one
+	/// pool per connection), the worst status is reported.
+	///
+	/// # Panics
+	///
+	/// Will panic if internal lock is poisoned.
+	pub fn register<H: Health + Send + Sync + 'static>(
+		&self,
+		name: impl Into<String>,
+		kind: Kind,
+		health: &Arc<H>,
+	) {
+		let health: Arc<dyn Health + Send + Sync> = Arc::clone(health) as _;
+		let mut components = self.inner.components.write().unwrap();
+		components.retain(|component| component.health.strong_count() > 0);
+		components.push(Component {
+			name: name.into(),
+			kind,
+			health: Arc::downgrade(&health),
+		});
+	}
+
+	/// Returns the current health of all components. Components registered with the same name and
+	/// kind are merged (worst status, sum of members).
+	///
+	/// # Panics
+	///
+	/// Will panic if internal lock is poisoned.
+	#[must_use]
+	pub fn checks(&self) -> Vec<Check> {
+		let components = self.inner.components.read().unwrap();
+		let mut checks: Vec<Check> = Vec::new();
+		for component in components.iter() {
+			let Some(health) = component.health.upgrade() else {
+				continue;
+			};
+			let status = health.health();
+			let members = health.members();
+			match checks
+				.iter_mut()
+				.find(|check| check.name == component.name && check.kind == component.kind)
+			{
+				Some(check) => {
+					check.status = check.status.max(status);
+					check.members = match (check.members, members) {
+						(Some(a), Some(b)) => Some(a + b),
+						(a, b) => a.or(b),
+					};
+				}
+				None => checks.push(Check {
+					name: component.name.clone(),
+					kind: component.kind,
+					status,
+					members,
+				}),
+			}
+		}
+		checks
+	}
+
+	/// Returns the worst status of all components.
+	#[must_use]
+	pub fn status(&self) -> Status {
+		self.checks()
+			.into_iter()
+			.map(|check| check.status)
+			.max()
+			.unwrap_or(Status::Healthy)
+	}
+
+	/// Returns `true` once all services were created and all sessions are accepting connections.
+	/// Turns `false` again if a service becomes unhealthy or a session stops.
+	#[must_use]
+	pub fn is_ready(&self) -> bool {
+		self.inner.ready.load(Ordering::SeqCst)
+			&& self.checks().iter().all(|check| match check.kind {
+				Kind::Service => check.status != Status::Unhealthy,
+				Kind::Session => check.status == Status::Healthy,
+				Kind::Remote => true,
+			})
+	}
+
+	/// Returns `false` if any service is unhealthy (e.g. a service pool failed to be created).
+	#[must_use]
+	pub fn is_live(&self) -> bool {
+		self.checks()
+			.iter()
+			.all(|check| check.kind != Kind::Service || check.status != Status::Unhealthy)
+	}
+
+	/// Marks the runtime as built.
+	pub(crate) fn set_ready(&self) {
+		self.inner.ready.store(true, Ordering::SeqCst);
+	}
+}
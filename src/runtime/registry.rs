@@ -1,29 +1,158 @@
+use crate::service::Managed;
 use std::any::Any;
 use std::borrow::Borrow;
 use std::collections::HashMap;
+use std::fmt::Display;
 use std::hash::Hash;
+use std::marker::PhantomData;
+use tokio::sync::watch;
 
 #[derive(Debug, thiserror::Error)]
-#[error("Failed to convert entry")]
-pub struct Error;
+pub enum Error {
+	#[error("Entry `{key}` is of type `{actual}` but expected `{expected}`")]
+	Mismatch {
+		key: String,
+		expected: &'static str,
+		actual: &'static str,
+	},
+	#[error("Registry lock poisoned")]
+	Poisoned,
+	#[error("Object `{0}` not registered")]
+	Missing(String),
+}
+
+/// Typed key of a registry entry. Lookups with a key will fail with [`Error::Mismatch`] if the
+/// entry is not of type `T`.
+pub struct Key<T: ?Sized> {
+	name: &'static str,
+	_p: PhantomData<fn() -> T>,
+}
+
+impl<T: ?Sized> Key<T> {
+	#[must_use]
+	pub const fn new(name: &'static str) -> Self {
+		Self {
+			name,
+			_p: PhantomData,
+		}
+	}
+
+	/// Returns the name of the entry.
+	#[must_use]
+	pub fn name(&self) -> &'static str {
+		self.name
+	}
+}
+
+impl<T: ?Sized> Clone for Key<T> {
+	fn clone(&self) -> Self {
+		*self
+	}
+}
+
+impl<T: ?Sized> Copy for Key<T> {}
+
+impl<S: crate::service::Info> Key<crate::service::Service<S>> {
+	/// Returns key of service `S` (see [`crate::service::Info::name`]).
+	#[must_use]
+	pub fn service() -> Self {
+		Self::new(S::name())
+	}
+}
+
+struct Entry {
+	value: Box<dyn Any + Send + Sync>,
+	type_name: &'static str,
+}
 
 /// Registry to store any type by name/identifier respectively a hash map of any object.
-#[derive(Default)]
 pub struct Type {
-	data: HashMap<String, Box<dyn Any + Send + Sync>>,
+	data: HashMap<String, Entry>,
+	version: watch::Sender<usize>,
+}
+
+impl Default for Type {
+	fn default() -> Self {
+		let (version, _) = watch::channel(0);
+		Self {
+			data: HashMap::default(),
+			version,
+		}
+	}
 }
 
 impl Type {
-	/// Insert object into registry. Will return the old value of this `key` (if exist)
+	/// Insert object into registry. Will return the old value of this `key` (if exist). Notifies
+	/// all subscribers (see [`Self::subscribe`]).
 	pub fn insert<T: Send + Sync + 'static>(
 		&mut self,
 		key: impl Into<String>,
 		value: Box<T>,
 	) -> Option<Box<dyn Any + Send + Sync>> {
-		self.data.insert(key.into(), value)
+		let entry = Entry {
+			value,
+			type_name: std::any::type_name::<T>(),
+		};
+		let old = self.data.insert(key.into(), entry);
+		self.version.send_modify(|version| *version += 1);
+		old.map(|entry| entry.value)
+	}
+
+	/// Same as [`Self::insert`] but uses a typed `key`.
+	pub fn insert_key<T: Send + Sync + 'static>(
+		&mut self,
+		key: Key<T>,
+		value: T,
+	) -> Option<Box<dyn Any + Send + Sync>> {
+		self.insert(key.name(), Box::new(value))
+	}
+
+	/// Removes the entry with `key`. Will return the removed value (if exist). Notifies all
+	/// subscribers (see [`Self::subscribe`]).
+	pub fn remove<Q>(&mut self, key: &Q) -> Option<Box<dyn Any + Send + Sync>>
+	where
+		String: Borrow<Q>,
+		Q: ?Sized + Hash + Eq,
+	{
+		let old = self.data.remove(key)?;
+		self.version.send_modify(|version| *version += 1);
+		Some(old.value)
+	}
+
+	/// Returns a receiver which is notified each time an object is inserted into or removed from
+	/// the registry.
+	#[must_use]
+	pub fn subscribe(&self) -> watch::Receiver<usize> {
+		self.version.subscribe()
+	}
+
+	/// Returns `true` if an entry with `key` exists.
+	pub fn contains_key<Q>(&self, key: &Q) -> bool
+	where
+		String: Borrow<Q>,
+		Q: ?Sized + Hash + Eq,
+	{
+		self.data.contains_key(key)
+	}
+
+	/// Returns the keys and type names of all entries in arbitrary order.
+	pub fn entries(&self) -> impl Iterator<Item = (&str, &'static str)> {
+		self.data
+			.iter()
+			.map(|(key, entry)| (key.as_str(), entry.type_name))
+	}
+
+	/// Returns the type name of the entry with `key` (if exist).
+	pub fn type_name<Q>(&self, key: &Q) -> Option<&'static str>
+	where
+		String: Borrow<Q>,
+		Q: ?Sized + Hash + Eq,
+	{
+		self.data.get(key).map(|entry| entry.type_name)
 	}
 
-	/// Get value of entry with `key`. Will return `Ok(None)` if key not exists.
+	/// Get value of entry with `key`. Will return `Ok(None)` if key not exists. Entries of type
+	/// [`Managed<T>`] can also be accessed as `T`.
 	///
 	/// # Errors
 	///
@@ -31,13 +160,40 @@ impl Type {
 	pub fn get<Q, T: 'static>(&self, key: &Q) -> Result<Option<&T>, Error>
 	where
 		String: Borrow<Q>,
-		Q: ?Sized + Hash + Eq,
+		Q: ?Sized + Hash + Eq + Display,
 	{
-		self.data
-			.get(key)
-			.map(|v| v.downcast_ref::<T>())
-			.map(|v| v.ok_or(Error))
-			.transpose()
+		let Some(entry) = self.data.get(key) else {
+			return Ok(None);
+		};
+		entry
+			.value
+			.downcast_ref::<T>()
+			.or_else(|| entry.value.downcast_ref::<Managed<T>>().map(|m| &**m))
+			.map(Some)
+			.ok_or_else(|| mismatch::<_, T>(key, entry))
+	}
+
+	/// Same as [`Self::get`] but uses a typed `key`.
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if value exists but is not of type `T`.
+	pub fn get_key<T: 'static>(&self, key: &Key<T>) -> Result<Option<&T>, Error> {
+		self.get(key.name())
+	}
+
+	/// Returns the only entry of type `T`. Will return `None` if no or multiple entries of type `T`
+	/// exist.
+	#[must_use]
+	pub fn find<T: 'static>(&self) -> Option<&T> {
+		let mut entries = self
+			.data
+			.values()
+			.filter_map(|entry| entry.value.downcast_ref::<T>());
+		match (entries.next(), entries.next()) {
+			(Some(entry), None) => Some(entry),
+			_ => None,
+		}
 	}
 
 	/// Same as [`Self::get`] but returns a mutable reference to `T`. Will return `Ok(None)` if key
@@ -49,19 +205,36 @@ impl Type {
 	pub fn get_mut<Q, T: 'static>(&mut self, key: &Q) -> Result<Option<&mut T>, Error>
 	where
 		String: Borrow<Q>,
-		Q: ?Sized + Hash + Eq,
+		Q: ?Sized + Hash + Eq + Display,
 	{
-		self.data
-			.get_mut(key)
-			.map(|v| v.downcast_mut::<T>())
-			.map(|v| v.ok_or(Error))
-			.transpose()
+		let Some(entry) = self.data.get_mut(key) else {
+			return Ok(None);
+		};
+		let type_name = entry.type_name;
+		entry
+			.value
+			.downcast_mut::<T>()
+			.map(Some)
+			.ok_or_else(|| Error::Mismatch {
+				key: key.to_string(),
+				expected: std::any::type_name::<T>(),
+				actual: type_name,
+			})
+	}
+}
+
+fn mismatch<Q: ?Sized + Display, T>(key: &Q, entry: &Entry) -> Error {
+	Error::Mismatch {
+		key: key.to_string(),
+		expected: std::any::type_name::<T>(),
+		actual: entry.type_name,
 	}
 }
 
 #[cfg(test)]
 mod tests {
-	use super::Type;
+	use super::{Error, Key, Type};
+	use crate::service::Managed;
 
 	#[test]
 	pub fn default() {
@@ -99,6 +272,61 @@ mod tests {
 		assert!(registry.get_mut::<_, i32>("key2").unwrap().is_none());
 	}
 
+	#[test]
+	pub fn get_managed() {
+		let mut registry = Type::default();
+
+		registry.insert("key", Box::new(Managed::new(42_i32)));
+		assert_eq!(*registry.get::<_, i32>("key").unwrap().unwrap(), 42);
+		assert_eq!(
+			**registry.get::<_, Managed<i32>>("key").unwrap().unwrap(),
+			42
+		);
+	}
+
+	#[test]
+	pub fn remove() {
+		let mut registry = Type::default();
+		let mut changes = registry.subscribe();
+
+		registry.insert("key", Box::new(42_i32));
+		assert!(registry.remove("key").is_some());
+		assert!(registry.remove("key").is_none());
+		assert!(!registry.contains_key("key"));
+		assert_eq!(*changes.borrow_and_update(), 2);
+	}
+
+	#[test]
+	pub fn find() {
+		let mut registry = Type::default();
+
+		registry.insert("key", Box::new(42_i32));
+		registry.insert("key2", Box::new(42_usize));
+		assert_eq!(*registry.find::<i32>().unwrap(), 42);
+		registry.insert("key3", Box::new(24_i32));
+		assert!(registry.find::<i32>().is_none());
+	}
+
+	#[test]
+	pub fn get_key() {
+		let mut registry = Type::default();
+
+		registry.insert_key(Key::<i32>::new("key"), 42);
+		assert_eq!(
+			*registry.get_key(&Key::<i32>::new("key")).unwrap().unwrap(),
+			42
+		);
+		let err = registry.get_key(&Key::<usize>::new("key")).unwrap_err();
+		assert!(matches!(
+			err,
+			Error::Mismatch {
+				key,
+				expected: "usize",
+				actual: "i32"
+			} if key == "key"
+		));
+	}
+
 	#[test]
 	pub fn get_error() {
 		let mut registry = Type::default();
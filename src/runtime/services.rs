@@ -0,0 +1,228 @@
+//! Adds, replaces and removes services of a running runtime.
+//!
+//! Every service registered by [`super::builder::Builder::service`] or [`Services::register`] is
+//! swappable (see [`crate::service::swap`]). Services which already hold a handle to a replaced
+//! service route new requests to the new implementation, calls in flight are completed by the
+//! old one. Sessions create a new service per connection, so only their dependencies are
+//! swapped.
+//!
+//! # Usage
+//!
+//! ```rust,no_run
+//! # #![feature(error_reporter)]
+//! use micro_tower::runtime::Runtime;
+//! use micro_tower::service::Service;
+//! use micro_tower::util::{BoxError, BoxFuture};
+//!
+//! #[micro_tower::codegen::service(buffer = 1)]
+//! async fn model(request: u32) -> u32 {
+//!     request
+//! }
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let runtime = Runtime::builder().service::<model>().build().await?;
+//! let services = runtime.services();
+//! tokio::spawn(runtime.run());
+//! let model_v2 = tower::service_fn(|request: u32| -> BoxFuture<Result<u32, BoxError>> {
+//!     Box::pin(async move { Ok(request * 2) })
+//! });
+//! services.replace::<model>(Service::from(Box::new(model_v2)))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use super::builder::StopFn;
+use super::registry;
+use super::supervisor::Supervisor;
+use crate::service::{swap, Create, Info, Service};
+use std::sync::{Arc, Mutex, RwLock};
+use tower::BoxError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error("service `{0}` is not registered")]
+	Missing(&'static str),
+	#[error("service or object `{0}` already registered")]
+	Duplicate(&'static str),
+	#[error("dependencies of service `{0}` are not registered")]
+	Dependencies(&'static str),
+	#[error("failed to access service registry")]
+	Registry(
+		#[from]
+		#[source]
+		registry::Error,
+	),
+	#[error("failed to create service")]
+	Create(#[source] BoxError),
+	#[error("failed to start service")]
+	Start(#[source] BoxError),
+	#[error("failed to stop service")]
+	Stop(#[source] BoxError),
+}
+
+/// Handle to the services of a runtime (see [`super::Runtime::services`]). Can be cloned and
+/// used while the runtime is running.
+#[derive(Clone)]
+pub struct Services {
+	pub(crate) registry: Arc<RwLock<registry::Type>>,
+	/// Swap handles of all swappable services by service name.
+	pub(crate) handles: Arc<RwLock<registry::Type>>,
+	/// Lifecycle hooks of all started services in dependency order.
+	pub(crate) stop_hooks: Arc<Mutex<Vec<(&'static str, StopFn)>>>,
+	pub(crate) supervisor: Supervisor,
+}
+
+impl Services {
+	/// Returns `true` if a service or object named `name` is registered.
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if the registry lock is poisoned.
+	pub fn contains(&self, name: &str) -> Result<bool, Error> {
+		let registry = self
+			.registry
+			.read()
+			.map_err(|_| registry::Error::Poisoned)?;
+		Ok(registry.contains_key(name))
+	}
+
+	/// Returns a handle to service `S`. Will return `Ok(None)` if `S` is not registered.
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if the entry named after `S` is not a service of type `S`.
+	pub fn get<S>(&self) -> Result<Option<Service<S>>, Error>
+	where
+		S: Info + 'static,
+	{
+		let registry = self
+			.registry
+			.read()
+			.map_err(|_| registry::Error::Poisoned)?;
+		let service = registry.get_key(&registry::Key::<Service<S>>::service())?;
+		Ok(service.map(Service::duplicate))
+	}
+
+	/// Routes all new requests to `S` to `service`.
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if `S` was not registered as swappable service.
+	pub fn replace<S>(&self, service: Service<S>) -> Result<(), Error>
+	where
+		S: Info + 'static,
+		S::Request: 'static,
+		S::Response: 'static,
+	{
+		let name = S::name();
+		let handles = self.handles.read().map_err(|_| registry::Error::Poisoned)?;
+		let handle = handles
+			.get::<_, swap::Handle<S>>(name)?
+			.ok_or(Error::Missing(name))?;
+		handle.replace(service);
+		tracing::info!(message = "service replaced", name);
+		Ok(())
+	}
+
+	/// Creates and starts service `S` (see [`Create::on_start`]) and registers it. The service is
+	/// restarted if it fails (see [`super::supervisor`]) and stopped when the runtime shuts down
+	/// or `S` is removed.
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if `S` is already registered, any dependency of `S` is not registered or
+	/// `S` failed to be created or started.
+	pub async fn register<S>(&self) -> Result<(), Error>
+	where
+		S: Create + 'static,
+		S::Error: std::error::Error + Send + Sync + 'static,
+	{
+		let name = S::name();
+		if self.contains(name)? {
+			return Err(Error::Duplicate(name));
+		}
+		let service = S::with_registry(Arc::clone(&self.registry))
+			.map_err(|err| Error::Create(Box::new(err)))?
+			.ok_or(Error::Dependencies(name))?;
+		S::on_start(Arc::clone(&self.registry))
+			.await
+			.map_err(Error::Start)?;
+		let (service, handle) = swap::swappable(service);
+		{
+			let mut registry = self
+				.registry
+				.write()
+				.map_err(|_| registry::Error::Poisoned)?;
+			if registry.contains_key(name) {
+				return Err(Error::Duplicate(name));
+			}
+			registry.insert(name, Box::new(service));
+		}
+		self.handles
+			.write()
+			.map_err(|_| registry::Error::Poisoned)?
+			.insert(name, Box::new(handle));
+		let registry = Arc::clone(&self.registry);
+		self.stop_hooks
+			.lock()
+			.map_err(|_| registry::Error::Poisoned)?
+			.push((name, Box::new(move || S::on_stop(registry))));
+		tracing::info!(message = "service registered", name);
+		self.supervisor.supervise::<S>(None);
+		Ok(())
+	}
+
+	/// Removes service `S` and stops it (see [`Create::on_stop`]). Services which hold a handle to
+	/// `S` fail new requests with [`crate::service::NotReady`].
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if `S` was not registered as swappable service or failed to stop.
+	pub async fn remove<S>(&self) -> Result<(), Error>
+	where
+		S: Info + 'static,
+		S::Request: 'static,
+		S::Response: 'static,
+	{
+		let name = S::name();
+		{
+			let mut handles = self
+				.handles
+				.write()
+				.map_err(|_| registry::Error::Poisoned)?;
+			handles
+				.get::<_, swap::Handle<S>>(name)?
+				.ok_or(Error::Missing(name))?
+				.remove();
+			handles.remove(name);
+		}
+		self.supervisor.abort(name);
+		self.registry
+			.write()
+			.map_err(|_| registry::Error::Poisoned)?
+			.remove(name);
+		let stop = {
+			let mut hooks = self
+				.stop_hooks
+				.lock()
+				.map_err(|_| registry::Error::Poisoned)?;
+			hooks
+				.iter()
+				.position(|(hook, _)| *hook == name)
+				.map(|i| hooks.remove(i).1)
+		};
+		if let Some(stop) = stop {
+			stop().await.map_err(Error::Stop)?;
+		}
+		tracing::info!(message = "service removed", name);
+		Ok(())
+	}
+
+	/// Takes the lifecycle hooks of all services which are still registered.
+	pub(crate) fn take_stop_hooks(&self) -> Vec<(&'static str, StopFn)> {
+		self.stop_hooks
+			.lock()
+			.map(|mut hooks| std::mem::take(&mut *hooks))
+			.unwrap_or_default()
+	}
+}
@@ -0,0 +1,226 @@
+//! Restarts failed services.
+//!
+//! A service failed if its `poll_ready` returned an error (e.g. a pool failed to be created). The
+//! supervisor creates a new instance through [`Create::with_registry`], starts it (see
+//! [`Create::on_start`]) and replaces the failed one (see [`crate::service::swap`]). Restarts are
+//! delayed with an exponential backoff and given up once a service restarted more than
+//! [`Policy::max_restarts`] times within [`Policy::window`].
+//!
+//! Restarts are counted by `micro_tower_service_restarts_total{service}` (see
+//! [`crate::metrics`]).
+
+use super::admin::ErrorLog;
+use super::health::Indicator;
+use super::registry;
+use crate::service::{swap, Create, Status};
+use crate::shutdown::Controller;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// Reported if a service exceeded the restart rate (see [`Policy::max_restarts`]).
+#[derive(Debug, thiserror::Error)]
+#[error("service restarted {restarts} times within {window:?}, giving up")]
+pub struct GaveUp {
+	pub restarts: usize,
+	pub window: Duration,
+}
+
+/// Configures when and how often failed services are restarted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Policy {
+	/// Delay of the first restart. Doubled for each consecutive restart. Defaults to 100ms.
+	pub initial_backoff: Duration,
+	/// Maximum delay of a restart. Defaults to 30 seconds.
+	pub max_backoff: Duration,
+	/// Maximum number of restart attempts within `window`. Defaults to 5.
+	pub max_restarts: usize,
+	/// Defaults to 60 seconds. A service which did not fail within `window` after its last
+	/// restart is restarted with `initial_backoff` again.
+	pub window: Duration,
+}
+
+/// Supervises the services of a runtime.
+#[derive(Clone)]
+pub(crate) struct Supervisor {
+	pub policy: Option<Policy>,
+	pub registry: Arc<RwLock<registry::Type>>,
+	/// Swap handles of all services (see [`super::services::Services`]).
+	pub handles: Arc<RwLock<registry::Type>>,
+	pub controller: Controller,
+	pub errors: ErrorLog,
+	/// Supervision tasks by service name. Aborted if the service is removed.
+	pub tasks: Arc<Mutex<HashMap<&'static str, JoinHandle<()>>>>,
+}
+
+impl Default for Policy {
+	fn default() -> Self {
+		Self {
+			initial_backoff: Duration::from_millis(100),
+			max_backoff: Duration::from_secs(30),
+			max_restarts: 5,
+			window: Duration::from_secs(60),
+		}
+	}
+}
+
+impl Policy {
+	/// Returns the delay of the `attempt`-th consecutive restart (starting at 0).
+	#[must_use]
+	pub fn backoff(&self, attempt: u32) -> Duration {
+		self.initial_backoff
+			.checked_mul(2_u32.saturating_pow(attempt))
+			.map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff))
+	}
+}
+
+impl Supervisor {
+	/// Spawns a task which restarts service `S` whenever it fails. Does nothing if supervision is
+	/// disabled. The task ends once `S` is removed (see [`Supervisor::abort`]) or the runtime is
+	/// shut down.
+	pub fn supervise<S>(&self, indicator: Option<Arc<Indicator>>)
+	where
+		S: Create + 'static,
+		S::Error: std::error::Error + Send + Sync + 'static,
+	{
+		let Some(policy) = self.policy else {
+			return;
+		};
+		let supervisor = self.clone();
+		let task = tokio::spawn(async move {
+			let shutdown = supervisor.controller.clone();
+			tokio::select! {
+				() = supervisor.run::<S>(policy, indicator) => {},
+				() = shutdown.wait_for_shutdown() => {},
+			}
+		});
+		if let Ok(mut tasks) = self.tasks.lock() {
+			if let Some(previous) = tasks.insert(S::name(), task) {
+				previous.abort();
+			}
+		}
+	}
+
+	/// Stops supervising the service named `name`.
+	pub fn abort(&self, name: &str) {
+		let task = self
+			.tasks
+			.lock()
+			.ok()
+			.and_then(|mut tasks| tasks.remove(name));
+		if let Some(task) = task {
+			task.abort();
+		}
+	}
+
+	async fn run<S>(&self, policy: Policy, indicator: Option<Arc<Indicator>>)
+	where
+		S: Create + 'static,
+		S::Error: std::error::Error + Send + Sync + 'static,
+	{
+		let name = S::name();
+		let Some(mut failures) = self.handle::<S, _>(swap::Handle::failures) else {
+			return;
+		};
+		let set = |status| {
+			if let Some(indicator) = &indicator {
+				indicator.set(status);
+			}
+		};
+		let restarts_total = crate::metrics::global().counter(
+			"micro_tower_service_restarts_total",
+			"Number of restart attempts of a failed service.",
+			&[("service", name)],
+		);
+		let mut restarts: VecDeque<Instant> = VecDeque::new();
+		let mut attempt = 0;
+		while failures.changed().await.is_ok() {
+			let failed = *failures.borrow_and_update();
+			match self.handle::<S, _>(swap::Handle::generation) {
+				Some(generation) if generation == failed => {}
+				Some(_) => continue,
+				None => return,
+			}
+			set(Status::Degraded);
+			loop {
+				let now = Instant::now();
+				let recent = restarts
+					.back()
+					.filter(|last| now.duration_since(**last) <= policy.window);
+				if recent.is_none() {
+					attempt = 0;
+				}
+				restarts.retain(|restart| now.duration_since(*restart) <= policy.window);
+				if restarts.len() >= policy.max_restarts {
+					let err = GaveUp {
+						restarts: restarts.len(),
+						window: policy.window,
+					};
+					tracing::error!(
+						message = "failed to restart service",
+						name,
+						reason = format!("{}", std::error::Report::new(&err))
+					);
+					self.errors.push(name, &err);
+					set(Status::Unhealthy);
+					return;
+				}
+				let backoff = policy.backoff(attempt);
+				attempt = attempt.saturating_add(1);
+				tracing::warn!(
+					message = "service failed, restarting",
+					name,
+					attempt,
+					backoff = format!("{backoff:?}")
+				);
+				tokio::time::sleep(backoff).await;
+				restarts.push_back(Instant::now());
+				restarts_total.inc();
+				match self.restart::<S>().await {
+					Ok(true) => break,
+					Ok(false) => return,
+					Err(err) => {
+						tracing::error!(
+							message = "failed to restart service",
+							name,
+							reason = format!("{}", std::error::Report::new(&*err))
+						);
+						self.errors.push(name, &*err);
+					}
+				}
+			}
+			set(Status::Healthy);
+			tracing::info!(message = "service restarted", name);
+		}
+	}
+
+	/// Creates and starts a new instance of `S` and replaces the current one. Will return
+	/// `Ok(false)` if `S` was removed.
+	async fn restart<S>(&self) -> Result<bool, tower::BoxError>
+	where
+		S: Create + 'static,
+		S::Error: std::error::Error + Send + Sync + 'static,
+	{
+		let service = S::with_registry(Arc::clone(&self.registry))?
+			.ok_or(crate::service::NotReady(S::name()))?;
+		S::on_start(Arc::clone(&self.registry)).await?;
+		Ok(self
+			.handle::<S, _>(|handle| handle.replace(service))
+			.is_some())
+	}
+
+	/// Calls `f` with the swap handle of `S`. Will return `None` if `S` is not registered.
+	fn handle<S, T>(&self, f: impl FnOnce(&swap::Handle<S>) -> T) -> Option<T>
+	where
+		S: Create + 'static,
+	{
+		let handles = self.handles.read().ok()?;
+		let handle = handles
+			.get::<_, swap::Handle<S>>(S::name())
+			.ok()
+			.flatten()?;
+		Some(f(handle))
+	}
+}
@@ -1,6 +1,7 @@
 //! Reexport [`tower`] utilities.
 
 pub mod pool;
+pub mod swap;
 
 use crate::runtime::registry;
 use crate::util::BoxFuture;
@@ -13,6 +14,67 @@ pub use tower::*;
 
 pub struct NotReady(pub &'static str);
 
+/// Shared object managed by the runtime (see [`crate::runtime::builder::Builder::manage`]).
+/// Services generated with `#[service]` receive managed objects by declaring an argument of type
+/// `Managed<T>`. The object is looked up by argument name first and by type otherwise.
+pub struct Managed<T: ?Sized> {
+	inner: Arc<T>,
+}
+
+impl<T> Managed<T> {
+	#[must_use]
+	pub fn new(object: T) -> Self {
+		Self {
+			inner: Arc::new(object),
+		}
+	}
+}
+
+impl<T: ?Sized> Clone for Managed<T> {
+	fn clone(&self) -> Self {
+		Self {
+			inner: Arc::clone(&self.inner),
+		}
+	}
+}
+
+impl<T: ?Sized> std::ops::Deref for Managed<T> {
+	type Target = T;
+
+	fn deref(&self) -> &T {
+		&self.inner
+	}
+}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for Managed<T> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		self.inner.fmt(f)
+	}
+}
+
+/// Health of a service, pool or session. Ordered from best to worst.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+	/// Fully operational.
+	Healthy,
+	/// Operational but with reduced capacity (e.g. some pool members are unavailable).
+	Degraded,
+	/// Not operational.
+	Unhealthy,
+}
+
+/// Interface to report the health of a component (see [`crate::runtime::health::Monitor`]).
+pub trait Health {
+	/// Returns the current health of the component.
+	fn health(&self) -> Status;
+
+	/// Returns the number of members if the component is a pool.
+	fn members(&self) -> Option<usize> {
+		None
+	}
+}
+
 /// Interface to obtain information about services. Used at compile and runtime time.
 pub trait Info {
 	/// Request type of service.
@@ -23,6 +85,13 @@ pub trait Info {
 	/// Returns generic service name. Used to identify a service and should be unique other all
 	/// services.
 	fn name() -> &'static str;
+
+	/// Returns the registry keys of all inner services required to create this service. Used to
+	/// validate dependencies at runtime build.
+	#[must_use]
+	fn dependencies() -> Vec<&'static str> {
+		Vec::new()
+	}
 }
 
 /// Interface used to create service with request and response specified as in [`Info`].
@@ -37,6 +106,31 @@ pub trait Create: Sized + Info {
 	fn with_registry(
 		registry: Arc<RwLock<registry::Type>>,
 	) -> Result<Option<Service<Self>>, Self::Error>;
+
+	/// Called once by [`crate::runtime::builder::Builder::build`] after the service was created
+	/// but before it is registered. Services are started in dependency order, so all inner
+	/// services were already started. Also called for every instance created by a restart (see
+	/// [`crate::runtime::supervisor`]) before it replaces the failed one. Does nothing by default.
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if the service failed to initialise. The runtime will not be built or the
+	/// restart is retried.
+	fn on_start(registry: Arc<RwLock<registry::Type>>) -> BoxFuture<Result<(), BoxError>> {
+		let _ = registry;
+		Box::pin(async { Ok(()) })
+	}
+
+	/// Called once by [`crate::runtime::Runtime::run`] after all sessions were shut down. Services
+	/// are stopped in reverse dependency order. Does nothing by default.
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if the service failed to clean up. The error is logged only.
+	fn on_stop(registry: Arc<RwLock<registry::Type>>) -> BoxFuture<Result<(), BoxError>> {
+		let _ = registry;
+		Box::pin(async { Ok(()) })
+	}
 }
 
 #[doc(hidden)]
@@ -77,6 +171,16 @@ pub struct Service<S: Info> {
 	inner: BoxServiceClone<S>,
 }
 
+impl<S: Info> Service<S> {
+	/// Returns a new handle to the same service. Unlike [`Clone::clone`] no bounds on `S` are
+	/// required.
+	pub(crate) fn duplicate(&self) -> Self {
+		Self {
+			inner: self.inner.clone(),
+		}
+	}
+}
+
 impl<S: Info, T> From<Box<T>> for Service<S>
 where
 	T: tower::Service<
@@ -124,6 +228,10 @@ impl<S: Info> Info for Service<S> {
 	fn name() -> &'static str {
 		S::name()
 	}
+
+	fn dependencies() -> Vec<&'static str> {
+		S::dependencies()
+	}
 }
 
 impl<S: Info> tower::Service<S::Request> for Service<S> {
@@ -1,12 +1,32 @@
+use crate::metrics::{self, Counter, Gauge};
+use crate::runtime::config;
+use crate::runtime::health::{self, Indicator, Monitor};
+use crate::service::{Health, Status};
 use crate::util::BoxFuture;
-use futures::FutureExt;
+use futures::{FutureExt, Stream};
+use std::convert::Infallible;
+use std::future::Future;
 use std::marker::PhantomData;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::sync::Notify;
 use tokio::task::{JoinError, JoinHandle};
-use tower::balance::p2c::Balance;
-use tower::discover::ServiceList;
+use tokio::time::{Instant, MissedTickBehavior};
+use tower::discover::Change;
 use tower::{BoxError, Service, ServiceExt};
 
+mod balance;
+
+pub use balance::{KeyFn, Strategy};
+
+/// Name of the gauge of pending requests of each member of a named pool.
+const MEMBER_LOAD: &str = "micro_tower_pool_member_load";
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
 	#[error("failed to create service pool")]
@@ -21,8 +41,54 @@ pub enum Error {
 	Failed,
 }
 
+/// Invalid pool size (see [`Size`]).
+#[derive(Debug, thiserror::Error)]
+#[error("invalid pool size `{0}`, expected `<size>` or `<min>..<max>` with 0 < min <= max")]
+pub struct InvalidSize(pub String);
+
+/// Number of members of a pool. The pool has a fixed size if `min == max` and scales between
+/// `min` and `max` members by its load otherwise (see [`Autoscale`]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Size {
+	pub min: usize,
+	pub max: usize,
+}
+
+/// Configures how a pool scales between the bounds of its [`Size`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Autoscale {
+	/// Average number of pending requests per member above which a member is added. Defaults to
+	/// 1.
+	pub threshold: f64,
+	/// Duration the member created last must be idle before it is removed. Defaults to 10
+	/// seconds.
+	pub cooldown: Duration,
+	/// Interval in which the load of the pool is sampled. Defaults to 100ms.
+	pub interval: Duration,
+}
+
+/// Configures when a member of a pool is ejected and replaced by a new one. A member is ejected
+/// if its `poll_ready` failed, if `max_consecutive_failures` requests failed in a row or if at
+/// least `max_error_rate` of at least `min_requests` requests within `interval` failed.
+///
+/// Failed requests do not necessarily indicate a broken member (e.g. a service which rejects
+/// invalid requests), so pools only eject members whose `poll_ready` failed by default (see
+/// [`Ejection::unready`]).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ejection {
+	/// Defaults to 5.
+	pub max_consecutive_failures: u32,
+	/// Fraction of failed requests. Defaults to 0.5.
+	pub max_error_rate: f64,
+	/// Minimum number of requests within `interval` to check the error rate. Defaults to 20.
+	pub min_requests: u64,
+	/// Interval in which error rates are checked. Defaults to 1 second.
+	pub interval: Duration,
+}
+
 type CreateHandle<T, E> = JoinHandle<Result<T, E>>;
-type ServiceSet<S, Req> = Balance<ServiceList<Vec<S>>, Req>;
+type ServiceSet<S, Req> = balance::Balancer<S, Req>;
+type MemberChange<S> = Change<usize, Member<S>>;
 
 enum CreateFuture<MS, Target, Req>
 where
@@ -45,49 +111,539 @@ where
 	MS::Response: tower::Service<Req, Error = BoxError>,
 {
 	services: CreateFuture<MS, Target, Req>,
+	indicator: Arc<Indicator>,
 	_p: PhantomData<Target>,
 }
 
+/// Service of a pool. Reports its pending requests (see [`crate::metrics`]) and the outcome of
+/// its requests (see [`Ejection`]).
+pub struct Member<S> {
+	inner: S,
+	load: Gauge,
+	outcomes: Arc<Outcomes>,
+}
+
+/// Outcomes of the requests of a member.
+#[derive(Default)]
+struct Outcomes {
+	requests: AtomicU64,
+	errors: AtomicU64,
+	consecutive_failures: AtomicU32,
+	/// Set once `poll_ready` failed. The balancer drops or skips a member whose `poll_ready`
+	/// failed.
+	unready: AtomicBool,
+	/// Maximum number of consecutive failures before `ejected` is notified.
+	max_consecutive_failures: u32,
+	/// Notified if the member must be ejected immediately. Shared by all members of a pool.
+	ejected: Arc<Notify>,
+}
+
+/// Member as seen by the [`Resizer`].
+struct Slot {
+	load: Gauge,
+	outcomes: Arc<Outcomes>,
+	/// Number of requests and errors at the last check of the error rate.
+	checked: (u64, u64),
+}
+
+/// Stream of members added to or removed from a [`Pool`].
+pub struct Members<S> {
+	rx: UnboundedReceiver<MemberChange<S>>,
+}
+
+/// Creates and removes the members of a pool.
+struct Resizer<MS, Target, S> {
+	make_service: MS,
+	target: Target,
+	name: Option<&'static str>,
+	tx: UnboundedSender<MemberChange<S>>,
+	/// Members keyed by their index.
+	members: Vec<Slot>,
+	bounds: Size,
+	autoscale: Autoscale,
+	ejection: Option<Ejection>,
+	/// Notified by members which must be ejected immediately.
+	ejected: Arc<Notify>,
+	ejections: Counter,
+	/// Time since the member created last is idle.
+	idle_since: Option<Instant>,
+}
+
+/// Decrements the load of a member when dropped.
+struct Pending(Gauge);
+
+/// Response future of a [`Member`]. The request is pending until the future is dropped.
+pub struct MemberFuture<F> {
+	inner: Pin<Box<F>>,
+	outcomes: Arc<Outcomes>,
+	_pending: Pending,
+}
+
 pub struct Layer<Target, Req> {
-	size: usize,
+	size: Size,
+	autoscale: Autoscale,
+	ejection: Option<Ejection>,
+	strategy: Strategy<Req>,
 	target: Target,
+	name: Option<&'static str>,
+	monitor: Option<Monitor>,
+	/// Configuration followed to resize pools and the size used if none is configured.
+	config: Option<(config::Watch, Size)>,
 	_p: PhantomData<Req>,
 }
 
+impl Size {
+	/// Returns the size of a pool with `size` members.
+	#[must_use]
+	pub const fn fixed(size: usize) -> Self {
+		Self {
+			min: size,
+			max: size,
+		}
+	}
+
+	/// Returns `true` if `min == max`.
+	#[must_use]
+	pub const fn is_fixed(&self) -> bool {
+		self.min == self.max
+	}
+}
+
+impl From<usize> for Size {
+	fn from(size: usize) -> Self {
+		Self::fixed(size)
+	}
+}
+
+impl FromStr for Size {
+	type Err = InvalidSize;
+
+	/// Parses `<size>` or `<min>..<max>` (both bounds inclusive, `<min>..=<max>` is accepted as
+	/// well).
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let err = || InvalidSize(s.to_string());
+		let parse = |size: &str| size.trim().parse::<usize>().map_err(|_| err());
+		let size = match s.split_once("..") {
+			Some((min, max)) => Self {
+				min: parse(min)?,
+				max: parse(max.strip_prefix('=').unwrap_or(max))?,
+			},
+			None => Self::fixed(parse(s)?),
+		};
+		if size.min == 0 || size.min > size.max {
+			return Err(err());
+		}
+		Ok(size)
+	}
+}
+
+impl std::fmt::Display for Size {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		if self.is_fixed() {
+			write!(f, "{}", self.min)
+		} else {
+			write!(f, "{}..{}", self.min, self.max)
+		}
+	}
+}
+
+impl Default for Ejection {
+	fn default() -> Self {
+		Self {
+			max_consecutive_failures: 5,
+			max_error_rate: 0.5,
+			min_requests: 20,
+			interval: Duration::from_secs(1),
+		}
+	}
+}
+
+impl Ejection {
+	/// Ejects members only if their `poll_ready` failed. Failed requests are ignored.
+	#[must_use]
+	pub fn unready() -> Self {
+		Self {
+			max_consecutive_failures: u32::MAX,
+			max_error_rate: f64::INFINITY,
+			..Self::default()
+		}
+	}
+}
+
+impl Default for Autoscale {
+	fn default() -> Self {
+		Self {
+			threshold: 1.0,
+			cooldown: Duration::from_secs(10),
+			interval: Duration::from_millis(100),
+		}
+	}
+}
+
 impl<MS, Target, Req> Pool<MS, Target, Req>
 where
 	Target: Clone + Send + 'static,
 	Req: Send + 'static,
 	MS: Service<Target> + Send + 'static,
-	MS::Response: tower::Service<Req, Error = BoxError> + Send,
-	MS::Error: Send,
+	MS::Response: tower::Service<Req, Error = BoxError> + Send + 'static,
+	MS::Error: Into<BoxError> + Send,
 	MS::Future: Send,
 {
 	/// Create new pool with `count` many services and `make_service` to create the inner services.
-	pub fn with_size(size: usize, mut make_service: MS, target: Target) -> Self {
-		tracing::debug!(message = "creating service pool", size);
+	/// The pool reports [`Status::Degraded`] until all services are created and
+	/// [`Status::Unhealthy`] if creation failed. Services whose `poll_ready` failed are replaced
+	/// (see [`Ejection::unready`]).
+	pub fn with_size(size: usize, make_service: MS, target: Target) -> Self {
+		Self::create(&Layer::with_size(size, target), make_service)
+	}
+
+	/// Same as [`Pool::with_size`] but reports the load of each member labelled with service
+	/// `name` (see [`crate::metrics`]).
+	pub fn named(size: usize, name: &'static str, make_service: MS, target: Target) -> Self {
+		Self::create(&Layer::with_size(size, target).named(name), make_service)
+	}
+
+	/// Same as [`Pool::with_size`] but starts with `size.min` services and adds or removes
+	/// services within the bounds of `size` by the load of the pool (see [`Autoscale`]).
+	pub fn autoscaled(size: Size, autoscale: Autoscale, make_service: MS, target: Target) -> Self {
+		Self::create(
+			&Layer::with_size(size, target).autoscale(autoscale),
+			make_service,
+		)
+	}
+
+	/// Creates a pool configured by `layer`.
+	fn create(layer: &Layer<Target, Req>, make_service: MS) -> Self {
+		let Layer {
+			size,
+			autoscale,
+			ejection,
+			name,
+			..
+		} = *layer;
+		let strategy = layer.strategy.clone();
+		let config = layer.config.clone();
+		let target = layer.target.clone();
+		tracing::debug!(message = "creating service pool", size = format!("{size}"));
+		let indicator = Arc::new(Indicator::pool(Status::Degraded, size.min));
+		let pool_indicator = Arc::clone(&indicator);
+		let (tx, rx) = mpsc::unbounded_channel();
 		let handle = tokio::spawn(async move {
-			let mut services = Vec::with_capacity(size);
-			for _ in 0..size {
-				let target = target.clone();
-				let service = make_service.ready().await?.call(target).await?;
-				services.push(service);
+			let mut resizer = Resizer {
+				make_service,
+				target,
+				name,
+				tx,
+				members: Vec::new(),
+				bounds: size,
+				autoscale,
+				ejection,
+				ejected: Arc::new(Notify::new()),
+				ejections: name.map_or_else(Counter::default, |name| {
+					metrics::global().counter(
+						"micro_tower_pool_member_ejections_total",
+						"Number of service pool members ejected due to failures.",
+						&[("service", name)],
+					)
+				}),
+				idle_since: None,
+			};
+			if let Err(err) = resizer.resize(size.min).await {
+				indicator.set(Status::Unhealthy);
+				return Err(err);
+			}
+			tracing::debug!(message = "service pool created", size = size.min);
+			indicator.set(Status::Healthy);
+			let config = config.and_then(|(config, default)| Some((config, name?, default)));
+			if config.is_some() || !size.is_fixed() || ejection.is_some() {
+				tokio::spawn(resizer.run(config, indicator));
 			}
-			tracing::debug!(message = "service pool created", size);
-			Ok(Balance::new(ServiceList::new(services)))
+			Ok(balance::Balancer::new(Members { rx }, strategy))
 		});
 
 		Self {
 			services: CreateFuture::Pending { handle },
+			indicator: pool_indicator,
 			_p: PhantomData,
 		}
 	}
 }
 
+impl<MS, Target, S> Resizer<MS, Target, S>
+where
+	Target: Clone,
+	MS: Service<Target, Response = S>,
+	MS::Error: Into<BoxError>,
+{
+	/// Creates or removes members until the pool has `size` members. The members created last
+	/// are removed first.
+	async fn resize(&mut self, size: usize) -> Result<(), MS::Error> {
+		while self.members.len() < size {
+			let key = self.members.len();
+			let (member, slot) = self.create(key).await?;
+			self.members.push(slot);
+			let _ = self.tx.send(Change::Insert(key, member));
+		}
+		while self.members.len() > size {
+			self.members.pop();
+			let key = self.members.len();
+			if let Some(name) = self.name {
+				metrics::global().remove(
+					MEMBER_LOAD,
+					&[("service", name), ("member", &key.to_string())],
+				);
+			}
+			let _ = self.tx.send(Change::Remove(key));
+		}
+		self.idle_since = None;
+		Ok(())
+	}
+
+	/// Creates the member with `key`.
+	async fn create(&mut self, key: usize) -> Result<(Member<S>, Slot), MS::Error> {
+		let target = self.target.clone();
+		let service = self.make_service.ready().await?.call(target).await?;
+		let load = self.name.map_or_else(Gauge::default, |name| {
+			metrics::global().gauge(
+				MEMBER_LOAD,
+				"Number of pending requests of a service pool member.",
+				&[("service", name), ("member", &key.to_string())],
+			)
+		});
+		let outcomes = Arc::new(Outcomes {
+			max_consecutive_failures: self
+				.ejection
+				.map_or(u32::MAX, |ejection| ejection.max_consecutive_failures),
+			ejected: Arc::clone(&self.ejected),
+			..Outcomes::default()
+		});
+		let member = Member {
+			inner: service,
+			load: load.clone(),
+			outcomes: Arc::clone(&outcomes),
+		};
+		let slot = Slot {
+			load,
+			outcomes,
+			checked: (0, 0),
+		};
+		Ok((member, slot))
+	}
+
+	/// Replaces all members which must be ejected (see [`Ejection`]). If `check_rate` is set the
+	/// error rates since the last check are checked as well.
+	async fn eject(&mut self, check_rate: bool, indicator: &Indicator) {
+		let Some(ejection) = self.ejection else {
+			return;
+		};
+		let name = self.name.unwrap_or_default();
+		let mut ejected = false;
+		for key in 0..self.members.len() {
+			let Some(reason) = self.members[key].ejection(&ejection, check_rate) else {
+				continue;
+			};
+			tracing::warn!(
+				message = "ejecting service pool member",
+				name,
+				member = key,
+				reason
+			);
+			match self.create(key).await {
+				Ok((member, slot)) => {
+					self.members[key] = slot;
+					// replaces the ejected member if the balancer did not drop it already
+					let _ = self.tx.send(Change::Insert(key, member));
+					self.ejections.inc();
+					ejected = true;
+				}
+				Err(err) => {
+					let err = err.into();
+					tracing::error!(
+						message = "failed to replace service pool member",
+						name,
+						member = key,
+						reason = format!("{}", std::error::Report::new(&*err))
+					);
+					indicator.set(Status::Degraded);
+					return;
+				}
+			}
+		}
+		if ejected {
+			indicator.set(Status::Healthy);
+		}
+	}
+
+	/// Resizes the pool whenever the pool size of service `name` changes in `config` (`default`
+	/// is used if no size is configured) and scales the pool by its load. Stops once the pool was
+	/// dropped.
+	async fn run(
+		mut self,
+		mut config: Option<(config::Watch, &'static str, Size)>,
+		indicator: Arc<Indicator>,
+	) {
+		let mut interval = tokio::time::interval(self.autoscale.interval);
+		interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		let ejected = Arc::clone(&self.ejected);
+		let checks = self
+			.ejection
+			.map_or(self.autoscale.interval, |e| e.interval);
+		let mut checks = tokio::time::interval(checks);
+		checks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		loop {
+			let size = tokio::select! {
+				bounds = changed(&mut config) => match bounds {
+					Some(bounds) => {
+						self.bounds = bounds;
+						self.members.len().clamp(bounds.min, bounds.max)
+					}
+					None => return,
+				},
+				_ = interval.tick(), if !self.bounds.is_fixed() => self.scale(),
+				_ = checks.tick(), if self.ejection.is_some() => {
+					self.eject(true, &indicator).await;
+					continue;
+				}
+				() = ejected.notified() => {
+					self.eject(false, &indicator).await;
+					continue;
+				}
+				() = self.tx.closed() => return,
+			};
+			if size == self.members.len() {
+				continue;
+			}
+			let name = self.name.unwrap_or_default();
+			tracing::info!(
+				message = "resizing service pool",
+				name,
+				from = self.members.len(),
+				to = size
+			);
+			match self.resize(size).await {
+				Ok(()) => indicator.set(Status::Healthy),
+				Err(err) => {
+					let err = err.into();
+					tracing::error!(
+						message = "failed to resize service pool",
+						name,
+						reason = format!("{}", std::error::Report::new(&*err))
+					);
+					indicator.set(Status::Degraded);
+				}
+			}
+			indicator.set_members(self.members.len());
+		}
+	}
+
+	/// Returns the size the pool should be scaled to. Adds a member if the average load exceeds
+	/// the threshold and removes the member created last once it was idle for the cooldown.
+	fn scale(&mut self) -> usize {
+		let size = self.members.len();
+		let total: i64 = self.members.iter().map(|member| member.load.get()).sum();
+		#[allow(clippy::cast_precision_loss)]
+		let average = total as f64 / size.max(1) as f64;
+		if average > self.autoscale.threshold && size < self.bounds.max {
+			return size + 1;
+		}
+		let idle = self
+			.members
+			.last()
+			.is_some_and(|member| member.load.get() == 0);
+		if !idle || size <= self.bounds.min {
+			self.idle_since = None;
+			return size;
+		}
+		let idle_since = *self.idle_since.get_or_insert_with(Instant::now);
+		if idle_since.elapsed() >= self.autoscale.cooldown {
+			return size - 1;
+		}
+		size
+	}
+}
+
+/// Waits until the pool size of the configured service changes. Will return `None` if the
+/// configuration was dropped and never completes without configuration.
+async fn changed(config: &mut Option<(config::Watch, &'static str, Size)>) -> Option<Size> {
+	let Some((config, name, default)) = config else {
+		return std::future::pending().await;
+	};
+	if !config.changed().await {
+		return None;
+	}
+	Some(config.service(name).pool.unwrap_or(*default))
+}
+
+impl Slot {
+	/// Returns the reason to eject the member (if any).
+	fn ejection(&mut self, ejection: &Ejection, check_rate: bool) -> Option<&'static str> {
+		let outcomes = &self.outcomes;
+		if outcomes.unready.load(Ordering::SeqCst) {
+			return Some("not ready");
+		}
+		if outcomes.consecutive_failures.load(Ordering::SeqCst) >= ejection.max_consecutive_failures
+		{
+			return Some("consecutive failures");
+		}
+		if !check_rate {
+			return None;
+		}
+		let checked = (
+			outcomes.requests.load(Ordering::SeqCst),
+			outcomes.errors.load(Ordering::SeqCst),
+		);
+		let requests = checked.0 - self.checked.0;
+		let errors = checked.1 - self.checked.1;
+		self.checked = checked;
+		#[allow(clippy::cast_precision_loss)]
+		let exceeded = requests >= ejection.min_requests.max(1)
+			&& errors as f64 / requests as f64 >= ejection.max_error_rate;
+		exceeded.then_some("error rate")
+	}
+}
+
+impl Outcomes {
+	fn record(&self, success: bool) {
+		self.requests.fetch_add(1, Ordering::SeqCst);
+		if success {
+			self.consecutive_failures.store(0, Ordering::SeqCst);
+			return;
+		}
+		self.errors.fetch_add(1, Ordering::SeqCst);
+		let failures = self.consecutive_failures.fetch_add(1, Ordering::SeqCst) + 1;
+		if failures == self.max_consecutive_failures {
+			self.ejected.notify_one();
+		}
+	}
+}
+
+impl<S> Stream for Members<S> {
+	type Item = Result<MemberChange<S>, Infallible>;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		self.rx.poll_recv(cx).map(|change| change.map(Ok))
+	}
+}
+
+impl<MS, Target, Req> Health for Pool<MS, Target, Req>
+where
+	MS: Service<Target>,
+	MS::Response: tower::Service<Req, Error = BoxError>,
+{
+	fn health(&self) -> Status {
+		self.indicator.health()
+	}
+
+	fn members(&self) -> Option<usize> {
+		self.indicator.members()
+	}
+}
+
 impl<MS, Target, Req> tower::Service<Req> for Pool<MS, Target, Req>
 where
+	Req: Send + 'static,
 	MS: Service<Target>,
-	MS::Response: tower::Service<Req, Error = BoxError> + tower::load::Load,
+	MS::Response: tower::Service<Req, Error = BoxError> + tower::load::Load + Send + 'static,
 	MS::Error: Into<BoxError>,
 	<MS::Response as tower::Service<Req>>::Future: Send + 'static,
 	<MS::Response as tower::load::Load>::Metric: std::fmt::Debug,
@@ -105,10 +661,12 @@ where
 				}
 				Poll::Ready(Ok(Err(err))) => {
 					self.services = CreateFuture::Failed;
+					self.indicator.set(Status::Unhealthy);
 					Poll::Ready(Err(err.into()))
 				}
 				Poll::Ready(Err(err)) => {
 					self.services = CreateFuture::Failed;
+					self.indicator.set(Status::Unhealthy);
 					Poll::Ready(Err(err.into()))
 				}
 				Poll::Pending => Poll::Pending,
@@ -120,36 +678,149 @@ where
 
 	fn call(&mut self, req: Req) -> Self::Future {
 		match self.services {
-			CreateFuture::Ready { ref mut services } => Box::pin(services.call(req)),
+			CreateFuture::Ready { ref mut services } => services.call(req),
 			_ => unimplemented!("called before ready"),
 		}
 	}
 }
 
 impl<Target, Req> Layer<Target, Req> {
+	/// Creates pools of `size` services. Pools scale by their load if `size` is a range (see
+	/// [`Layer::autoscale`]).
 	#[must_use]
-	pub fn with_size(size: usize, target: Target) -> Self {
+	pub fn with_size(size: impl Into<Size>, target: Target) -> Self {
 		Self {
-			size,
+			size: size.into(),
+			autoscale: Autoscale::default(),
+			ejection: Some(Ejection::unready()),
+			strategy: Strategy::default(),
 			target,
+			name: None,
+			monitor: None,
+			config: None,
 			_p: PhantomData,
 		}
 	}
+
+	/// Labels metrics of every pool created by this layer with service `name`.
+	#[must_use]
+	pub fn named(mut self, name: &'static str) -> Self {
+		self.name = Some(name);
+		self
+	}
+
+	/// Registers the health of every pool created by this layer at `monitor`. Requires a name
+	/// (see [`Layer::named`]).
+	#[must_use]
+	pub fn monitored(mut self, monitor: Monitor) -> Self {
+		self.monitor = Some(monitor);
+		self
+	}
+
+	/// Scales every pool created by this layer with `autoscale` (see [`Size`]).
+	#[must_use]
+	pub fn autoscale(mut self, autoscale: Autoscale) -> Self {
+		self.autoscale = autoscale;
+		self
+	}
+
+	/// Balances requests between the members of every pool created by this layer by `strategy`.
+	#[must_use]
+	pub fn strategy(mut self, strategy: Strategy<Req>) -> Self {
+		self.strategy = strategy;
+		self
+	}
+
+	/// Replaces failing members of every pool created by this layer as configured by `ejection`.
+	/// Members are never ejected if `ejection` is `None`. Defaults to [`Ejection::unready`], use
+	/// [`Ejection::default`] to eject members by their failed requests as well.
+	#[must_use]
+	pub fn ejection(mut self, ejection: Option<Ejection>) -> Self {
+		self.ejection = ejection;
+		self
+	}
+
+	/// Resizes every pool created by this layer whenever its size changes in `config`. Uses
+	/// `default` if no size is configured. Requires a name (see [`Layer::named`]).
+	#[must_use]
+	pub fn configured(mut self, config: config::Watch, default: Size) -> Self {
+		self.config = Some((config, default));
+		self
+	}
 }
 
 impl<MS, Target, Req> tower::Layer<MS> for Layer<Target, Req>
 where
 	Req: Send + 'static,
 	MS: Service<Target> + Send + 'static,
-	MS::Response: tower::Service<Req, Error = BoxError> + Send,
-	MS::Error: Send,
+	MS::Response: tower::Service<Req, Error = BoxError> + Send + 'static,
+	MS::Error: Into<BoxError> + Send,
 	MS::Future: Send,
 	Target: Clone + Send + 'static,
 {
 	type Service = Pool<MS, Target, Req>;
 
 	fn layer(&self, inner: MS) -> Self::Service {
-		let target = self.target.clone();
-		Pool::with_size(self.size, inner, target)
+		let pool = Pool::create(self, inner);
+		if let (Some(name), Some(monitor)) = (self.name, &self.monitor) {
+			monitor.register(name, health::Kind::Service, &pool.indicator);
+		}
+		pool
+	}
+}
+
+impl<S, Req> Service<Req> for Member<S>
+where
+	S: Service<Req>,
+{
+	type Response = S::Response;
+	type Error = S::Error;
+	type Future = MemberFuture<S::Future>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		let result = self.inner.poll_ready(cx);
+		if let Poll::Ready(Err(_)) = &result {
+			self.outcomes.unready.store(true, Ordering::SeqCst);
+			self.outcomes.ejected.notify_one();
+		}
+		result
+	}
+
+	fn call(&mut self, req: Req) -> Self::Future {
+		self.load.inc();
+		MemberFuture {
+			inner: Box::pin(self.inner.call(req)),
+			outcomes: Arc::clone(&self.outcomes),
+			_pending: Pending(self.load.clone()),
+		}
+	}
+}
+
+impl<F, T, E> Future for MemberFuture<F>
+where
+	F: Future<Output = Result<T, E>>,
+{
+	type Output = F::Output;
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		let result = self.inner.as_mut().poll(cx);
+		if let Poll::Ready(result) = &result {
+			self.outcomes.record(result.is_ok());
+		}
+		result
+	}
+}
+
+impl<S: tower::load::Load> tower::load::Load for Member<S> {
+	type Metric = S::Metric;
+
+	fn load(&self) -> Self::Metric {
+		self.inner.load()
+	}
+}
+
+impl Drop for Pending {
+	fn drop(&mut self) {
+		self.0.dec();
 	}
 }
@@ -0,0 +1,363 @@
+//! Strategies to balance requests between the members of a pool.
+
+use super::{Member, Members, Outcomes};
+use crate::util::BoxFuture;
+use futures::Stream;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{Hash, Hasher};
+use std::pin::Pin;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::Mutex;
+use tower::balance::p2c::Balance;
+use tower::discover::Change;
+use tower::load::Load;
+use tower::ready_cache::error::Failed;
+use tower::ready_cache::ReadyCache;
+use tower::{BoxError, Service, ServiceExt};
+
+/// Extracts the hash of the key of a request (see [`Strategy::ConsistentHash`]).
+pub type KeyFn<Req> = Arc<dyn Fn(&Req) -> u64 + Send + Sync>;
+
+/// Member shared with the futures of its requests.
+type Shared<S> = Arc<Mutex<Member<S>>>;
+
+/// Selects the member of a pool which receives a request.
+#[derive(Default)]
+pub enum Strategy<Req> {
+	/// Picks the less loaded of two random members (see [`tower::balance::p2c`]). Default.
+	#[default]
+	PowerOfTwoChoices,
+	/// Picks the ready members in turn.
+	RoundRobin,
+	/// Picks the ready member with the fewest pending requests.
+	LeastPending,
+	/// Picks the member by the hash of a key of the request. Requests with the same key are
+	/// routed to the same member as long as the pool is not resized. The member is only known
+	/// once the request is received, so each request waits for the readiness of its member
+	/// after it was accepted by the pool.
+	ConsistentHash(KeyFn<Req>),
+}
+
+/// Balances requests between the members of a pool by a [`Strategy`].
+pub enum Balancer<S, Req>
+where
+	S: Service<Req>,
+{
+	PowerOfTwoChoices(Balance<Members<S>, Req>),
+	Select(Select<S, Req>),
+	ConsistentHash(Hashed<S, Req>),
+}
+
+/// Balances requests by [`Strategy::RoundRobin`] or [`Strategy::LeastPending`].
+pub struct Select<S, Req>
+where
+	S: Service<Req>,
+{
+	members: Members<S>,
+	services: ReadyCache<usize, Member<S>, Req>,
+	/// Keys of all members which were not dropped.
+	keys: BTreeSet<usize>,
+	strategy: Strategy<Req>,
+	/// Member selected by `poll_ready`.
+	selected: Option<usize>,
+	/// Member selected last by [`Strategy::RoundRobin`].
+	last: Option<usize>,
+}
+
+/// Balances requests by [`Strategy::ConsistentHash`]. Members are shared with the futures of
+/// their requests, so requests only wait for their own member to become ready.
+pub struct Hashed<S, Req> {
+	members: Members<S>,
+	services: BTreeMap<usize, (Arc<Outcomes>, Shared<S>)>,
+	key: KeyFn<Req>,
+}
+
+impl<Req: 'static> Strategy<Req> {
+	/// Returns [`Strategy::ConsistentHash`] which extracts the key of a request with `key`.
+	#[must_use]
+	pub fn hashed<K, F>(key: F) -> Self
+	where
+		K: Hash,
+		F: Fn(&Req) -> K + Send + Sync + 'static,
+	{
+		Self::ConsistentHash(Arc::new(move |req| hash(&key(req))))
+	}
+
+	/// Returns [`Strategy::ConsistentHash`] which uses the whole request as key.
+	#[must_use]
+	pub fn hashed_request() -> Self
+	where
+		Req: Hash,
+	{
+		Self::ConsistentHash(Arc::new(hash))
+	}
+}
+
+impl<Req> Clone for Strategy<Req> {
+	fn clone(&self) -> Self {
+		match self {
+			Self::PowerOfTwoChoices => Self::PowerOfTwoChoices,
+			Self::RoundRobin => Self::RoundRobin,
+			Self::LeastPending => Self::LeastPending,
+			Self::ConsistentHash(key) => Self::ConsistentHash(Arc::clone(key)),
+		}
+	}
+}
+
+impl<Req> std::fmt::Debug for Strategy<Req> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::PowerOfTwoChoices => f.write_str("PowerOfTwoChoices"),
+			Self::RoundRobin => f.write_str("RoundRobin"),
+			Self::LeastPending => f.write_str("LeastPending"),
+			Self::ConsistentHash(_) => f.write_str("ConsistentHash"),
+		}
+	}
+}
+
+impl<S, Req> Balancer<S, Req>
+where
+	S: Service<Req>,
+	S::Error: Into<BoxError>,
+{
+	pub(super) fn new(members: Members<S>, strategy: Strategy<Req>) -> Self {
+		match strategy {
+			Strategy::PowerOfTwoChoices => Self::PowerOfTwoChoices(Balance::new(members)),
+			Strategy::ConsistentHash(key) => Self::ConsistentHash(Hashed {
+				members,
+				services: BTreeMap::new(),
+				key,
+			}),
+			strategy => Self::Select(Select {
+				members,
+				services: ReadyCache::default(),
+				keys: BTreeSet::new(),
+				strategy,
+				selected: None,
+				last: None,
+			}),
+		}
+	}
+}
+
+impl<S, Req> Service<Req> for Balancer<S, Req>
+where
+	Req: Send + 'static,
+	S: Service<Req> + Load + Send + 'static,
+	S::Error: Into<BoxError> + 'static,
+	S::Future: Send + 'static,
+	<S as Load>::Metric: std::fmt::Debug,
+{
+	type Response = S::Response;
+	type Error = BoxError;
+	type Future = BoxFuture<Result<S::Response, BoxError>>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		match self {
+			Self::PowerOfTwoChoices(balance) => balance.poll_ready(cx),
+			Self::Select(select) => select.poll_ready(cx),
+			Self::ConsistentHash(hashed) => hashed.poll_ready(cx),
+		}
+	}
+
+	fn call(&mut self, req: Req) -> Self::Future {
+		match self {
+			Self::PowerOfTwoChoices(balance) => Box::pin(balance.call(req)),
+			Self::Select(select) => select.call(req),
+			Self::ConsistentHash(hashed) => hashed.call(req),
+		}
+	}
+}
+
+impl<S, Req> Select<S, Req>
+where
+	S: Service<Req> + Load,
+	S::Error: Into<BoxError>,
+	S::Future: Send + 'static,
+{
+	/// Adds and removes members announced by the pool.
+	fn update(&mut self, cx: &mut Context<'_>) {
+		while let Poll::Ready(Some(Ok(change))) = Pin::new(&mut self.members).poll_next(cx) {
+			match change {
+				Change::Insert(key, member) => {
+					self.keys.insert(key);
+					self.services.push(key, member);
+				}
+				Change::Remove(key) => {
+					self.keys.remove(&key);
+					self.services.evict(&key);
+				}
+			}
+		}
+	}
+
+	/// Promotes members which became ready. Failed members are dropped.
+	fn promote(&mut self, cx: &mut Context<'_>) {
+		while let Poll::Ready(Err(err)) = self.services.poll_pending(cx) {
+			self.dropped(err);
+		}
+	}
+
+	fn dropped(&mut self, Failed(key, err): Failed<usize>) {
+		tracing::debug!(
+			message = "dropping failed pool member",
+			member = key,
+			reason = format!("{err}")
+		);
+		self.keys.remove(&key);
+	}
+
+	/// Will return `true` if the member with `key` is ready.
+	fn check(&mut self, cx: &mut Context<'_>, key: usize) -> bool {
+		match self.services.check_ready(cx, &key) {
+			Ok(ready) => ready,
+			Err(err) => {
+				self.dropped(err);
+				false
+			}
+		}
+	}
+
+	/// Selects the next ready member after the member selected last.
+	fn round_robin(&mut self, cx: &mut Context<'_>) -> Option<usize> {
+		let start = self.last.map_or(0, |last| last + 1);
+		let keys: Vec<usize> = self
+			.keys
+			.range(start..)
+			.chain(self.keys.range(..start))
+			.copied()
+			.collect();
+		let key = keys.into_iter().find(|key| self.check(cx, *key))?;
+		self.last = Some(key);
+		Some(key)
+	}
+
+	/// Selects the ready member with the lowest load.
+	fn least_pending(&mut self, cx: &mut Context<'_>) -> Option<usize> {
+		while self.services.ready_len() > 0 {
+			let (key, _) = (0..self.services.ready_len())
+				.filter_map(|index| self.services.get_ready_index(index))
+				.min_by(|(_, a), (_, b)| {
+					a.load()
+						.partial_cmp(&b.load())
+						.unwrap_or(std::cmp::Ordering::Equal)
+				})?;
+			let key = *key;
+			if self.check(cx, key) {
+				return Some(key);
+			}
+		}
+		None
+	}
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+		self.update(cx);
+		loop {
+			self.promote(cx);
+			if self.services.ready_len() == 0 {
+				return Poll::Pending;
+			}
+			self.selected = match self.strategy {
+				Strategy::RoundRobin => self.round_robin(cx),
+				Strategy::LeastPending => self.least_pending(cx),
+				Strategy::PowerOfTwoChoices | Strategy::ConsistentHash(_) => {
+					unreachable!("balanced by `Balance` or `Hashed`")
+				}
+			};
+			if self.selected.is_some() {
+				return Poll::Ready(Ok(()));
+			}
+		}
+	}
+
+	fn call(&mut self, req: Req) -> BoxFuture<Result<S::Response, BoxError>> {
+		let key = self.selected.take().expect("called before ready");
+		let future = self.services.call_ready(&key, req);
+		Box::pin(async move { future.await.map_err(Into::into) })
+	}
+}
+
+impl<S, Req> Hashed<S, Req>
+where
+	Req: Send + 'static,
+	S: Service<Req> + Send + 'static,
+	S::Error: Into<BoxError>,
+	S::Future: Send + 'static,
+{
+	/// Adds and removes members announced by the pool. Replaced members are swapped in place.
+	fn update(&mut self, cx: &mut Context<'_>) {
+		while let Poll::Ready(Some(Ok(change))) = Pin::new(&mut self.members).poll_next(cx) {
+			match change {
+				Change::Insert(key, member) => {
+					let outcomes = Arc::clone(&member.outcomes);
+					self.services
+						.insert(key, (outcomes, Arc::new(Mutex::new(member))));
+				}
+				Change::Remove(key) => {
+					self.services.remove(&key);
+				}
+			}
+		}
+	}
+
+	/// Ready as soon as the pool has a member. The readiness of the member which receives a
+	/// request is awaited by the future of the request.
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+		self.update(cx);
+		if self.services.is_empty() {
+			Poll::Pending
+		} else {
+			Poll::Ready(Ok(()))
+		}
+	}
+
+	fn call(&mut self, req: Req) -> BoxFuture<Result<S::Response, BoxError>> {
+		let buckets = self.services.keys().last().map_or(0, |last| last + 1);
+		let bucket = jump((self.key)(&req), buckets);
+		// members which failed to become ready are skipped until they are replaced
+		let mut members = self
+			.services
+			.range(bucket..)
+			.chain(self.services.range(..bucket));
+		let member = members
+			.clone()
+			.find(|(_, (outcomes, _))| !outcomes.unready.load(Ordering::SeqCst))
+			.or_else(|| members.next())
+			.map(|(_, (_, member))| Arc::clone(member))
+			.expect("called before ready");
+		Box::pin(async move {
+			let mut member = member.lock_owned().await;
+			let future = member.ready().await.map_err(Into::into)?.call(req);
+			drop(member);
+			future.await.map_err(Into::into)
+		})
+	}
+}
+
+fn hash<K: Hash + ?Sized>(key: &K) -> u64 {
+	let mut hasher = DefaultHasher::new();
+	key.hash(&mut hasher);
+	hasher.finish()
+}
+
+/// Maps `key` to one of `buckets` buckets. Only keys of the last bucket are moved if the last
+/// bucket is removed (see "A Fast, Minimal Memory, Consistent Hash Algorithm" by Lamping and
+/// Veach).
+#[allow(
+	clippy::cast_possible_truncation,
+	clippy::cast_precision_loss,
+	clippy::cast_sign_loss
+)]
+fn jump(mut key: u64, buckets: usize) -> usize {
+	let mut bucket = 0;
+	let mut next = 0;
+	while next < buckets as u64 {
+		bucket = next;
+		key = key.wrapping_mul(2_862_933_555_777_941_757).wrapping_add(1);
+		next = ((bucket + 1) as f64 * ((1_u64 << 31) as f64 / ((key >> 33) + 1) as f64)) as u64;
+	}
+	bucket as usize
+}
@@ -0,0 +1,158 @@
+//! Services whose implementation can be replaced while they are in use (see
+//! [`crate::runtime::services::Services`]).
+
+use super::{Info, NotReady, Service};
+use crate::util::BoxFuture;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::watch;
+use tower::BoxError;
+
+/// Current implementation of a swappable service.
+struct Current<S: Info> {
+	/// Incremented on each replacement. Starts at 1.
+	generation: u64,
+	service: Service<S>,
+}
+
+/// Routes requests to the current implementation of service `S`. Clones switch to a new
+/// implementation on their next call to `poll_ready`. Calls already in flight are completed by
+/// the implementation which accepted them. Failures of `poll_ready` are reported to the handle
+/// (see [`Handle::failures`]).
+pub struct Swap<S: Info> {
+	rx: watch::Receiver<Current<S>>,
+	generation: u64,
+	current: Service<S>,
+	failed: Arc<watch::Sender<u64>>,
+}
+
+/// Replaces the implementation of all [`Swap`]s created by [`swappable`].
+pub struct Handle<S: Info> {
+	tx: watch::Sender<Current<S>>,
+	failed: Arc<watch::Sender<u64>>,
+}
+
+/// Implementation of a removed service. Fails every request with [`NotReady`].
+struct Removed<S> {
+	_p: PhantomData<fn() -> S>,
+}
+
+/// Wraps `service` so its implementation can be replaced by the returned handle.
+#[must_use]
+pub fn swappable<S>(service: Service<S>) -> (Service<S>, Handle<S>)
+where
+	S: Info + 'static,
+	S::Request: 'static,
+	S::Response: 'static,
+{
+	let current = service.duplicate();
+	let (tx, rx) = watch::channel(Current {
+		generation: 1,
+		service,
+	});
+	let failed = Arc::new(watch::channel(0).0);
+	let swap = Swap {
+		rx,
+		generation: 1,
+		current,
+		failed: Arc::clone(&failed),
+	};
+	(Service::from(Box::new(swap)), Handle { tx, failed })
+}
+
+impl<S> Handle<S>
+where
+	S: Info + 'static,
+	S::Request: 'static,
+	S::Response: 'static,
+{
+	/// Routes all new requests to `service`.
+	pub fn replace(&self, service: Service<S>) {
+		self.tx.send_modify(|current| {
+			current.generation += 1;
+			current.service = service;
+		});
+	}
+
+	/// Fails all new requests with [`NotReady`].
+	pub fn remove(&self) {
+		self.replace(Service::from(Box::new(Removed::<S> { _p: PhantomData })));
+	}
+
+	/// Returns the generation of the current implementation. Starts at 1 and is incremented on
+	/// each replacement.
+	#[must_use]
+	pub fn generation(&self) -> u64 {
+		self.tx.borrow().generation
+	}
+
+	/// Returns a receiver of the latest generation whose `poll_ready` failed. The receiver is
+	/// notified once per failed generation.
+	#[must_use]
+	pub fn failures(&self) -> watch::Receiver<u64> {
+		self.failed.subscribe()
+	}
+}
+
+impl<S: Info> Clone for Swap<S> {
+	fn clone(&self) -> Self {
+		let current = self.rx.borrow();
+		Self {
+			rx: self.rx.clone(),
+			generation: current.generation,
+			current: current.service.duplicate(),
+			failed: Arc::clone(&self.failed),
+		}
+	}
+}
+
+impl<S: Info> tower::Service<S::Request> for Swap<S> {
+	type Response = S::Response;
+	type Error = BoxError;
+	type Future = BoxFuture<Result<S::Response, BoxError>>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		// unlike `Receiver::has_changed` this also detects a last replacement after the handle
+		// was dropped
+		if self.rx.borrow().has_changed() {
+			let current = self.rx.borrow_and_update();
+			self.generation = current.generation;
+			self.current = current.service.duplicate();
+		}
+		let result = self.current.poll_ready(cx);
+		if let Poll::Ready(Err(_)) = &result {
+			let generation = self.generation;
+			self.failed.send_if_modified(|failed| {
+				let modified = *failed < generation;
+				*failed = (*failed).max(generation);
+				modified
+			});
+		}
+		result
+	}
+
+	fn call(&mut self, req: S::Request) -> Self::Future {
+		self.current.call(req)
+	}
+}
+
+impl<S> Clone for Removed<S> {
+	fn clone(&self) -> Self {
+		Self { _p: PhantomData }
+	}
+}
+
+impl<S: Info> tower::Service<S::Request> for Removed<S> {
+	type Response = S::Response;
+	type Error = BoxError;
+	type Future = BoxFuture<Result<S::Response, BoxError>>;
+
+	fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		Poll::Ready(Err(Box::new(NotReady(S::name()))))
+	}
+
+	fn call(&mut self, _req: S::Request) -> Self::Future {
+		Box::pin(async { Err::<S::Response, BoxError>(Box::new(NotReady(S::name()))) })
+	}
+}
@@ -2,6 +2,9 @@
 
 use crate::shutdown::Controller;
 use crate::util::BoxFuture;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use tower::BoxError;
 
 pub mod stream;
@@ -13,4 +16,62 @@ pub trait Session<SB> {
 	/// - `service_builder` Used to build new services on demand
 	/// - `controller` Used to manage graceful shutdown
 	fn run(self, service_builder: SB, controller: Controller) -> BoxFuture<Result<(), BoxError>>;
+
+	/// Returns a description of the endpoint the session listens on (e.g.
+	/// `tcp://127.0.0.1:8080`). Used to export the dependency graph of a runtime.
+	fn endpoint(&self) -> Option<String> {
+		None
+	}
+
+	/// Returns the counter of open connections of this session if supported. Used to report the
+	/// runtime state (see [`crate::runtime::admin`]).
+	fn connections(&self) -> Option<Connections> {
+		None
+	}
+}
+
+/// Sessions which can be bound to an address of the runtime configuration (see
+/// [`crate::runtime::builder::Builder::expose`]).
+pub trait Bind: Sized {
+	/// Creates a session bound to `addr`. Must be called within a tokio runtime.
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if failed to bind to `addr`.
+	fn bind(addr: SocketAddr) -> std::io::Result<Self>;
+}
+
+/// Counts the open connections of a session. Clones share the same counter.
+#[derive(Clone, Debug, Default)]
+pub struct Connections {
+	count: Arc<AtomicUsize>,
+}
+
+/// Keeps a connection counted as open until dropped (see [`Connections::open`]).
+#[derive(Debug)]
+pub struct ConnectionGuard {
+	count: Arc<AtomicUsize>,
+}
+
+impl Connections {
+	/// Returns the number of open connections.
+	#[must_use]
+	pub fn count(&self) -> usize {
+		self.count.load(Ordering::SeqCst)
+	}
+
+	/// Counts a new connection as open until the returned guard is dropped.
+	#[must_use]
+	pub fn open(&self) -> ConnectionGuard {
+		self.count.fetch_add(1, Ordering::SeqCst);
+		ConnectionGuard {
+			count: Arc::clone(&self.count),
+		}
+	}
+}
+
+impl Drop for ConnectionGuard {
+	fn drop(&mut self) {
+		self.count.fetch_sub(1, Ordering::SeqCst);
+	}
 }
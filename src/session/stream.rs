@@ -1,13 +1,62 @@
 use crate::api;
+use crate::api::stream::Requests;
 use crate::shutdown::Controller;
-use crate::util::BoxError;
-use bytes::BytesMut;
+use crate::util::{BoxError, BoxStream};
+use bytes::{Buf, BufMut, BytesMut};
+use futures::StreamExt;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tower::ServiceExt;
 
 const BUF_SIZE: usize = 1024;
+const FRAME_DELIMITER: u8 = b'\n';
 
-/// Spawns a future to handle streams of requests (e.g. a tcp stream).
+/// Returns the position of the delimiter which terminates the first request frame of `buf`. A
+/// frame which starts with a trace context line (see [`api::TRACEPARENT`]) ends with the line
+/// following it.
+fn request_end(buf: &[u8]) -> Option<usize> {
+	let end = buf.iter().position(|b| *b == FRAME_DELIMITER)?;
+	if !buf.starts_with(api::TRACEPARENT) {
+		return Some(end);
+	}
+	let start = end + 1;
+	let end = buf[start..].iter().position(|b| *b == FRAME_DELIMITER)?;
+	Some(start + end)
+}
+
+/// Reads the next newline terminated request from `stream`. Bytes following the request are kept
+/// in `pending` for the next call. Will return `Ok(None)` if the stream was closed or shutdown was
+/// requested before the request was read. An unterminated request at the end of the stream is
+/// returned as is.
+async fn read_request<St>(
+	stream: &mut St,
+	pending: &mut BytesMut,
+	controller: &Controller,
+) -> std::io::Result<Option<BytesMut>>
+where
+	St: AsyncReadExt + Unpin,
+{
+	let mut local_buf = [0_u8; BUF_SIZE];
+	loop {
+		if let Some(end) = request_end(pending) {
+			let request = pending.split_to(end);
+			pending.advance(1);
+			tracing::trace!(message = "request read", size = request.len());
+			return Ok(Some(request));
+		}
+		let num = tokio::select! {
+			res = stream.read(&mut local_buf) => res,
+			_ = controller.wait_for_shutdown() => return Ok(None)
+		};
+		match num? {
+			0 if pending.is_empty() => return Ok(None),
+			0 => return Ok(Some(pending.split())),
+			num => pending.extend_from_slice(&local_buf[..num]),
+		}
+	}
+}
+
+/// Spawns a future to handle streams of requests (e.g. a tcp stream). Each request and each
+/// response is terminated by a newline (see [`api`]).
 ///
 /// # Errors
 ///
@@ -22,29 +71,18 @@ where
 	Sv: tower::Service<BytesMut, Response = BytesMut, Error = api::Error> + Send + 'static,
 	Sv::Future: Send,
 {
-	let mut buf = BytesMut::new();
-	let mut local_buf = [0_u8; BUF_SIZE];
+	let mut pending = BytesMut::new();
 	loop {
-		buf.clear();
-		loop {
-			let num = tokio::select! {
-				res = stream.read(&mut local_buf) => res,
-				_ = controller.wait_for_shutdown() => return Ok(())
-			};
-			let num = num?;
-			buf.extend_from_slice(&local_buf[..num]);
-			if num < BUF_SIZE {
-				break;
-			}
-		}
-		tracing::trace!(message = "buffer read", size = buf.len());
+		let Some(request) = read_request(&mut stream, &mut pending, &controller).await? else {
+			return Ok(());
+		};
 		let ready = match service.ready().await {
 			Ok(service) => service,
 			Err(err) => {
 				return Err(err.err);
 			}
 		};
-		buf = match ready.call(buf).await {
+		let mut buf = match ready.call(request).await {
 			Ok(buf) => buf,
 			Err(err) => {
 				let report = crate::report!(err.err.as_ref());
@@ -52,7 +90,153 @@ where
 				err.buf
 			}
 		};
+		buf.put_u8(FRAME_DELIMITER);
 		tracing::trace!(message = "write buffer", size = buf.len());
-		stream.write_buf(&mut buf).await?;
+		stream.write_all_buf(&mut buf).await?;
+	}
+}
+
+/// Same as [`spawn_fut`] but for services which reply with a stream of frames (see
+/// [`api::stream::Service`]). Each frame is terminated by a newline and written to the stream as
+/// soon as it is available.
+///
+/// # Errors
+///
+/// Will return `Err` if failed to read bytes from stream or send bytes to stream.
+pub async fn spawn_stream_fut<St, Sv>(
+	mut stream: St,
+	mut service: Sv,
+	controller: Controller,
+) -> Result<(), BoxError>
+where
+	St: AsyncReadExt + AsyncWriteExt + Unpin + Send + 'static,
+	Sv: tower::Service<BytesMut, Response = BoxStream<BytesMut>, Error = api::Error>
+		+ Send
+		+ 'static,
+	Sv::Future: Send,
+{
+	let mut pending = BytesMut::new();
+	loop {
+		let Some(request) = read_request(&mut stream, &mut pending, &controller).await? else {
+			return Ok(());
+		};
+		let ready = match service.ready().await {
+			Ok(service) => service,
+			Err(err) => {
+				return Err(err.err);
+			}
+		};
+		let mut frames = match ready.call(request).await {
+			Ok(frames) => frames,
+			Err(mut err) => {
+				let report = crate::report!(err.err.as_ref());
+				tracing::error!("{report:?}");
+				err.buf.put_u8(FRAME_DELIMITER);
+				stream.write_all_buf(&mut err.buf).await?;
+				continue;
+			}
+		};
+		loop {
+			let mut frame = tokio::select! {
+				frame = frames.next() => match frame {
+					Some(frame) => frame,
+					None => break,
+				},
+				_ = controller.wait_for_shutdown() => return Ok(())
+			};
+			frame.put_u8(FRAME_DELIMITER);
+			tracing::trace!(message = "write frame", size = frame.len());
+			stream.write_all_buf(&mut frame).await?;
+		}
+	}
+}
+
+/// Returns a stream of newline terminated frames read from `reader`. The stream ends if the reader
+/// is closed, failed to read or shutdown was requested.
+fn read_frames<R>(reader: R, controller: Controller) -> Requests<BytesMut>
+where
+	R: AsyncReadExt + Unpin + Send + 'static,
+{
+	let state = (reader, BytesMut::new(), controller);
+	let frames = futures::stream::unfold(state, |(mut reader, mut buf, controller)| async move {
+		let mut local_buf = [0_u8; BUF_SIZE];
+		loop {
+			if let Some(pos) = buf.iter().position(|b| *b == FRAME_DELIMITER) {
+				let frame = buf.split_to(pos);
+				buf.advance(1);
+				tracing::trace!(message = "frame read", size = frame.len());
+				return Some((frame, (reader, buf, controller)));
+			}
+			let num = tokio::select! {
+				res = reader.read(&mut local_buf) => res,
+				_ = controller.wait_for_shutdown() => return None
+			};
+			match num {
+				Ok(0) if buf.is_empty() => return None,
+				Ok(0) => {
+					let frame = buf.split();
+					return Some((frame, (reader, buf, controller)));
+				}
+				Ok(num) => buf.extend_from_slice(&local_buf[..num]),
+				Err(err) => {
+					let report = crate::report!(err);
+					tracing::error!("{report:?}");
+					return None;
+				}
+			}
+		}
+	});
+	Requests::new(frames)
+}
+
+/// Spawns a future to handle a bidirectional stream of newline terminated frames (see
+/// [`api::stream::DuplexService`]). The service is called once with the stream of received frames.
+/// Each frame returned by the service is written to the stream as soon as it is available. The
+/// future completes after the last frame was written.
+///
+/// # Errors
+///
+/// Will return `Err` if service failed or failed to send bytes to stream.
+pub async fn spawn_duplex_fut<St, Sv>(
+	stream: St,
+	mut service: Sv,
+	controller: Controller,
+) -> Result<(), BoxError>
+where
+	St: AsyncReadExt + AsyncWriteExt + Unpin + Send + 'static,
+	Sv: tower::Service<Requests<BytesMut>, Response = BoxStream<BytesMut>, Error = api::Error>
+		+ Send
+		+ 'static,
+	Sv::Future: Send,
+{
+	let (reader, mut writer) = tokio::io::split(stream);
+	let frames = read_frames(reader, controller.clone());
+	let ready = match service.ready().await {
+		Ok(service) => service,
+		Err(err) => {
+			return Err(err.err);
+		}
+	};
+	let mut responses = match ready.call(frames).await {
+		Ok(responses) => responses,
+		Err(mut err) => {
+			let report = crate::report!(err.err.as_ref());
+			tracing::error!("{report:?}");
+			err.buf.put_u8(FRAME_DELIMITER);
+			writer.write_all_buf(&mut err.buf).await?;
+			return Ok(());
+		}
+	};
+	loop {
+		let mut frame = tokio::select! {
+			frame = responses.next() => match frame {
+				Some(frame) => frame,
+				None => return Ok(()),
+			},
+			_ = controller.wait_for_shutdown() => return Ok(())
+		};
+		frame.put_u8(FRAME_DELIMITER);
+		tracing::trace!(message = "write frame", size = frame.len());
+		writer.write_all_buf(&mut frame).await?;
 	}
 }
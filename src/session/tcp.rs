@@ -1,15 +1,38 @@
 use crate::api::codec::{Decode, Encode};
+use crate::api::stream::Requests;
 use crate::api::Message;
+use crate::metrics;
+use crate::service::Info;
+use crate::session::Connections;
 use crate::shutdown::Controller;
-use crate::util::BoxFuture;
+use crate::util::{BoxFuture, BoxStream};
 use std::marker::PhantomData;
 use std::net::SocketAddr;
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpStream};
 use tower::{BoxError, Layer, Service, ServiceExt};
 
 pub struct Session<ED, Req> {
 	addr: SocketAddr,
 	listener: TcpListener,
+	connections: Connections,
+	_p: PhantomData<(Req, ED)>,
+}
+
+/// Same as [`Session`] but for services which reply with a stream of responses (see
+/// [`crate::api::stream::Service`]).
+pub struct StreamSession<ED, Req> {
+	addr: SocketAddr,
+	listener: TcpListener,
+	connections: Connections,
+	_p: PhantomData<(Req, ED)>,
+}
+
+/// Same as [`Session`] but for services which receive a stream of requests (see
+/// [`crate::api::stream::DuplexService`]). Each connection handles a single call.
+pub struct DuplexSession<ED, Req> {
+	addr: SocketAddr,
+	listener: TcpListener,
+	connections: Connections,
 	_p: PhantomData<(Req, ED)>,
 }
 
@@ -24,9 +47,171 @@ impl<ED, Req> Session<ED, Req> {
 		Ok(Self {
 			addr,
 			listener,
+			connections: Connections::default(),
 			_p: PhantomData,
 		})
 	}
+
+	/// Returns the local address the session is bound to.
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if failed to query the address of the tcp listener.
+	pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+		self.listener.local_addr()
+	}
+}
+
+impl<ED, Req> StreamSession<ED, Req> {
+	/// Create streaming tcp session that binds to address `addr`.
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if failed to create tcp listener.
+	pub async fn with_addr(addr: SocketAddr) -> std::io::Result<Self> {
+		let listener = TcpListener::bind(&addr).await?;
+		Ok(Self {
+			addr,
+			listener,
+			connections: Connections::default(),
+			_p: PhantomData,
+		})
+	}
+
+	/// Returns the local address the session is bound to.
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if failed to query the address of the tcp listener.
+	pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+		self.listener.local_addr()
+	}
+}
+
+impl<ED, Req> DuplexSession<ED, Req> {
+	/// Create bidirectional streaming tcp session that binds to address `addr`.
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if failed to create tcp listener.
+	pub async fn with_addr(addr: SocketAddr) -> std::io::Result<Self> {
+		let listener = TcpListener::bind(&addr).await?;
+		Ok(Self {
+			addr,
+			listener,
+			connections: Connections::default(),
+			_p: PhantomData,
+		})
+	}
+
+	/// Returns the local address the session is bound to.
+	///
+	/// # Errors
+	///
+	/// Will return `Err` if failed to query the address of the tcp listener.
+	pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+		self.listener.local_addr()
+	}
+}
+
+/// Binds a tcp listener to `addr` without awaiting (see [`super::Bind`]).
+fn listen(addr: SocketAddr) -> std::io::Result<TcpListener> {
+	let listener = std::net::TcpListener::bind(addr)?;
+	listener.set_nonblocking(true)?;
+	TcpListener::from_std(listener)
+}
+
+macro_rules! impl_bind {
+	($($session:ident),*) => {
+		$(
+			impl<ED, Req> super::Bind for $session<ED, Req> {
+				fn bind(addr: SocketAddr) -> std::io::Result<Self> {
+					Ok(Self {
+						addr,
+						listener: listen(addr)?,
+						connections: Connections::default(),
+						_p: PhantomData,
+					})
+				}
+			}
+		)*
+	};
+}
+
+impl_bind!(Session, StreamSession, DuplexSession);
+
+/// Accepts new connections on `listener` until shutdown is requested. Will build a new service
+/// with `builder` for each connection and pass it together with the tcp stream to `handle`.
+async fn accept<SB, F>(
+	addr: SocketAddr,
+	listener: TcpListener,
+	connections: Connections,
+	mut builder: SB,
+	controller: Controller,
+	handle: F,
+) -> Result<(), BoxError>
+where
+	SB: Service<SocketAddr, Error = BoxError>,
+	F: Fn(TcpStream, SB::Response, Controller) -> BoxFuture<Result<(), BoxError>>,
+{
+	tracing::info!(message = "listening on", port = addr.port());
+	let endpoint = listener.local_addr().unwrap_or(addr).to_string();
+	let labels = [("endpoint", endpoint.as_str())];
+	let accepted = metrics::global().counter(
+		"micro_tower_connections_accepted_total",
+		"Number of connections accepted by a session.",
+		&labels,
+	);
+	let active = metrics::global().gauge(
+		"micro_tower_connections_active",
+		"Number of open connections of a session.",
+		&labels,
+	);
+
+	loop {
+		tracing::trace!(message = "wait for new connections", port = addr.port());
+
+		let (stream, addr) = tokio::select! {
+			result = listener.accept() => result?,
+			_ = controller.wait_for_shutdown() => {
+				tracing::trace!(message = "Received shutdown signal. Stop accepting new connections.", port = addr.port());
+				return Ok(())
+			}
+		};
+
+		let service = match builder.ready().await {
+			Ok(service) => service,
+			Err(err) => {
+				let report = crate::report!(err.as_ref());
+				tracing::error!("{report:?}");
+				continue;
+			}
+		};
+		let service = match service.call(addr).await {
+			Ok(service) => service,
+			Err(err) => {
+				let report = crate::report!(err.as_ref());
+				tracing::error!("{report:?}");
+				continue;
+			}
+		};
+
+		tracing::info!(message = "new connection", addr = format!("{addr}"));
+
+		let fut = handle(stream, service, controller.clone());
+		let guard = connections.open();
+		accepted.inc();
+		active.inc();
+		let active = active.clone();
+		tokio::spawn(async move {
+			let _guard = guard;
+			if let Err(err) = fut.await {
+				let report = crate::report!(err.as_ref());
+				tracing::error!("{report:?}");
+			}
+			active.dec();
+		});
+	}
 }
 
 impl<SB, ED, Req> super::Session<SB> for Session<ED, Req>
@@ -34,7 +219,7 @@ where
 	Req: Send + 'static,
 	SB: Service<SocketAddr, Error = BoxError> + Send + 'static,
 	SB::Future: Send,
-	SB::Response: Service<Req, Error = BoxError> + Send,
+	SB::Response: Service<Req, Error = BoxError> + Info + Send,
 	<SB::Response as Service<Req>>::Future: Send,
 	ED: Encode<Message<<SB::Response as tower::Service<Req>>::Response>>
 		+ Decode<Req>
@@ -44,52 +229,104 @@ where
 		std::error::Error + Send + Sync + 'static,
 	<ED as Decode<Req>>::Error: std::error::Error + Send + Sync + Unpin + 'static,
 {
-	fn run(self, mut builder: SB, controller: Controller) -> BoxFuture<Result<(), BoxError>> {
-		Box::pin(async move {
-			let addr = self.addr;
-			let listener = self.listener;
-			tracing::info!(message = "listening on", port = addr.port());
-
-			loop {
-				tracing::trace!(message = "wait for new connections", port = addr.port());
-
-				let (stream, addr) = tokio::select! {
-					result = listener.accept() => result?,
-					_ = controller.wait_for_shutdown() => {
-						tracing::trace!(message = "Received shutdown signal. Stop accepting new connections.", port = addr.port());
-						return Ok(())
-					}
-				};
-
-				let service = match builder.ready().await {
-					Ok(service) => service,
-					Err(err) => {
-						let report = crate::report!(err.as_ref());
-						tracing::error!("{report:?}");
-						continue;
-					}
-				};
-				let service = match service.call(addr).await {
-					Ok(service) => service,
-					Err(err) => {
-						let report = crate::report!(err.as_ref());
-						tracing::error!("{report:?}");
-						continue;
-					}
-				};
-				let layer = crate::api::Layer::<Req, ED>::default();
+	fn run(self, builder: SB, controller: Controller) -> BoxFuture<Result<(), BoxError>> {
+		Box::pin(accept(
+			self.addr,
+			self.listener,
+			self.connections,
+			builder,
+			controller,
+			|stream, service, controller| {
+				let layer = crate::api::Layer::<Req, ED>::named(<SB::Response as Info>::name());
 				let service = layer.layer(service);
+				Box::pin(super::stream::spawn_fut(stream, service, controller))
+			},
+		))
+	}
 
-				tracing::info!(message = "new connection", addr = format!("{addr}"));
+	fn endpoint(&self) -> Option<String> {
+		let addr = self.listener.local_addr().ok()?;
+		Some(format!("tcp://{addr}"))
+	}
 
-				let controller = controller.clone();
-				tokio::spawn(async move {
-					if let Err(err) = super::stream::spawn_fut(stream, service, controller).await {
-						let report = crate::report!(err.as_ref());
-						tracing::error!("{report:?}");
-					}
-				});
-			}
-		})
+	fn connections(&self) -> Option<Connections> {
+		Some(self.connections.clone())
+	}
+}
+
+impl<SB, ED, Req, T> super::Session<SB> for StreamSession<ED, Req>
+where
+	Req: Send + 'static,
+	T: Send + 'static,
+	SB: Service<SocketAddr, Error = BoxError> + Send + 'static,
+	SB::Future: Send,
+	SB::Response:
+		Service<Req, Response = BoxStream<Result<T, BoxError>>, Error = BoxError> + Info + Send,
+	<SB::Response as Service<Req>>::Future: Send + 'static,
+	ED: Encode<Message<T>> + Decode<Req> + Send + 'static,
+	<ED as Encode<Message<T>>>::Error: std::error::Error + Send + Sync + 'static,
+	<ED as Decode<Req>>::Error: std::error::Error + Send + Sync + Unpin + 'static,
+{
+	fn run(self, builder: SB, controller: Controller) -> BoxFuture<Result<(), BoxError>> {
+		Box::pin(accept(
+			self.addr,
+			self.listener,
+			self.connections,
+			builder,
+			controller,
+			|stream, service, controller| {
+				let layer =
+					crate::api::stream::Layer::<Req, ED>::named(<SB::Response as Info>::name());
+				let service = layer.layer(service);
+				Box::pin(super::stream::spawn_stream_fut(stream, service, controller))
+			},
+		))
+	}
+
+	fn endpoint(&self) -> Option<String> {
+		let addr = self.listener.local_addr().ok()?;
+		Some(format!("tcp://{addr}"))
+	}
+
+	fn connections(&self) -> Option<Connections> {
+		Some(self.connections.clone())
+	}
+}
+
+impl<SB, ED, Req, T> super::Session<SB> for DuplexSession<ED, Req>
+where
+	Req: Send + 'static,
+	T: Send + 'static,
+	SB: Service<SocketAddr, Error = BoxError> + Send + 'static,
+	SB::Future: Send,
+	SB::Response:
+		Service<Requests<Req>, Response = BoxStream<Result<T, BoxError>>, Error = BoxError> + Send,
+	<SB::Response as Service<Requests<Req>>>::Future: Send + 'static,
+	ED: Encode<Message<T>> + Decode<Message<Req>> + Send + 'static,
+	<ED as Encode<Message<T>>>::Error: std::error::Error + Send + Sync + 'static,
+	<ED as Decode<Message<Req>>>::Error: std::error::Error + Send + Sync + 'static,
+{
+	fn run(self, builder: SB, controller: Controller) -> BoxFuture<Result<(), BoxError>> {
+		Box::pin(accept(
+			self.addr,
+			self.listener,
+			self.connections,
+			builder,
+			controller,
+			|stream, service, controller| {
+				let layer = crate::api::stream::DuplexLayer::<Req, ED>::default();
+				let service = layer.layer(service);
+				Box::pin(super::stream::spawn_duplex_fut(stream, service, controller))
+			},
+		))
+	}
+
+	fn endpoint(&self) -> Option<String> {
+		let addr = self.listener.local_addr().ok()?;
+		Some(format!("tcp://{addr}"))
+	}
+
+	fn connections(&self) -> Option<Connections> {
+		Some(self.connections.clone())
 	}
 }
@@ -1,19 +1,33 @@
-//! Utilities to manage unified shutdown on system events (e.g. SIGTERM, SIGQUIT)
+//! Utilities to manage unified shutdown on system events (e.g. SIGTERM, SIGQUIT) and reloads of
+//! the runtime configuration (SIGHUP).
 
+use std::sync::Arc;
 use tokio::signal::unix;
 use tokio::signal::unix::SignalKind;
+use tokio::sync::watch;
 use tokio::task::JoinHandle;
 use tokio_util::sync::{CancellationToken, WaitForCancellationFuture};
 
-#[derive(Default)]
 pub struct Controller {
 	token: CancellationToken,
+	/// Counts reload requests. Shared by all clones.
+	reload: Arc<watch::Sender<usize>>,
+}
+
+impl Default for Controller {
+	fn default() -> Self {
+		Self {
+			token: CancellationToken::default(),
+			reload: Arc::new(watch::channel(0).0),
+		}
+	}
 }
 
 impl Clone for Controller {
 	fn clone(&self) -> Self {
 		Self {
 			token: self.token.child_token(),
+			reload: Arc::clone(&self.reload),
 		}
 	}
 }
@@ -25,6 +39,15 @@ impl Controller {
 		Self::default()
 	}
 
+	/// Returns a controller which shares the shutdown signal with `self`. Unlike [`Clone::clone`]
+	/// a shutdown emitted by the returned controller also shuts down `self`.
+	pub(crate) fn share(&self) -> Self {
+		Self {
+			token: self.token.clone(),
+			reload: Arc::clone(&self.reload),
+		}
+	}
+
 	/// Emit shutdown signal.
 	pub fn shutdown(&self) {
 		self.token.cancel();
@@ -35,32 +58,57 @@ impl Controller {
 		self.token.cancelled()
 	}
 
-	/// Spawns a new handler which waits for shutdown signals.
+	/// Emit reload signal. Does not shut down.
+	pub fn reload(&self) {
+		self.reload.send_modify(|count| *count += 1);
+	}
+
+	/// Returns a receiver which is notified on each reload signal (see [`Controller::reload`]).
+	#[must_use]
+	pub fn subscribe_reload(&self) -> watch::Receiver<usize> {
+		self.reload.subscribe()
+	}
+
+	/// Spawns a new handler which waits for shutdown signals or a shutdown emitted by this
+	/// controller. Emits a reload signal on SIGHUP (see [`Controller::subscribe_reload`]).
 	///
 	/// # Errors
 	///
-	/// Will return `Err` if process wasn't able to acquire quit and terminate signal handler.
+	/// Will return `Err` if process wasn't able to acquire quit, terminate and hangup signal
+	/// handler.
 	pub fn spawn_handler(self) -> std::io::Result<JoinHandle<()>> {
 		let mut qt = unix::signal(SignalKind::quit())?;
 		let mut tm = unix::signal(SignalKind::terminate())?;
+		let mut hup = unix::signal(SignalKind::hangup())?;
 
 		let handle = tokio::spawn(async move {
-			tokio::select! {
-				_ = qt.recv() => {
-					tracing::debug!("received SIGQUIT signal");
-				},
-				_ = tm.recv() => {
-					tracing::debug!("received SIGTERM signal");
-				}
-				res = tokio::signal::ctrl_c() => {
-					match res {
-						Ok(_) => tracing::debug!("received ctrl-c shutdown request"),
-						Err(err) => {
-							let report = crate::report!(err);
-							tracing::error!("{report:?}");
+			loop {
+				tokio::select! {
+					_ = qt.recv() => {
+						tracing::debug!("received SIGQUIT signal");
+					},
+					_ = tm.recv() => {
+						tracing::debug!("received SIGTERM signal");
+					}
+					_ = hup.recv() => {
+						tracing::debug!("received SIGHUP signal");
+						self.reload();
+						continue;
+					}
+					_ = self.token.cancelled() => {
+						tracing::debug!("received shutdown request");
+					}
+					res = tokio::signal::ctrl_c() => {
+						match res {
+							Ok(_) => tracing::debug!("received ctrl-c shutdown request"),
+							Err(err) => {
+								let report = crate::report!(err);
+								tracing::error!("{report:?}");
+							}
 						}
 					}
 				}
+				break;
 			}
 			tracing::trace!("sending shutdown signal");
 			self.shutdown();
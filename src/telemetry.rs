@@ -0,0 +1,447 @@
+//! Installs a global tracing subscriber. Used by `#[micro_tower::codegen::main]` unless disabled
+//! with `subscriber = false`.
+//!
+//! Log filters use the directive syntax of `RUST_LOG` (e.g. `info,micro_tower=debug`). If
+//! `RUST_LOG` is set, it takes precedence over the filter passed by the application. Only target
+//! and level directives are supported (see [`Targets`]). Span and field directives such as
+//! `micro_tower[request]=debug` or `[{id=42}]=trace` are rejected with [`Error::Filter`].
+//!
+//! # Trace Context
+//!
+//! Every span is assigned a W3C trace [`Context`] (see [`propagation`]). Remote clients send the
+//! context of the current span along with each request and sessions continue the trace of the
+//! caller (see [`crate::api::TRACEPARENT`]), so one logical request can be followed across
+//! processes by its trace id.
+
+use crate::util::BoxError;
+use serde_json::{Map, Value};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::filter::{filter_fn, ParseError, Targets};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields, MakeWriter};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{reload, Layer};
+
+/// Filter used if neither the application nor `RUST_LOG` specify one.
+pub const DEFAULT_FILTER: &str = "info";
+
+/// Environment variable which overrides the log filter.
+pub const FILTER_ENV: &str = "RUST_LOG";
+
+/// Name of the span field which holds the trace context of a remote caller. Spans with this field
+/// continue the trace of the caller instead of their parent span.
+pub const TRACEPARENT: &str = "traceparent";
+
+/// Output format of log events.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+	/// Single line per event including span context.
+	#[default]
+	Full,
+	/// Multi-line human readable output.
+	Pretty,
+	/// Single line per event with less verbose span context.
+	Compact,
+	/// One JSON object per line.
+	Json,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error("invalid log filter `{filter}`")]
+	Filter {
+		filter: String,
+		#[source]
+		source: ParseError,
+	},
+	#[error("unknown log format `{0}` (expected `full`, `pretty`, `compact` or `json`)")]
+	Format(String),
+	#[error("failed to install global tracing subscriber")]
+	Install(#[source] tracing::subscriber::SetGlobalDefaultError),
+	#[error("invalid traceparent `{0}`")]
+	TraceParent(String),
+	#[error("log filter can only be changed if the subscriber was installed by `init`")]
+	Unsupported,
+	#[error("failed to change log filter: {0}")]
+	Reload(String),
+}
+
+/// Replaces the filter of the subscriber installed by [`init`].
+type SetFilter = Box<dyn Fn(Targets) -> Result<(), reload::Error> + Send + Sync>;
+
+static FILTER: OnceLock<SetFilter> = OnceLock::new();
+
+/// Trace context of a span as defined by W3C Trace Context (see
+/// <https://www.w3.org/TR/trace-context/#traceparent-header>). Formatted as `traceparent` header
+/// value (e.g. `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Context {
+	pub trace_id: u128,
+	pub span_id: u64,
+	pub sampled: bool,
+}
+
+/// Assigns a [`Context`] to each new span (see [`propagation`]).
+struct Propagation;
+
+/// Reads the [`TRACEPARENT`] field of a new span.
+#[derive(Default)]
+struct TraceParentVisitor {
+	context: Option<Context>,
+}
+
+/// Formats events as JSON lines with the fields `timestamp` (seconds since unix epoch), `level`,
+/// `target`, `fields` and `spans` (from root to leaf). Events within a span with a trace
+/// [`Context`] also contain `trace_id` and `span_id`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Json;
+
+#[derive(Default)]
+struct JsonVisitor {
+	fields: Map<String, Value>,
+}
+
+impl FromStr for Format {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"full" => Ok(Self::Full),
+			"pretty" => Ok(Self::Pretty),
+			"compact" => Ok(Self::Compact),
+			"json" => Ok(Self::Json),
+			_ => Err(Error::Format(s.to_string())),
+		}
+	}
+}
+
+impl Context {
+	/// Starts a new trace.
+	#[must_use]
+	pub fn root() -> Self {
+		Self {
+			trace_id: u128::from(random()) << 64 | u128::from(random()),
+			span_id: random(),
+			sampled: true,
+		}
+	}
+
+	/// Returns the context of a new span within the same trace.
+	#[must_use]
+	pub fn child(&self) -> Self {
+		Self {
+			span_id: random(),
+			..*self
+		}
+	}
+
+	/// Returns the context of the current span. Returns `None` if there is no current span or the
+	/// subscriber does not include [`propagation`].
+	#[must_use]
+	pub fn current() -> Option<Self> {
+		tracing::Span::current()
+			.with_subscriber(|(id, dispatch)| {
+				let registry = dispatch.downcast_ref::<tracing_subscriber::Registry>()?;
+				let span = registry.span(id)?;
+				let extensions = span.extensions();
+				extensions.get::<Self>().copied()
+			})
+			.flatten()
+	}
+}
+
+impl std::fmt::Display for Context {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let flags = u8::from(self.sampled);
+		write!(
+			f,
+			"00-{:032x}-{:016x}-{flags:02x}",
+			self.trace_id, self.span_id
+		)
+	}
+}
+
+impl FromStr for Context {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let err = || Error::TraceParent(s.to_string());
+		let mut parts = s.split('-');
+		let (Some(version), Some(trace_id), Some(span_id), Some(flags)) =
+			(parts.next(), parts.next(), parts.next(), parts.next())
+		else {
+			return Err(err());
+		};
+		let hex = |part: &str, len: usize| {
+			part.len() == len && part.bytes().all(|b| b.is_ascii_hexdigit())
+		};
+		if !hex(version, 2) || version == "ff" || (version == "00" && parts.next().is_some()) {
+			return Err(err());
+		}
+		if !hex(trace_id, 32) || !hex(span_id, 16) || !hex(flags, 2) {
+			return Err(err());
+		}
+		let trace_id = u128::from_str_radix(trace_id, 16).map_err(|_| err())?;
+		let span_id = u64::from_str_radix(span_id, 16).map_err(|_| err())?;
+		let flags = u8::from_str_radix(flags, 16).map_err(|_| err())?;
+		if trace_id == 0 || span_id == 0 {
+			return Err(err());
+		}
+		Ok(Self {
+			trace_id,
+			span_id,
+			sampled: flags & 1 == 1,
+		})
+	}
+}
+
+/// Returns a non-zero random id.
+fn random() -> u64 {
+	static COUNTER: AtomicU64 = AtomicU64::new(0);
+	loop {
+		let mut hasher = RandomState::new().build_hasher();
+		hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+		let id = hasher.finish();
+		if id != 0 {
+			return id;
+		}
+	}
+}
+
+/// Creates a layer which assigns a trace [`Context`] to every span. Spans with a [`TRACEPARENT`]
+/// field continue the trace of the remote caller, all other spans continue the trace of their
+/// parent or start a new trace. Included in [`subscriber`], must be added manually to custom
+/// subscribers to propagate trace contexts.
+#[must_use]
+pub fn propagation<S>() -> impl tracing_subscriber::Layer<S> + Send + Sync
+where
+	S: Subscriber + for<'a> LookupSpan<'a>,
+{
+	Propagation.with_filter(filter_fn(is_span))
+}
+
+fn is_span(metadata: &tracing::Metadata<'_>) -> bool {
+	metadata.is_span()
+}
+
+impl<S> tracing_subscriber::Layer<S> for Propagation
+where
+	S: Subscriber + for<'a> LookupSpan<'a>,
+{
+	fn on_new_span(
+		&self,
+		attrs: &Attributes<'_>,
+		id: &Id,
+		ctx: tracing_subscriber::layer::Context<'_, S>,
+	) {
+		let Some(span) = ctx.span(id) else {
+			return;
+		};
+		let mut visitor = TraceParentVisitor::default();
+		attrs.record(&mut visitor);
+		let context = visitor.context.map_or_else(
+			|| {
+				span.parent()
+					.and_then(|parent| parent.extensions().get::<Context>().map(Context::child))
+					.unwrap_or_else(Context::root)
+			},
+			|remote| remote.child(),
+		);
+		span.extensions_mut().insert(context);
+	}
+}
+
+impl Visit for TraceParentVisitor {
+	fn record_str(&mut self, field: &Field, value: &str) {
+		if field.name() == TRACEPARENT {
+			self.context = value.parse().ok();
+		}
+	}
+
+	fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+		if field.name() == TRACEPARENT {
+			self.context = format!("{value:?}").parse().ok();
+		}
+	}
+}
+
+/// Returns the value of `RUST_LOG` if set and `filter` otherwise.
+#[must_use]
+pub fn filter(filter: &str) -> String {
+	std::env::var(FILTER_ENV).unwrap_or_else(|_| filter.to_string())
+}
+
+/// Creates a subscriber which writes events matching `filter` with `format` to `writer`. Unlike
+/// [`init`], `RUST_LOG` is not considered.
+///
+/// # Errors
+///
+/// Will return `Err` if `filter` is not a valid filter directive.
+pub fn subscriber<W>(
+	filter: &str,
+	format: Format,
+	writer: W,
+) -> Result<Box<dyn Subscriber + Send + Sync>, Error>
+where
+	W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+	build(filter, format, writer).map(|(subscriber, _)| subscriber)
+}
+
+/// Same as [`subscriber`] but also returns a function to replace the filter.
+fn build<W>(
+	filter: &str,
+	format: Format,
+	writer: W,
+) -> Result<(Box<dyn Subscriber + Send + Sync>, SetFilter), Error>
+where
+	W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+	let (targets, handle) = reload::Layer::new(parse_filter(filter)?);
+	let set_filter: SetFilter = Box::new(move |targets| handle.reload(targets));
+	let layer = tracing_subscriber::fmt::layer().with_writer(writer);
+	let registry = tracing_subscriber::registry().with(propagation());
+	let subscriber: Box<dyn Subscriber + Send + Sync> = match format {
+		Format::Full => Box::new(registry.with(layer.with_filter(targets))),
+		Format::Pretty => Box::new(registry.with(layer.pretty().with_filter(targets))),
+		Format::Compact => Box::new(registry.with(layer.compact().with_filter(targets))),
+		Format::Json => {
+			let layer = layer.with_ansi(false).event_format(Json);
+			Box::new(registry.with(layer.with_filter(targets)))
+		}
+	};
+	Ok((subscriber, set_filter))
+}
+
+/// Parses `filter` as [`Targets`]. Span and field directives are rejected because [`Targets`]
+/// would treat them as part of the target name and silently never match.
+fn parse_filter(filter: &str) -> Result<Targets, Error> {
+	let error = |source| Error::Filter {
+		filter: filter.to_string(),
+		source,
+	};
+	if filter.contains('[') {
+		let err: BoxError = "span and field directives are not supported".into();
+		return Err(error(ParseError::from(err)));
+	}
+	Targets::from_str(filter).map_err(error)
+}
+
+/// Installs a global subscriber which writes events to stdout. Uses `RUST_LOG` as filter if set
+/// and `filter` otherwise. The filter can be changed later on (see [`set_filter`]). Only target
+/// and level directives are accepted (e.g. `warn,micro_tower::session=debug`), span and field
+/// directives of `RUST_LOG` are not supported.
+///
+/// # Errors
+///
+/// Will return `Err` if the filter is invalid (including span and field directives) or a global
+/// subscriber is already installed.
+pub fn init(filter: &str, format: Format) -> Result<(), Error> {
+	let (subscriber, set_filter) = build(&self::filter(filter), format, std::io::stdout)?;
+	tracing::subscriber::set_global_default(subscriber).map_err(Error::Install)?;
+	let _ = FILTER.set(set_filter);
+	Ok(())
+}
+
+/// Replaces the filter of the global subscriber installed by [`init`]. Takes precedence over
+/// `RUST_LOG`.
+///
+/// # Errors
+///
+/// Will return `Err` if `filter` is invalid or the global subscriber was not installed by
+/// [`init`].
+pub fn set_filter(filter: &str) -> Result<(), Error> {
+	let targets = parse_filter(filter)?;
+	let set_filter = FILTER.get().ok_or(Error::Unsupported)?;
+	set_filter(targets).map_err(|err| Error::Reload(err.to_string()))
+}
+
+impl<S, N> FormatEvent<S, N> for Json
+where
+	S: Subscriber + for<'a> LookupSpan<'a>,
+	N: for<'a> FormatFields<'a> + 'static,
+{
+	fn format_event(
+		&self,
+		ctx: &FmtContext<'_, S, N>,
+		mut writer: Writer<'_>,
+		event: &Event<'_>,
+	) -> std::fmt::Result {
+		let timestamp = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map_or(0.0, |time| time.as_secs_f64());
+		let mut visitor = JsonVisitor::default();
+		event.record(&mut visitor);
+		let spans: Vec<_> = ctx
+			.event_scope()
+			.into_iter()
+			.flat_map(tracing_subscriber::registry::Scope::from_root)
+			.map(|span| {
+				let extensions = span.extensions();
+				let fields = extensions
+					.get::<FormattedFields<N>>()
+					.map(|fields| fields.fields.clone())
+					.unwrap_or_default();
+				serde_json::json!({ "name": span.name(), "fields": fields })
+			})
+			.collect();
+		let context = ctx
+			.event_scope()
+			.and_then(|mut scope| scope.next())
+			.and_then(|span| span.extensions().get::<Context>().copied());
+		let metadata = event.metadata();
+		let mut line = serde_json::json!({
+			"timestamp": timestamp,
+			"level": metadata.level().as_str(),
+			"target": metadata.target(),
+			"fields": visitor.fields,
+			"spans": spans,
+		});
+		if let Some(context) = context {
+			line["trace_id"] = format!("{:032x}", context.trace_id).into();
+			line["span_id"] = format!("{:016x}", context.span_id).into();
+		}
+		writeln!(writer, "{line}")
+	}
+}
+
+impl Visit for JsonVisitor {
+	fn record_f64(&mut self, field: &Field, value: f64) {
+		self.fields.insert(field.name().to_string(), value.into());
+	}
+
+	fn record_i64(&mut self, field: &Field, value: i64) {
+		self.fields.insert(field.name().to_string(), value.into());
+	}
+
+	fn record_u64(&mut self, field: &Field, value: u64) {
+		self.fields.insert(field.name().to_string(), value.into());
+	}
+
+	fn record_bool(&mut self, field: &Field, value: bool) {
+		self.fields.insert(field.name().to_string(), value.into());
+	}
+
+	fn record_str(&mut self, field: &Field, value: &str) {
+		self.fields.insert(field.name().to_string(), value.into());
+	}
+
+	fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+		let message = format!("{}", std::error::Report::new(value));
+		self.fields.insert(field.name().to_string(), message.into());
+	}
+
+	fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+		self.fields
+			.insert(field.name().to_string(), format!("{value:?}").into());
+	}
+}
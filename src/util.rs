@@ -1,5 +1,6 @@
 pub mod borrow;
 
+use futures::Stream;
 use std::future::Future;
 use std::pin::Pin;
 pub use tower::BoxError;
@@ -19,6 +20,8 @@ pub type BoxCloneService<R, S> =
 
 pub type BoxFuture<O> = Pin<Box<dyn Future<Output = O> + Send>>;
 
+pub type BoxStream<O> = Pin<Box<dyn Stream<Item = O> + Send>>;
+
 /// Generates a [`std::error::Report`] with `pretty` and `backtrace` enabled
 #[macro_export]
 macro_rules! report {
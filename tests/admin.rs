@@ -0,0 +1,146 @@
+// `Ok(result?)` in the generated `call` is needless for services failing with `BoxError`
+#![allow(clippy::needless_question_mark)]
+
+use micro_tower::api::codec;
+use micro_tower::prelude::*;
+use micro_tower::runtime::admin::Admin;
+use micro_tower::runtime::Runtime;
+use micro_tower::service::Service;
+use micro_tower::session::tcp;
+use micro_tower::util::BoxError;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpStream, UnixStream};
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn hello(_request: ()) -> &'static str {
+	"Hello, World!"
+}
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn greet(_request: (), mut hello: Service<hello>) -> Result<&'static str, BoxError> {
+	hello.ready().await?.call(()).await
+}
+
+/// Sends a HTTP request to `io` and returns status code and JSON body of the response.
+async fn request(
+	mut io: impl AsyncRead + AsyncWrite + Unpin,
+	method: &str,
+	path: &str,
+) -> (u16, serde_json::Value) {
+	let request = format!("{method} {path} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+	io.write_all(request.as_bytes()).await.unwrap();
+	let mut response = String::new();
+	io.read_to_string(&mut response).await.unwrap();
+	let (head, body) = response.split_once("\r\n\r\n").unwrap();
+	let code = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+	(code, serde_json::from_str(body).unwrap())
+}
+
+#[tokio::test]
+async fn admin_tcp() {
+	let admin = Admin::with_addr("127.0.0.1:0".parse().unwrap())
+		.await
+		.unwrap()
+		.with_shutdown();
+	let addr = admin.local_addr().unwrap();
+	let session = tcp::Session::<codec::Json, _>::with_addr("127.0.0.1:0".parse().unwrap())
+		.await
+		.unwrap();
+	let runtime = Runtime::builder()
+		.service::<hello>()
+		.bind_service::<greet, _>(session)
+		.admin(admin)
+		.build()
+		.await
+		.unwrap();
+	let get = |path: &'static str| async move {
+		request(TcpStream::connect(addr).await.unwrap(), "GET", path).await
+	};
+
+	tokio::time::timeout(Duration::from_secs(5), async {
+		while get("/ready").await.0 != 200 {
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.unwrap();
+	assert_eq!(
+		get("/live").await,
+		(200, serde_json::json!({ "live": true }))
+	);
+	let (code, health) = get("/health").await;
+	assert_eq!(code, 200);
+	assert_eq!(health["status"], "healthy");
+
+	let (code, state) = get("/state").await;
+	assert_eq!(code, 200);
+	let registry = state["registry"].as_array().unwrap();
+	assert!(registry.iter().any(|entry| entry["key"] == "hello"));
+	assert_eq!(state["sessions"][0]["service"], "greet");
+	assert_eq!(state["sessions"][0]["connections"], 0);
+	assert_eq!(state["errors"], serde_json::json!([]));
+
+	assert_eq!(get("/unknown").await.0, 404);
+	assert_eq!(get("/shutdown").await.0, 405);
+
+	let run = tokio::spawn(runtime.run());
+	let (code, _) = request(TcpStream::connect(addr).await.unwrap(), "POST", "/shutdown").await;
+	assert_eq!(code, 202);
+	tokio::time::timeout(Duration::from_secs(5), run)
+		.await
+		.unwrap()
+		.unwrap();
+}
+
+#[tokio::test]
+async fn admin_unix() {
+	let path = std::env::temp_dir().join(format!("micro-tower-admin-{}.sock", std::process::id()));
+	let _ = std::fs::remove_file(&path);
+	let admin = Admin::with_path(&path).unwrap().with_shutdown();
+	assert_eq!(admin.endpoint(), Some(format!("unix://{}", path.display())));
+	let runtime = Runtime::builder()
+		.service::<hello>()
+		.admin(admin)
+		.build()
+		.await
+		.unwrap();
+
+	let (code, body) = request(UnixStream::connect(&path).await.unwrap(), "GET", "/ready").await;
+	assert_eq!(code, 200);
+	assert_eq!(body["ready"], true);
+
+	let run = tokio::spawn(runtime.run());
+	let (code, _) = request(
+		UnixStream::connect(&path).await.unwrap(),
+		"POST",
+		"/shutdown",
+	)
+	.await;
+	assert_eq!(code, 202);
+	tokio::time::timeout(Duration::from_secs(5), run)
+		.await
+		.unwrap()
+		.unwrap();
+	assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn admin_shutdown_disabled() {
+	let admin = Admin::with_addr("127.0.0.1:0".parse().unwrap())
+		.await
+		.unwrap();
+	let addr = admin.local_addr().unwrap();
+	let runtime = Runtime::builder()
+		.service::<hello>()
+		.admin(admin)
+		.build()
+		.await
+		.unwrap();
+
+	let (code, _) = request(TcpStream::connect(addr).await.unwrap(), "POST", "/shutdown").await;
+	assert_eq!(code, 404);
+	let (code, _) = request(TcpStream::connect(addr).await.unwrap(), "GET", "/shutdown").await;
+	assert_eq!(code, 404);
+	drop(runtime);
+}
@@ -167,3 +167,130 @@ async fn api_call_internal_error() {
 		}
 	};
 }
+
+#[derive(Default)]
+struct StreamService;
+
+impl tower::Service<Request> for StreamService {
+	type Response = micro_tower::util::BoxStream<Result<Response, BoxError>>;
+	type Error = BoxError;
+	type Future = micro_tower::util::BoxFuture<Result<Self::Response, Self::Error>>;
+
+	fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn call(&mut self, req: Request) -> Self::Future {
+		Box::pin(async move {
+			let m: i32 = req.input.parse()?;
+			let stream: Self::Response =
+				Box::pin(futures::stream::iter((0..m).map(|m| Ok(Response { m }))));
+			Ok(stream)
+		})
+	}
+}
+
+#[tokio::test]
+async fn api_stream_call() {
+	use futures::StreamExt;
+
+	let layer = api::stream::Layer::<Request, api::codec::Json>::default();
+	let mut service = layer.layer(StreamService);
+
+	let mut buf = BytesMut::new();
+	buf.put(&b"{\"input\":\"2\"}"[..]);
+
+	let frames = match service.ready().await.unwrap().call(buf).await {
+		Ok(frames) => frames,
+		Err(err) => {
+			let report = micro_tower::report!(err);
+			panic!("{report:?}")
+		}
+	};
+	let frames = frames
+		.map(|frame| String::from_utf8_lossy(&frame[..]).into_owned())
+		.collect::<Vec<_>>()
+		.await;
+	assert_eq!(
+		frames,
+		vec![
+			r#"{"type":"ok","data":{"m":0}}"#,
+			r#"{"type":"ok","data":{"m":1}}"#,
+			r#"{"type":"end"}"#
+		]
+	);
+}
+
+#[tokio::test]
+async fn api_stream_bad_request() {
+	let layer = api::stream::Layer::<Request, api::codec::Json>::default();
+	let mut service = layer.layer(StreamService);
+
+	let mut buf = BytesMut::new();
+	buf.put(&b"{input:42}"[..]);
+
+	match service.ready().await.unwrap().call(buf).await {
+		Ok(_) => panic!("expected error"),
+		Err(err) => assert_eq!(String::from_utf8_lossy(&err.buf[..]), r#"{"type":"400"}"#),
+	}
+}
+
+#[derive(Default)]
+struct CountService;
+
+impl tower::Service<api::stream::Requests<Request>> for CountService {
+	type Response = micro_tower::util::BoxStream<Result<Response, BoxError>>;
+	type Error = BoxError;
+	type Future = micro_tower::util::BoxFuture<Result<Self::Response, Self::Error>>;
+
+	fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn call(&mut self, requests: api::stream::Requests<Request>) -> Self::Future {
+		use futures::StreamExt;
+
+		Box::pin(async move {
+			let m = requests.count().await;
+			let stream: Self::Response = Box::pin(futures::stream::once(async move {
+				Ok(Response {
+					m: i32::try_from(m)?,
+				})
+			}));
+			Ok(stream)
+		})
+	}
+}
+
+#[tokio::test]
+async fn api_duplex_call() {
+	use futures::StreamExt;
+
+	let layer = api::stream::DuplexLayer::<Request, api::codec::Json>::default();
+	let mut service = layer.layer(CountService);
+
+	let frames = [
+		&br#"{"type":"ok","data":{"input":""}}"#[..],
+		&br#"{"type":"ok","data":{"input":""}}"#[..],
+		&br#"{"type":"end"}"#[..],
+		&br#"{"type":"ok","data":{"input":""}}"#[..],
+	];
+	let frames = futures::stream::iter(frames.map(BytesMut::from));
+	let frames = api::stream::Requests::new(frames);
+
+	let frames = match service.ready().await.unwrap().call(frames).await {
+		Ok(frames) => frames,
+		Err(err) => {
+			let report = micro_tower::report!(err);
+			panic!("{report:?}")
+		}
+	};
+	let frames = frames
+		.map(|frame| String::from_utf8_lossy(&frame[..]).into_owned())
+		.collect::<Vec<_>>()
+		.await;
+	assert_eq!(
+		frames,
+		vec![r#"{"type":"ok","data":{"m":2}}"#, r#"{"type":"end"}"#]
+	);
+}
@@ -0,0 +1,134 @@
+use micro_tower::metrics;
+use micro_tower::prelude::*;
+use micro_tower::runtime::health::Monitor;
+use micro_tower::runtime::Runtime;
+use micro_tower::service::pool::{self, Autoscale, Size};
+use micro_tower::util::BoxError;
+use micro_tower::ServiceBuilder;
+use std::convert::Infallible;
+use std::time::Duration;
+use tower::load::{CompleteOnResponse, PendingRequests};
+
+#[micro_tower::codegen::service(buffer = 4, pool = "1..4")]
+async fn ranged(request: u32) -> u32 {
+	request + 1
+}
+
+const AUTOSCALE: Autoscale = Autoscale {
+	threshold: 1.0,
+	cooldown: Duration::from_millis(50),
+	interval: Duration::from_millis(5),
+};
+
+/// Returns the number of members of pool `name`.
+fn members(monitor: &Monitor, name: &str) -> Option<usize> {
+	monitor
+		.checks()
+		.into_iter()
+		.find(|check| check.name == name)
+		.and_then(|check| check.members)
+}
+
+/// Waits until pool `name` has `size` members.
+async fn wait_for(monitor: &Monitor, name: &str, size: usize) {
+	let wait = async {
+		while members(monitor, name) != Some(size) {
+			tokio::time::sleep(Duration::from_millis(5)).await;
+		}
+	};
+	tokio::time::timeout(Duration::from_secs(5), wait)
+		.await
+		.unwrap();
+}
+
+#[test]
+fn parse_size() {
+	assert_eq!("4".parse::<Size>().unwrap(), Size::fixed(4));
+	assert_eq!("2..16".parse::<Size>().unwrap(), Size { min: 2, max: 16 });
+	assert_eq!("2..=16".parse::<Size>().unwrap(), Size { min: 2, max: 16 });
+	assert_eq!(" 3 .. 3 ".parse::<Size>().unwrap(), Size::fixed(3));
+	assert!("0..4".parse::<Size>().is_err());
+	assert!("8..2".parse::<Size>().is_err());
+	assert!("2..".parse::<Size>().is_err());
+	assert!("many".parse::<Size>().is_err());
+	assert_eq!(Size { min: 2, max: 16 }.to_string(), "2..16");
+	assert_eq!(Size::fixed(4).to_string(), "4");
+}
+
+#[tokio::test]
+async fn scale_with_load() {
+	let monitor = Monitor::default();
+	let mut pool = ServiceBuilder::new()
+		.layer(
+			pool::Layer::with_size(Size { min: 1, max: 4 }, ())
+				.named("autoscaled")
+				.monitored(monitor.clone())
+				.autoscale(AUTOSCALE),
+		)
+		.service_fn(|()| async move {
+			let service = tower::service_fn(|duration: u64| async move {
+				tokio::time::sleep(Duration::from_millis(duration)).await;
+				Ok::<_, BoxError>(duration)
+			});
+			Ok::<_, Infallible>(PendingRequests::new(service, CompleteOnResponse::default()))
+		});
+	pool.ready().await.unwrap();
+	assert_eq!(members(&monitor, "autoscaled"), Some(1));
+
+	let mut calls = Vec::new();
+	for _ in 0..16 {
+		calls.push(tokio::spawn(pool.ready().await.unwrap().call(500)));
+	}
+	wait_for(&monitor, "autoscaled", 4).await;
+	assert!(metrics::global()
+		.encode()
+		.contains("micro_tower_pool_member_load{service=\"autoscaled\",member=\"3\"}"));
+	for call in calls {
+		assert_eq!(call.await.unwrap().unwrap(), 500);
+	}
+	wait_for(&monitor, "autoscaled", 1).await;
+	let text = metrics::global().encode();
+	assert!(text.contains("micro_tower_pool_member_load{service=\"autoscaled\",member=\"0\"}"));
+	assert!(!text.contains("service=\"autoscaled\",member=\"1\""));
+}
+
+#[tokio::test]
+async fn fixed_size() {
+	let monitor = Monitor::default();
+	let mut pool = ServiceBuilder::new()
+		.layer(
+			pool::Layer::with_size(2, ())
+				.named("fixed")
+				.monitored(monitor.clone())
+				.autoscale(AUTOSCALE),
+		)
+		.service_fn(|()| async move {
+			let service = tower::service_fn(|duration: u64| async move {
+				tokio::time::sleep(Duration::from_millis(duration)).await;
+				Ok::<_, BoxError>(duration)
+			});
+			Ok::<_, Infallible>(PendingRequests::new(service, CompleteOnResponse::default()))
+		});
+
+	let mut calls = Vec::new();
+	for _ in 0..8 {
+		calls.push(tokio::spawn(pool.ready().await.unwrap().call(50)));
+	}
+	for call in calls {
+		call.await.unwrap().unwrap();
+	}
+	assert_eq!(members(&monitor, "fixed"), Some(2));
+}
+
+#[tokio::test]
+async fn ranged_service() {
+	let runtime = Runtime::builder()
+		.service::<ranged>()
+		.build()
+		.await
+		.unwrap();
+	let mut service = runtime.services().get::<ranged>().unwrap().unwrap();
+
+	assert_eq!(service.ready().await.unwrap().call(1).await.unwrap(), 2);
+	assert_eq!(members(runtime.monitor(), "ranged"), Some(1));
+}
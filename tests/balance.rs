@@ -0,0 +1,149 @@
+use micro_tower::prelude::*;
+use micro_tower::runtime::Runtime;
+use micro_tower::service::pool::Strategy;
+use micro_tower::util::{BoxError, BoxFuture};
+use micro_tower::ServiceBuilder;
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tower::load::{CompleteOnResponse, PendingRequests};
+
+#[micro_tower::codegen::service(buffer = 4, pool = 3, balance = "round_robin")]
+async fn rotated(request: u32) -> u32 {
+	request + 1
+}
+
+fn user(request: &(u64, u32)) -> u64 {
+	request.0
+}
+
+#[micro_tower::codegen::service(buffer = 4, pool = 3, balance = "hash", hash_key = "user")]
+async fn sharded(request: (u64, u32)) -> u32 {
+	request.1
+}
+
+/// Returns a pool of `size` members balanced by `strategy`. Members respond with their number
+/// after sleeping for the requested number of milliseconds.
+fn pool(
+	size: usize,
+	strategy: Strategy<u64>,
+) -> impl tower::Service<
+	u64,
+	Response = usize,
+	Error = BoxError,
+	Future = BoxFuture<Result<usize, BoxError>>,
+> {
+	let created = Arc::new(AtomicUsize::default());
+	ServiceBuilder::new()
+		.pooled_with(size, (), strategy)
+		.service_fn(move |()| {
+			let number = created.fetch_add(1, Ordering::SeqCst);
+			async move {
+				let service = tower::service_fn(move |duration: u64| async move {
+					tokio::time::sleep(Duration::from_millis(duration)).await;
+					Ok::<_, BoxError>(number)
+				});
+				Ok::<_, Infallible>(PendingRequests::new(service, CompleteOnResponse::default()))
+			}
+		})
+}
+
+#[tokio::test]
+async fn round_robin() {
+	let mut pool = pool(3, Strategy::RoundRobin);
+
+	let mut numbers = Vec::new();
+	for _ in 0..6 {
+		numbers.push(pool.ready().await.unwrap().call(0).await.unwrap());
+	}
+	assert_eq!(numbers, [0, 1, 2, 0, 1, 2]);
+}
+
+#[tokio::test]
+async fn least_pending() {
+	let mut pool = pool(3, Strategy::LeastPending);
+
+	let mut calls = Vec::new();
+	for _ in 0..3 {
+		calls.push(tokio::spawn(pool.ready().await.unwrap().call(50)));
+	}
+	let mut numbers = HashSet::new();
+	for call in calls {
+		numbers.insert(call.await.unwrap().unwrap());
+	}
+	assert_eq!(numbers, HashSet::from([0, 1, 2]));
+}
+
+#[tokio::test]
+async fn consistent_hash() {
+	let mut pool = pool(4, Strategy::hashed_request());
+
+	let mut members = HashMap::new();
+	for _ in 0..3 {
+		for key in 0..16 {
+			let number = pool.ready().await.unwrap().call(key).await.unwrap();
+			assert_eq!(*members.entry(key).or_insert(number), number);
+		}
+	}
+	assert!(members.values().collect::<HashSet<_>>().len() > 1);
+}
+
+#[tokio::test]
+async fn consistent_hash_waits_for_member() {
+	let created = Arc::new(AtomicUsize::default());
+	let mut pool = ServiceBuilder::new()
+		.pooled_with(2, (), Strategy::hashed(|request: &(u64, u64)| request.0))
+		.service_fn(move |()| {
+			let number = created.fetch_add(1, Ordering::SeqCst);
+			async move {
+				let service = tower::service_fn(move |(_, duration): (u64, u64)| async move {
+					tokio::time::sleep(Duration::from_millis(duration)).await;
+					Ok::<_, BoxError>(number)
+				});
+				let service = tower::limit::ConcurrencyLimit::new(service, 1);
+				Ok::<_, Infallible>(PendingRequests::new(service, CompleteOnResponse::default()))
+			}
+		});
+
+	let mut members = HashMap::new();
+	for key in 0..16 {
+		let number = pool.ready().await.unwrap().call((key, 0)).await.unwrap();
+		members.entry(number).or_insert(key);
+	}
+	let (busy, idle) = (members[&0], members[&1]);
+	let pending = tokio::spawn(pool.ready().await.unwrap().call((busy, 500)));
+	tokio::time::sleep(Duration::from_millis(10)).await;
+	let call = async { pool.ready().await.unwrap().call((idle, 0)).await.unwrap() };
+	let number = tokio::time::timeout(Duration::from_millis(200), call)
+		.await
+		.unwrap();
+	assert_eq!(number, 1);
+	assert_eq!(pending.await.unwrap().unwrap(), 0);
+}
+
+#[tokio::test]
+async fn generated_strategies() {
+	let runtime = Runtime::builder()
+		.service::<rotated>()
+		.service::<sharded>()
+		.build()
+		.await
+		.unwrap();
+	let mut rotated = runtime.services().get::<rotated>().unwrap().unwrap();
+	let mut sharded = runtime.services().get::<sharded>().unwrap().unwrap();
+
+	for request in 0..6 {
+		let response = rotated.ready().await.unwrap().call(request).await.unwrap();
+		assert_eq!(response, request + 1);
+		let response = sharded
+			.ready()
+			.await
+			.unwrap()
+			.call((u64::from(request % 2), request))
+			.await
+			.unwrap();
+		assert_eq!(response, request);
+	}
+}
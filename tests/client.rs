@@ -0,0 +1,167 @@
+use micro_tower::api::codec;
+use micro_tower::client::pool;
+use micro_tower::prelude::*;
+use micro_tower::service::{Health, Status};
+use micro_tower::session::{tcp, Session};
+use micro_tower::shutdown::Controller;
+use micro_tower::util::BoxError;
+use micro_tower::ServiceBuilder;
+use std::net::SocketAddr;
+use std::num::ParseIntError;
+use std::time::Duration;
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn parse(input: String) -> Result<i32, ParseIntError> {
+	input.parse()
+}
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn slow(millis: u64) -> u64 {
+	tokio::time::sleep(Duration::from_millis(millis)).await;
+	millis
+}
+
+async fn spawn_session(controller: &Controller) -> SocketAddr {
+	let session = tcp::Session::<codec::Json, String>::with_addr("127.0.0.1:0".parse().unwrap())
+		.await
+		.unwrap();
+	let addr = session.local_addr().unwrap();
+	let builder = ServiceBuilder::new()
+		.service_fn(|_: SocketAddr| async move { Ok::<_, BoxError>(parse::builder().build()) });
+	tokio::spawn(session.run(builder, controller.clone()));
+	addr
+}
+
+#[tokio::test]
+async fn call_client() {
+	let controller = Controller::default();
+	let addr = spawn_session(&controller).await;
+
+	let mut client = parseClient::<codec::Json>::connect(addr).await.unwrap();
+	let rep = client
+		.ready()
+		.await
+		.unwrap()
+		.call("42".into())
+		.await
+		.unwrap();
+	assert_eq!(rep, 42);
+	let rep = client
+		.ready()
+		.await
+		.unwrap()
+		.call("24".into())
+		.await
+		.unwrap();
+	assert_eq!(rep, 24);
+
+	controller.shutdown();
+}
+
+#[tokio::test]
+async fn cancelled_call() {
+	let controller = Controller::default();
+	let session = tcp::Session::<codec::Json, u64>::with_addr("127.0.0.1:0".parse().unwrap())
+		.await
+		.unwrap();
+	let addr = session.local_addr().unwrap();
+	let builder = ServiceBuilder::new()
+		.service_fn(|_: SocketAddr| async move { Ok::<_, BoxError>(slow::builder().build()) });
+	tokio::spawn(session.run(builder, controller.clone()));
+
+	let mut client = slowClient::<codec::Json>::connect(addr).await.unwrap();
+	let call = client.ready().await.unwrap().call(200);
+	assert!(tokio::time::timeout(Duration::from_millis(20), call)
+		.await
+		.is_err());
+	let rep = client.ready().await.unwrap().call(1).await.unwrap();
+	assert_eq!(rep, 1);
+
+	controller.shutdown();
+}
+
+#[tokio::test]
+async fn call_client_error() {
+	let controller = Controller::default();
+	let addr = spawn_session(&controller).await;
+
+	let mut client = parseClient::<codec::Json>::new(addr);
+	let err = client
+		.ready()
+		.await
+		.unwrap()
+		.call("not an int".into())
+		.await
+		.unwrap_err();
+	assert!(matches!(
+		err.downcast_ref::<micro_tower::client::Error>(),
+		Some(micro_tower::client::Error::InternalServerError)
+	));
+
+	controller.shutdown();
+}
+
+#[tokio::test]
+async fn call_pool_ejects_failing_replica() {
+	let controller = Controller::default();
+	let first = spawn_session(&controller).await;
+	let second = spawn_session(&controller).await;
+	let closed = {
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		listener.local_addr().unwrap()
+	};
+
+	let config = pool::Config {
+		max_failures: 1,
+		cooldown: Duration::from_secs(30),
+		..Default::default()
+	};
+	let mut pool = pool::Pool::<parse, codec::Json>::new([first, second, closed], config);
+	assert_eq!(pool.handle().health(), Status::Healthy);
+	let mut failed = 0;
+	for _ in 0..20 {
+		match pool.ready().await.unwrap().call("42".into()).await {
+			Ok(rep) => assert_eq!(rep, 42),
+			Err(_) => failed += 1,
+		}
+	}
+	assert!(failed <= 1, "{failed} calls failed");
+	assert_eq!(pool.handle().replicas().len(), 3);
+	let ejected = pool.handle().ejected();
+	assert!(ejected.iter().all(|addr| *addr == closed));
+	let expected = if ejected.is_empty() {
+		Status::Healthy
+	} else {
+		Status::Degraded
+	};
+	assert_eq!(pool.handle().health(), expected);
+
+	controller.shutdown();
+}
+
+#[tokio::test]
+async fn call_pool_keeps_last_replica() {
+	let closed = {
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		listener.local_addr().unwrap()
+	};
+
+	let config = pool::Config {
+		max_failures: 1,
+		cooldown: Duration::from_secs(30),
+		..Default::default()
+	};
+	let mut pool = pool::Pool::<parse, codec::Json>::new([closed], config);
+	for _ in 0..2 {
+		let result = pool.ready().await.unwrap().call("42".into()).await;
+		assert!(result.is_err());
+	}
+	assert!(pool.handle().ejected().is_empty());
+	assert_eq!(pool.handle().health(), Status::Healthy);
+}
+
+#[tokio::test]
+async fn empty_pool_unhealthy() {
+	let pool = pool::Pool::<parse, codec::Json>::new([], pool::Config::default());
+	assert_eq!(pool.handle().health(), Status::Unhealthy);
+}
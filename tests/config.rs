@@ -0,0 +1,187 @@
+use micro_tower::api::codec;
+use micro_tower::prelude::*;
+use micro_tower::runtime::builder::Error as BuildError;
+use micro_tower::runtime::config::{Config, Error, ServiceConfig};
+use micro_tower::runtime::Runtime;
+use micro_tower::service::pool::Size;
+use micro_tower::service::Create;
+use micro_tower::session::tcp;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn configured(request: u32) -> u32 {
+	request * 2
+}
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn sleepy(request: u64) -> u64 {
+	tokio::time::sleep(Duration::from_millis(request)).await;
+	request
+}
+
+const CONFIG: &str = r#"
+# Overrides per environment
+[services.configured]
+buffer = 64
+pool = 3 # members
+
+[services.sleepy]
+timeout = "50ms"
+
+[sessions.configured]
+addr = "127.0.0.1:0"
+
+[log]
+filter = "info,micro_tower=debug"
+"#;
+
+#[test]
+fn parse_config() {
+	let config: Config = CONFIG.parse().unwrap();
+	let service = config.service("configured");
+	assert_eq!(service.buffer, Some(64));
+	assert_eq!(service.pool, Some(Size::fixed(3)));
+	assert_eq!(service.timeout, None);
+	assert_eq!(
+		config.service("sleepy").timeout,
+		Some(Duration::from_millis(50))
+	);
+	assert_eq!(config.service("unknown"), ServiceConfig::default());
+	assert_eq!(
+		config.session("configured"),
+		Some("127.0.0.1:0".parse().unwrap())
+	);
+	assert_eq!(config.session("sleepy"), None);
+	assert_eq!(config.log_filter(), Some("info,micro_tower=debug"));
+}
+
+#[test]
+fn parse_inline_config() {
+	let config: Config = r#"
+services.Configured = { buffer = 8, pool = "1..=4" }
+sessions = { configured = { addr = '127.0.0.1:0' } }
+
+[log]
+filter = """
+info"""
+"#
+	.parse()
+	.unwrap();
+	let service = config.service("configured");
+	assert_eq!(service.buffer, Some(8));
+	assert_eq!(service.pool, Some(Size { min: 1, max: 4 }));
+	assert_eq!(
+		config.session("configured"),
+		Some("127.0.0.1:0".parse().unwrap())
+	);
+	assert_eq!(config.log_filter(), Some("info"));
+
+	let duplicate = "[services.a]\npool = 2\npool = 3".parse::<Config>();
+	assert!(matches!(duplicate, Err(Error::Syntax { .. })));
+	let redefined = "[services.a]\npool = 2\n[services.a]\nbuffer = 4".parse::<Config>();
+	assert!(matches!(redefined, Err(Error::Syntax { line: 3, .. })));
+	let leading_zeros = "[services.a]\nbuffer = 007".parse::<Config>();
+	assert!(matches!(leading_zeros, Err(Error::Syntax { line: 2, .. })));
+}
+
+#[test]
+fn env_overrides() {
+	let vars = [
+		("MICRO_TOWER_SERVICES__CONFIGURED__POOL", "2..8"),
+		("MICRO_TOWER_SERVICES__SLEEPY__TIMEOUT", "2s"),
+		("MICRO_TOWER_SESSIONS__CONFIGURED__ADDR", "0.0.0.0:4000"),
+		("MICRO_TOWER_OTHER", "ignored"),
+		("OTHER", "ignored"),
+	]
+	.map(|(name, value)| (name.to_string(), value.to_string()));
+	let config = CONFIG.parse::<Config>().unwrap().with_vars(vars).unwrap();
+	assert_eq!(
+		config.service("configured").pool,
+		Some(Size { min: 2, max: 8 })
+	);
+	assert_eq!(config.service("configured").buffer, Some(64));
+	assert_eq!(
+		config.service("sleepy").timeout,
+		Some(Duration::from_secs(2))
+	);
+	assert_eq!(
+		config.session("configured"),
+		Some("0.0.0.0:4000".parse().unwrap())
+	);
+
+	let vars = [("MICRO_TOWER_SERVICES__SLEEPY__POOL", "0")]
+		.map(|(name, value)| (name.to_string(), value.to_string()));
+	let result = Config::default().with_vars(vars);
+	assert!(matches!(result, Err(Error::Value { key, .. }) if key == "services.sleepy.pool"));
+}
+
+#[test]
+fn invalid_config() {
+	let syntax = "[services.a]\npool 4".parse::<Config>();
+	assert!(matches!(syntax, Err(Error::Syntax { line: 2, .. })));
+	let unknown = "[services.a]\nsize = 4".parse::<Config>();
+	assert!(matches!(unknown, Err(Error::Unknown(key)) if key == "services.a.size"));
+	let duration = "services.a.timeout = 5".parse::<Config>();
+	assert!(matches!(duration, Err(Error::Value { .. })));
+	let addr = "[sessions.a]\naddr = \"localhost\"".parse::<Config>();
+	assert!(matches!(addr, Err(Error::Value { .. })));
+	let range = "[services.a]\npool = \"8..2\"".parse::<Config>();
+	assert!(matches!(range, Err(Error::Value { key, .. }) if key == "services.a.pool"));
+	let filter = "[log]\nfilter = \"micro_tower=loud\"".parse::<Config>();
+	assert!(matches!(filter, Err(Error::Value { key, .. }) if key == "log.filter"));
+	let missing = Config::from_file("/nonexistent/micro-tower.toml");
+	assert!(matches!(missing, Err(Error::Read { .. })));
+}
+
+#[tokio::test]
+async fn runtime_config() {
+	let config: Config = CONFIG.parse().unwrap();
+	let runtime = Runtime::builder()
+		.config(config)
+		.service::<sleepy>()
+		.expose::<configured, tcp::Session<codec::Json, _>>()
+		.build()
+		.await
+		.unwrap();
+
+	let endpoint = runtime.graph().sessions[0].endpoint.clone().unwrap();
+	let addr = endpoint.strip_prefix("tcp://").unwrap().parse().unwrap();
+	let mut client = configuredClient::<codec::Json>::connect(addr)
+		.await
+		.unwrap();
+	let rep = client.ready().await.unwrap().call(21).await;
+	assert_eq!(rep.unwrap(), 42);
+
+	// Sessions create a pool per connection.
+	let checks = runtime.monitor().checks();
+	let pool = checks
+		.iter()
+		.find(|check| check.name == "configured" && check.members.is_some())
+		.unwrap();
+	assert_eq!(pool.members, Some(3));
+}
+
+#[tokio::test]
+async fn service_timeout() {
+	let config: Config = CONFIG.parse().unwrap();
+	let mut registry = micro_tower::runtime::registry::Type::default();
+	let (_config, watch) = tokio::sync::watch::channel(config);
+	registry.insert_key(micro_tower::runtime::config::KEY, watch.into());
+	let registry = Arc::new(std::sync::RwLock::new(registry));
+	let mut service = sleepy::with_registry(registry).unwrap().unwrap();
+
+	let rep = service.ready().await.unwrap().call(1).await;
+	assert_eq!(rep.unwrap(), 1);
+	let rep = service.ready().await.unwrap().call(500).await;
+	assert!(rep.is_err());
+}
+
+#[tokio::test]
+async fn unbound_session() {
+	let result = Runtime::builder()
+		.expose::<configured, tcp::Session<codec::Json, _>>()
+		.build()
+		.await;
+	assert!(matches!(result, Err(BuildError::Unbound("configured"))));
+}
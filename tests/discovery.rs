@@ -0,0 +1,133 @@
+use futures::StreamExt;
+use micro_tower::api::codec;
+use micro_tower::client::pool;
+use micro_tower::discovery::file::{Error, Source};
+use micro_tower::discovery::Changes;
+use micro_tower::shutdown::Controller;
+use std::net::SocketAddr;
+use std::num::ParseIntError;
+use std::path::PathBuf;
+use std::time::Duration;
+use tower::discover::Change;
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn parse(input: String) -> Result<i32, ParseIntError> {
+	input.parse()
+}
+
+fn temp_file(name: &str, content: &str) -> PathBuf {
+	let path = std::env::temp_dir().join(format!(
+		"micro-tower-discovery-{}-{name}",
+		std::process::id()
+	));
+	std::fs::write(&path, content).unwrap();
+	path
+}
+
+fn addr(s: &str) -> SocketAddr {
+	s.parse().unwrap()
+}
+
+#[tokio::test]
+async fn load_json() {
+	let path = temp_file(
+		"load.json",
+		r#"{ "parse": ["127.0.0.1:8080", "127.0.0.1:8081"], "double": "127.0.0.1:9000" }"#,
+	);
+	let services = Source::new(&path).load().await.unwrap();
+	assert_eq!(
+		services["parse"],
+		vec![addr("127.0.0.1:8080"), addr("127.0.0.1:8081")]
+	);
+	assert_eq!(services["double"], vec![addr("127.0.0.1:9000")]);
+	std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn load_toml() {
+	let path = temp_file(
+		"load.toml",
+		"# replicas\nparse = [\"127.0.0.1:8080\", \"127.0.0.1:8081\"] # two\n\n\"double\" = \"127.0.0.1:9000\"\n",
+	);
+	let services = Source::new(&path).load().await.unwrap();
+	assert_eq!(
+		services["parse"],
+		vec![addr("127.0.0.1:8080"), addr("127.0.0.1:8081")]
+	);
+	assert_eq!(services["double"], vec![addr("127.0.0.1:9000")]);
+	std::fs::remove_file(path).unwrap();
+
+	let path = temp_file(
+		"multiline.toml",
+		"parse = [\n  \"127.0.0.1:8080\", # first\n  '127.0.0.1:8081',\n]\n",
+	);
+	let services = Source::new(&path).load().await.unwrap();
+	assert_eq!(
+		services["parse"],
+		vec![addr("127.0.0.1:8080"), addr("127.0.0.1:8081")]
+	);
+	std::fs::remove_file(path).unwrap();
+
+	let path = temp_file("invalid.toml", "parse = \"127.0.0.1:8080\"\ndouble\n");
+	let err = Source::new(&path).load().await.unwrap_err();
+	assert!(matches!(err, Error::Toml(err) if err.line_col().map(|(line, _)| line) == Some(1)));
+	std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn watch_reload() {
+	let controller = Controller::default();
+	let path = temp_file("watch.json", r#"{ "parse": "127.0.0.1:8080" }"#);
+	let watcher = Source::new(&path)
+		.with_interval(Duration::from_millis(10))
+		.watch(controller.clone())
+		.await
+		.unwrap();
+
+	let mut changes = watcher.discover::<parse>();
+	let change = next_change(&mut changes).await;
+	assert!(matches!(change, Change::Insert(a, b) if a == addr("127.0.0.1:8080") && b == a));
+
+	// Ensure modification time differs from the initial write.
+	tokio::time::sleep(Duration::from_millis(20)).await;
+	std::fs::write(&path, r#"{ "parse": "127.0.0.1:8081" }"#).unwrap();
+	let change = next_change(&mut changes).await;
+	assert!(matches!(change, Change::Remove(a) if a == addr("127.0.0.1:8080")));
+	let change = next_change(&mut changes).await;
+	assert!(matches!(change, Change::Insert(a, _) if a == addr("127.0.0.1:8081")));
+	assert_eq!(watcher.addrs("parse"), vec![addr("127.0.0.1:8081")]);
+
+	controller.shutdown();
+	std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn pool_follows_discovery() {
+	let controller = Controller::default();
+	let path = temp_file(
+		"pool.json",
+		r#"{ "parse": ["127.0.0.1:8080", "127.0.0.1:8081"] }"#,
+	);
+	let watcher = Source::new(&path).watch(controller.clone()).await.unwrap();
+
+	let pool = pool::Pool::<parse, codec::Json>::new([], pool::Config::default());
+	pool.handle().follow(watcher.discover::<parse>());
+	tokio::time::timeout(Duration::from_secs(5), async {
+		while pool.handle().replicas().len() < 2 {
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.unwrap();
+
+	controller.shutdown();
+	std::fs::remove_file(path).unwrap();
+}
+
+async fn next_change(changes: &mut Changes) -> Change<SocketAddr, SocketAddr> {
+	tokio::time::timeout(Duration::from_secs(5), changes.next())
+		.await
+		.unwrap()
+		.unwrap()
+		.unwrap()
+}
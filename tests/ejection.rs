@@ -0,0 +1,239 @@
+use micro_tower::prelude::*;
+use micro_tower::runtime::Runtime;
+use micro_tower::service::pool::{self, Ejection};
+use micro_tower::util::{BoxError, BoxFuture};
+use micro_tower::ServiceBuilder;
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower::load::{CompleteOnResponse, PendingRequests};
+
+const EJECTION: Ejection = Ejection {
+	max_consecutive_failures: 3,
+	max_error_rate: 0.5,
+	min_requests: 4,
+	interval: Duration::from_millis(10),
+};
+
+#[derive(Debug, thiserror::Error)]
+#[error("request rejected")]
+struct Rejected;
+
+#[micro_tower::codegen::service(buffer = 4, pool = 2)]
+async fn tolerant(request: u32) -> Result<u32, Rejected> {
+	if request == 0 {
+		return Err(Rejected);
+	}
+	Ok(request)
+}
+
+#[micro_tower::codegen::service(buffer = 4, pool = 2, eject = "errors")]
+async fn strict(request: u32) -> Result<u32, Rejected> {
+	if request == 0 {
+		return Err(Rejected);
+	}
+	Ok(request)
+}
+
+/// How the first member of a pool misbehaves.
+#[derive(Clone, Copy)]
+enum Fault {
+	/// Fails every request.
+	Failing,
+	/// Fails every other request.
+	Flaky,
+	/// Fails `poll_ready`.
+	Unready,
+}
+
+/// Member of a pool. Responds with its number unless it is faulty.
+struct Instance {
+	number: usize,
+	fault: Option<Fault>,
+	calls: usize,
+}
+
+impl tower::Service<()> for Instance {
+	type Response = usize;
+	type Error = BoxError;
+	type Future = BoxFuture<Result<usize, BoxError>>;
+
+	fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		match self.fault {
+			Some(Fault::Unready) => Poll::Ready(Err("instance not ready".into())),
+			_ => Poll::Ready(Ok(())),
+		}
+	}
+
+	fn call(&mut self, _req: ()) -> Self::Future {
+		self.calls += 1;
+		let failed = match self.fault {
+			Some(Fault::Failing) => true,
+			Some(Fault::Flaky) => self.calls.is_multiple_of(2),
+			_ => false,
+		};
+		let number = self.number;
+		Box::pin(async move {
+			if failed {
+				Err("instance failed".into())
+			} else {
+				Ok(number)
+			}
+		})
+	}
+}
+
+/// Returns a pool of two members whose first member has `fault` and the number of created
+/// members.
+fn pool(
+	name: &'static str,
+	fault: Fault,
+	ejection: Option<Ejection>,
+) -> (
+	impl tower::Service<(), Response = usize, Error = BoxError>,
+	Arc<AtomicUsize>,
+) {
+	let created = Arc::new(AtomicUsize::default());
+	let counter = Arc::clone(&created);
+	let pool = ServiceBuilder::new()
+		.layer(pool::Layer::with_size(2, ()).named(name).ejection(ejection))
+		.service_fn(move |()| {
+			let number = counter.fetch_add(1, Ordering::SeqCst);
+			async move {
+				let instance = Instance {
+					number,
+					fault: (number == 0).then_some(fault),
+					calls: 0,
+				};
+				Ok::<_, Infallible>(PendingRequests::new(
+					instance,
+					CompleteOnResponse::default(),
+				))
+			}
+		});
+	(pool, created)
+}
+
+/// Calls `pool` until it created `size` members.
+async fn wait_for<S>(pool: &mut S, created: &AtomicUsize, size: usize)
+where
+	S: tower::Service<(), Response = usize, Error = BoxError>,
+{
+	let wait = async {
+		while created.load(Ordering::SeqCst) < size {
+			let _ = pool.ready().await.unwrap().call(()).await;
+			tokio::time::sleep(Duration::from_millis(1)).await;
+		}
+	};
+	tokio::time::timeout(Duration::from_secs(5), wait)
+		.await
+		.unwrap();
+}
+
+fn ejections(name: &str) -> u64 {
+	micro_tower::metrics::global()
+		.counter(
+			"micro_tower_pool_member_ejections_total",
+			"",
+			&[("service", name)],
+		)
+		.get()
+}
+
+#[tokio::test]
+async fn eject_failing_member() {
+	let (mut pool, created) = pool("failing", Fault::Failing, Some(EJECTION));
+
+	wait_for(&mut pool, &created, 3).await;
+	for _ in 0..32 {
+		let number = pool.ready().await.unwrap().call(()).await.unwrap();
+		assert_ne!(number, 0);
+	}
+	assert_eq!(created.load(Ordering::SeqCst), 3);
+	assert_eq!(ejections("failing"), 1);
+}
+
+#[tokio::test]
+async fn eject_by_error_rate() {
+	let ejection = Ejection {
+		max_consecutive_failures: u32::MAX,
+		..EJECTION
+	};
+	let (mut pool, created) = pool("flaky", Fault::Flaky, Some(ejection));
+
+	wait_for(&mut pool, &created, 3).await;
+	for _ in 0..32 {
+		assert!(pool.ready().await.unwrap().call(()).await.is_ok());
+	}
+	assert_eq!(ejections("flaky"), 1);
+}
+
+#[tokio::test]
+async fn replace_unready_member() {
+	let (mut pool, created) = pool("unready", Fault::Unready, Some(EJECTION));
+
+	wait_for(&mut pool, &created, 3).await;
+	let mut numbers = Vec::new();
+	for _ in 0..32 {
+		numbers.push(pool.ready().await.unwrap().call(()).await.unwrap());
+	}
+	assert!(numbers.contains(&1) && numbers.contains(&2));
+	assert_eq!(ejections("unready"), 1);
+}
+
+#[tokio::test]
+async fn ignore_failed_requests() {
+	let (mut pool, created) = pool("ignored", Fault::Failing, Some(Ejection::unready()));
+
+	let mut failures = 0;
+	for _ in 0..32 {
+		if pool.ready().await.unwrap().call(()).await.is_err() {
+			failures += 1;
+		}
+	}
+	assert!(failures > 0);
+	assert_eq!(created.load(Ordering::SeqCst), 2);
+	assert_eq!(ejections("ignored"), 0);
+}
+
+#[tokio::test]
+async fn generated_ejection() {
+	let runtime = Runtime::builder()
+		.service::<tolerant>()
+		.service::<strict>()
+		.build()
+		.await
+		.unwrap();
+	let mut tolerant = runtime.services().get::<tolerant>().unwrap().unwrap();
+	let mut strict = runtime.services().get::<strict>().unwrap().unwrap();
+
+	for _ in 0..32 {
+		assert!(tolerant.ready().await.unwrap().call(0).await.is_err());
+		assert!(strict.ready().await.unwrap().call(0).await.is_err());
+	}
+	let ejected = async {
+		while ejections("strict") == 0 {
+			tokio::time::sleep(Duration::from_millis(1)).await;
+		}
+	};
+	tokio::time::timeout(Duration::from_secs(5), ejected)
+		.await
+		.unwrap();
+	assert_eq!(ejections("tolerant"), 0);
+}
+
+#[tokio::test]
+async fn without_ejection() {
+	let (mut pool, created) = pool("kept", Fault::Failing, None);
+
+	let mut failures = 0;
+	for _ in 0..32 {
+		if pool.ready().await.unwrap().call(()).await.is_err() {
+			failures += 1;
+		}
+	}
+	assert!(failures > 0);
+	assert_eq!(created.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,102 @@
+use bytes::{BufMut, BytesMut};
+use micro_tower::api::{self, codec};
+use micro_tower::metrics;
+use micro_tower::prelude::*;
+use micro_tower::runtime::admin::Admin;
+use micro_tower::runtime::Runtime;
+use micro_tower::service::Create;
+use micro_tower::session::tcp;
+use micro_tower::util::BoxError;
+use micro_tower::ServiceBuilder;
+use std::num::ParseIntError;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn metrics_parse(input: String) -> Result<i32, ParseIntError> {
+	input.parse()
+}
+
+#[micro_tower::codegen::service(buffer = 1, pool = 2)]
+async fn metrics_pooled(input: String) -> Result<i32, ParseIntError> {
+	input.parse()
+}
+
+#[tokio::test]
+async fn service_metrics() {
+	let admin = Admin::with_addr("127.0.0.1:0".parse().unwrap())
+		.await
+		.unwrap();
+	let admin_addr = admin.local_addr().unwrap();
+	let session = tcp::Session::<codec::Json, _>::with_addr("127.0.0.1:0".parse().unwrap())
+		.await
+		.unwrap();
+	let addr = session.local_addr().unwrap();
+	let _runtime = Runtime::builder()
+		.bind_service::<metrics_parse, _>(session)
+		.admin(admin)
+		.build()
+		.await
+		.unwrap();
+
+	let mut client = metrics_parseClient::<codec::Json>::connect(addr)
+		.await
+		.unwrap();
+	let rep = client.ready().await.unwrap().call("42".into()).await;
+	assert_eq!(rep.unwrap(), 42);
+	let rep = client.ready().await.unwrap().call("x".into()).await;
+	assert!(rep.is_err());
+
+	let mut stream = TcpStream::connect(admin_addr).await.unwrap();
+	stream
+		.write_all(b"GET /metrics HTTP/1.1\r\n\r\n")
+		.await
+		.unwrap();
+	let mut response = String::new();
+	stream.read_to_string(&mut response).await.unwrap();
+	assert!(response.starts_with("HTTP/1.1 200 OK\r\nContent-Type: text/plain"));
+	assert!(response.contains("micro_tower_requests_total{service=\"metrics_parse\"} 2\n"));
+	assert!(response
+		.contains("micro_tower_errors_total{service=\"metrics_parse\",message=\"500\"} 1\n"));
+	assert!(response
+		.contains("micro_tower_request_duration_seconds_count{service=\"metrics_parse\"} 2\n"));
+	assert!(response.contains(&format!(
+		"micro_tower_connections_accepted_total{{endpoint=\"{addr}\"}} 1\n"
+	)));
+	assert!(response.contains(&format!(
+		"micro_tower_connections_active{{endpoint=\"{addr}\"}} 1\n"
+	)));
+}
+
+#[tokio::test]
+async fn bad_request_metrics() {
+	let layer = api::Layer::<String, codec::Json>::named("metrics_bad");
+	let mut service = layer.layer(
+		ServiceBuilder::new().service_fn(|input: String| async move { Ok::<_, BoxError>(input) }),
+	);
+	let mut buf = BytesMut::new();
+	buf.put(&b"{input:42}"[..]);
+	assert!(service.ready().await.unwrap().call(buf).await.is_err());
+
+	let text = metrics::global().encode();
+	assert!(text.contains("micro_tower_errors_total{service=\"metrics_bad\",message=\"400\"} 1\n"));
+}
+
+#[tokio::test]
+async fn pool_member_load() {
+	let mut service = metrics_pooled::with_registry(Arc::default())
+		.unwrap()
+		.unwrap();
+	let rep = service.ready().await.unwrap().call("7".into()).await;
+	assert_eq!(rep.unwrap(), 7);
+
+	let text = metrics::global().encode();
+	assert!(
+		text.contains("micro_tower_pool_member_load{service=\"metrics_pooled\",member=\"0\"} 0\n")
+	);
+	assert!(
+		text.contains("micro_tower_pool_member_load{service=\"metrics_pooled\",member=\"1\"} 0\n")
+	);
+	assert!(text.contains("micro_tower_requests_total{service=\"metrics_pooled\"} 1\n"));
+}
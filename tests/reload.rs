@@ -0,0 +1,119 @@
+use micro_tower::runtime::config::{Changes, Config, Error};
+use micro_tower::runtime::health::Monitor;
+use micro_tower::runtime::Runtime;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[micro_tower::codegen::service(buffer = 4, pool = 2)]
+async fn pooled(request: u32) -> u32 {
+	request + 1
+}
+
+const CONFIG: &str = r#"
+[services.pooled]
+buffer = 8
+
+[log]
+filter = "info"
+"#;
+
+const RELOADED: &str = r#"
+[services.pooled]
+buffer = 16
+pool = 4
+
+[log]
+filter = "debug"
+"#;
+
+/// Writes `content` to a new configuration file named after the calling test.
+fn config_file(name: &str, content: &str) -> PathBuf {
+	let path = std::env::temp_dir().join(format!("micro-tower-{}-{name}.toml", std::process::id()));
+	std::fs::write(&path, content).unwrap();
+	path
+}
+
+/// Waits until the pool of `pooled` reports `members` members.
+async fn wait_for_members(monitor: &Monitor, members: usize) {
+	let wait = async {
+		loop {
+			let pool = monitor
+				.checks()
+				.into_iter()
+				.find(|check| check.name == "pooled")
+				.and_then(|check| check.members);
+			if pool == Some(members) {
+				return;
+			}
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+	};
+	tokio::time::timeout(Duration::from_secs(5), wait)
+		.await
+		.unwrap();
+}
+
+#[test]
+fn config_diff() {
+	let old: Config = CONFIG.parse().unwrap();
+	let new: Config = RELOADED.parse().unwrap();
+	assert_eq!(old.diff(&old), Changes::default());
+	let changes = old.diff(&new);
+	assert_eq!(changes.live, ["services.pooled.pool", "log.filter"]);
+	assert_eq!(changes.restart, ["services.pooled.buffer"]);
+
+	let session: Config = "sessions.pooled.addr = \"127.0.0.1:4000\"".parse().unwrap();
+	assert_eq!(
+		Config::default().diff(&session).restart,
+		["sessions.pooled.addr"]
+	);
+}
+
+#[tokio::test]
+async fn reload_file() {
+	let path = config_file("reload_file", CONFIG);
+	let runtime = Runtime::builder()
+		.config_file(&path)
+		.service::<pooled>()
+		.build()
+		.await
+		.unwrap();
+	wait_for_members(runtime.monitor(), 2).await;
+
+	std::fs::write(&path, RELOADED).unwrap();
+	let changes = runtime.reload().unwrap();
+	assert_eq!(changes.live, ["services.pooled.pool", "log.filter"]);
+	assert_eq!(changes.restart, ["services.pooled.buffer"]);
+	wait_for_members(runtime.monitor(), 4).await;
+
+	std::fs::write(&path, CONFIG).unwrap();
+	runtime.reload().unwrap();
+	wait_for_members(runtime.monitor(), 2).await;
+
+	std::fs::write(&path, "[services.pooled]\npool = 0").unwrap();
+	let result = runtime.reload();
+	assert!(matches!(result, Err(Error::Value { .. })));
+	assert!(runtime
+		.errors()
+		.recent()
+		.iter()
+		.any(|entry| entry.source == "config"));
+	wait_for_members(runtime.monitor(), 2).await;
+	std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn reload_without_file() {
+	let runtime = Runtime::builder()
+		.config(CONFIG.parse().unwrap())
+		.build()
+		.await
+		.unwrap();
+	assert_eq!(runtime.reload().unwrap(), Changes::default());
+
+	let result = Runtime::builder()
+		.config_file("/nonexistent/micro-tower.toml")
+		.build()
+		.await;
+	assert!(result.is_err());
+}
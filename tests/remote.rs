@@ -0,0 +1,57 @@
+// `Ok(result?)` in the generated `call` is needless for services failing with `BoxError`
+#![allow(clippy::needless_question_mark)]
+
+use micro_tower::api::codec;
+use micro_tower::prelude::*;
+use micro_tower::runtime::Runtime;
+use micro_tower::service::Service;
+use micro_tower::session::tcp;
+use micro_tower::util::BoxError;
+use std::num::ParseIntError;
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn parse(input: String) -> Result<i32, ParseIntError> {
+	input.parse()
+}
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn double(input: String, mut parse: Service<parse>) -> Result<i32, BoxError> {
+	let n: i32 = parse.ready().await?.call(input).await?;
+	Ok(n * 2)
+}
+
+#[tokio::test]
+async fn call_remote_inner_service() {
+	let session = tcp::Session::<codec::Json, _>::with_addr("127.0.0.1:0".parse().unwrap())
+		.await
+		.unwrap();
+	let parse_addr = session.local_addr().unwrap();
+	let _parse_rt = Runtime::builder()
+		.bind_service::<parse, _>(session)
+		.build()
+		.await
+		.unwrap();
+
+	let session = tcp::Session::<codec::Json, _>::with_addr("127.0.0.1:0".parse().unwrap())
+		.await
+		.unwrap();
+	let double_addr = session.local_addr().unwrap();
+	let _double_rt = Runtime::builder()
+		.remote::<parse, codec::Json>(parse_addr)
+		.bind_service::<double, _>(session)
+		.build()
+		.await
+		.unwrap();
+
+	let mut client = doubleClient::<codec::Json>::connect(double_addr)
+		.await
+		.unwrap();
+	let rep = client
+		.ready()
+		.await
+		.unwrap()
+		.call("21".into())
+		.await
+		.unwrap();
+	assert_eq!(rep, 42);
+}
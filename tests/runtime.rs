@@ -0,0 +1,396 @@
+// `Ok(result?)` in the generated `call` is needless for services failing with `BoxError`
+#![allow(clippy::needless_question_mark)]
+
+use micro_tower::api::codec;
+use micro_tower::prelude::*;
+use micro_tower::runtime::builder::{Error, ServiceError};
+use micro_tower::runtime::graph::Kind;
+use micro_tower::runtime::health;
+use micro_tower::runtime::Runtime;
+use micro_tower::service::{Managed, Service, Status};
+use micro_tower::session::tcp;
+use micro_tower::util::BoxError;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn hello(_request: ()) -> &'static str {
+	"Hello, World!"
+}
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn greet(_request: (), mut hello: Service<hello>) -> Result<&'static str, BoxError> {
+	hello.ready().await?.call(()).await
+}
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn welcome(_request: (), mut inner: Service<hello>) -> Result<&'static str, BoxError> {
+	inner.ready().await?.call(()).await
+}
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn ping(_request: (), mut pong: Service<pong>) -> Result<&'static str, BoxError> {
+	pong.ready().await?.call(()).await
+}
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn pong(_request: (), mut ping: Service<ping>) -> Result<&'static str, BoxError> {
+	ping.ready().await?.call(()).await
+}
+
+#[micro_tower::codegen::service(buffer = 1, pool = 2)]
+async fn counter(_request: (), limit: Managed<u32>) -> u32 {
+	*limit
+}
+
+/// Lifecycle events of `first` and `second`.
+static EVENTS: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+
+macro_rules! hook {
+	($name:ident, $event:literal) => {
+		async fn $name(
+			_registry: Arc<RwLock<micro_tower::runtime::registry::Type>>,
+		) -> Result<(), Infallible> {
+			EVENTS.lock().unwrap().push($event);
+			Ok(())
+		}
+	};
+}
+
+hook!(start_first, "start first");
+hook!(stop_first, "stop first");
+hook!(start_second, "start second");
+hook!(stop_second, "stop second");
+
+#[micro_tower::codegen::service(buffer = 1, on_start = "start_first", on_stop = "stop_first")]
+async fn first(_request: ()) {}
+
+#[micro_tower::codegen::service(buffer = 1, on_start = "start_second", on_stop = "stop_second")]
+async fn second(_request: (), mut inner: Service<first>) -> Result<(), BoxError> {
+	inner.ready().await?.call(()).await
+}
+
+/// Service which never becomes ready.
+struct Never;
+
+impl micro_tower::service::Info for Never {
+	type Request = ();
+	type Response = ();
+
+	fn name() -> &'static str {
+		"never"
+	}
+}
+
+impl micro_tower::service::Create for Never {
+	type Error = Infallible;
+
+	fn with_registry(
+		_registry: Arc<RwLock<micro_tower::runtime::registry::Type>>,
+	) -> Result<Option<Service<Self>>, Self::Error> {
+		Ok(None)
+	}
+}
+
+/// Service which always fails to be created.
+struct Failing;
+
+impl micro_tower::service::Info for Failing {
+	type Request = ();
+	type Response = ();
+
+	fn name() -> &'static str {
+		"failing"
+	}
+}
+
+impl micro_tower::service::Create for Failing {
+	type Error = std::io::Error;
+
+	fn with_registry(
+		_registry: Arc<RwLock<micro_tower::runtime::registry::Type>>,
+	) -> Result<Option<Service<Self>>, Self::Error> {
+		Err(std::io::Error::other("failed"))
+	}
+}
+
+/// Service which waits for `failing`.
+struct Dependent;
+
+impl micro_tower::service::Info for Dependent {
+	type Request = ();
+	type Response = ();
+
+	fn name() -> &'static str {
+		"dependent"
+	}
+
+	fn dependencies() -> Vec<&'static str> {
+		vec!["failing"]
+	}
+}
+
+impl micro_tower::service::Create for Dependent {
+	type Error = Infallible;
+
+	fn with_registry(
+		_registry: Arc<RwLock<micro_tower::runtime::registry::Type>>,
+	) -> Result<Option<Service<Self>>, Self::Error> {
+		Ok(None)
+	}
+}
+
+#[tokio::test]
+async fn build_with_dependencies() {
+	let result = Runtime::builder()
+		.service::<greet>()
+		.service::<hello>()
+		.build()
+		.await;
+	assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn build_with_renamed_dependency() {
+	let runtime = Runtime::builder()
+		.service::<welcome>()
+		.service::<hello>()
+		.build()
+		.await
+		.unwrap();
+	assert_eq!(
+		runtime.graph().node("welcome").unwrap().dependencies,
+		vec!["hello"]
+	);
+}
+
+#[tokio::test]
+async fn build_missing_dependency() {
+	let result = Runtime::builder().service::<greet>().build().await;
+	assert!(matches!(
+		result,
+		Err(Error::Missing {
+			service: "greet",
+			dependency: "hello"
+		})
+	));
+}
+
+#[tokio::test]
+async fn build_dependency_cycle() {
+	let result = Runtime::builder()
+		.service::<ping>()
+		.service::<pong>()
+		.build()
+		.await;
+	match result {
+		Err(Error::Cycle(cycle)) => assert_eq!(cycle, vec!["ping", "pong", "ping"]),
+		_ => panic!("expected dependency cycle"),
+	}
+}
+
+#[tokio::test]
+async fn build_timeout() {
+	let result = Runtime::builder()
+		.startup_timeout(Duration::from_millis(50))
+		.service::<Never>()
+		.build()
+		.await;
+	let Err(Error::Services(report)) = result else {
+		panic!("expected service report");
+	};
+	assert!(report.created.is_empty());
+	assert!(matches!(
+		report.failed.as_slice(),
+		[("never", ServiceError::Timeout(_))]
+	));
+}
+
+#[tokio::test]
+async fn build_report() {
+	let result = Runtime::builder()
+		.service::<hello>()
+		.service::<Failing>()
+		.build()
+		.await;
+	let Err(Error::Services(report)) = result else {
+		panic!("expected service report");
+	};
+	assert_eq!(report.created, vec!["hello"]);
+	assert!(matches!(
+		report.failed.as_slice(),
+		[("failing", ServiceError::Create(_))]
+	));
+}
+
+#[tokio::test]
+async fn build_failed_dependency() {
+	let build = Runtime::builder()
+		.service::<Dependent>()
+		.service::<Failing>()
+		.build();
+	let result = tokio::time::timeout(Duration::from_secs(5), build)
+		.await
+		.unwrap();
+	let Err(Error::Services(report)) = result else {
+		panic!("expected service report");
+	};
+	assert!(matches!(
+		report.failed.as_slice(),
+		[
+			("failing", ServiceError::Create(_)),
+			("dependent", ServiceError::Dependency("failing"))
+		]
+	));
+}
+
+#[tokio::test]
+async fn build_lifecycle() {
+	let result = Runtime::builder()
+		.service::<second>()
+		.service::<first>()
+		.service::<Failing>()
+		.build()
+		.await;
+	assert!(matches!(result, Err(Error::Services(_))));
+	assert_eq!(
+		*EVENTS.lock().unwrap(),
+		vec!["start first", "start second", "stop second", "stop first"]
+	);
+}
+
+#[tokio::test]
+async fn build_duplicate() {
+	let result = Runtime::builder()
+		.manage("answer", 42_i32)
+		.manage("answer", 24_i32)
+		.build()
+		.await;
+	assert!(matches!(result, Err(Error::Duplicate(ident)) if ident == "answer"));
+
+	let result = Runtime::builder()
+		.manage("hello", 42_i32)
+		.service::<hello>()
+		.build()
+		.await;
+	assert!(matches!(result, Err(Error::Duplicate(ident)) if ident == "hello"));
+
+	let result = Runtime::builder()
+		.manage("answer", 42_i32)
+		.manage("answer", 24_i32)
+		.manage("hello", 42_i32)
+		.service::<hello>()
+		.manage("hello", 24_i32)
+		.build()
+		.await;
+	let Err(Error::Multiple(errors)) = result else {
+		panic!("expected multiple errors");
+	};
+	assert!(matches!(
+		errors.as_slice(),
+		[Error::Duplicate(answer), Error::Duplicate(hello)] if answer == "answer" && hello == "hello"
+	));
+}
+
+#[tokio::test]
+async fn managed_objects() {
+	let runtime = Runtime::builder()
+		.manage("limit", 5_u32)
+		.manage("other", 9_u32)
+		.service::<counter>()
+		.build()
+		.await
+		.unwrap();
+	let mut service = runtime.services().get::<counter>().unwrap().unwrap();
+	assert_eq!(service.ready().await.unwrap().call(()).await.unwrap(), 5);
+
+	let runtime = Runtime::builder()
+		.manage("max", 7_u32)
+		.service::<counter>()
+		.build()
+		.await
+		.unwrap();
+	let mut service = runtime.services().get::<counter>().unwrap().unwrap();
+	assert_eq!(service.ready().await.unwrap().call(()).await.unwrap(), 7);
+}
+
+#[tokio::test]
+async fn graph_export() {
+	let session = tcp::Session::<codec::Json, _>::with_addr("127.0.0.1:0".parse().unwrap())
+		.await
+		.unwrap();
+	let endpoint = format!("tcp://{}", session.local_addr().unwrap());
+	let builder = Runtime::builder()
+		.service::<hello>()
+		.manage("answer", 42_i32)
+		.bind_service::<greet, _>(session);
+
+	let graph = builder.graph();
+	assert_eq!(graph.node("hello").unwrap().kind, Kind::Service);
+	assert_eq!(graph.node("greet").unwrap().dependencies, vec!["hello"]);
+	assert_eq!(graph.node("answer").unwrap().kind, Kind::Object);
+	assert_eq!(graph.sessions[0].service, "greet");
+	assert_eq!(
+		graph.sessions[0].endpoint.as_deref(),
+		Some(endpoint.as_str())
+	);
+
+	let dot = graph.to_dot();
+	assert!(dot.contains("\"greet\" -> \"hello\";"));
+	assert!(dot.contains("session0 -> \"greet\";"));
+	let json: serde_json::Value = serde_json::from_str(&graph.to_json().unwrap()).unwrap();
+	assert_eq!(json["nodes"][0]["name"], "hello");
+	assert_eq!(json["nodes"][0]["kind"], "service");
+
+	let expected = graph.clone();
+	let runtime = builder.build().await.unwrap();
+	assert_eq!(runtime.graph(), &expected);
+}
+
+#[tokio::test]
+async fn health_ready() {
+	let session = tcp::Session::<codec::Json, _>::with_addr("127.0.0.1:0".parse().unwrap())
+		.await
+		.unwrap();
+	let builder = Runtime::builder()
+		.service::<hello>()
+		.bind_service::<greet, _>(session);
+	let monitor = builder.monitor();
+	assert!(!monitor.is_ready());
+
+	let _runtime = builder.build().await.unwrap();
+	wait_for(|| monitor.is_ready()).await;
+	assert!(monitor.is_live());
+	assert_eq!(monitor.status(), Status::Healthy);
+	let checks = monitor.checks();
+	assert!(checks.contains(&health::Check {
+		name: "greet".into(),
+		kind: health::Kind::Session,
+		status: Status::Healthy,
+		members: None,
+	}));
+}
+
+#[tokio::test]
+async fn health_failed_pool() {
+	let runtime = Runtime::builder()
+		.service::<counter>()
+		.build()
+		.await
+		.unwrap();
+	let monitor = runtime.monitor().clone();
+	wait_for(|| !monitor.is_live()).await;
+	assert!(!monitor.is_ready());
+	assert_eq!(monitor.status(), Status::Unhealthy);
+}
+
+async fn wait_for(condition: impl Fn() -> bool) {
+	tokio::time::timeout(Duration::from_secs(5), async {
+		while !condition() {
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+	})
+	.await
+	.unwrap();
+}
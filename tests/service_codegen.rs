@@ -1,7 +1,9 @@
 use micro_tower::prelude::*;
-use micro_tower::service::Service;
+use micro_tower::runtime::registry;
+use micro_tower::service::{Create, Managed, Service};
 use micro_tower::util::BoxError;
 use micro_tower::ServiceBuilder;
+use std::sync::{Arc, RwLock};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -112,3 +114,121 @@ async fn call_add_service() {
 	let rep = service.ready().await.unwrap().call(()).await.unwrap();
 	assert_eq!(rep, 24);
 }
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn count_service(request: usize) -> impl futures::Stream<Item = usize> {
+	futures::stream::iter(0..request)
+}
+
+#[tokio::test]
+async fn call_count_service() {
+	use futures::StreamExt;
+
+	let mut service = count_service::builder().build();
+	let stream = service.ready().await.unwrap().call(3).await.unwrap();
+	let items = stream.map(Result::unwrap).collect::<Vec<_>>().await;
+	assert_eq!(items, vec![0, 1, 2]);
+}
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn parse_all_service(
+	request: Vec<String>,
+) -> impl futures::Stream<Item = Result<i32, std::num::ParseIntError>> {
+	futures::stream::iter(request.into_iter().map(|s| s.parse()))
+}
+
+#[tokio::test]
+async fn call_parse_all_service() {
+	use futures::StreamExt;
+
+	let mut service = parse_all_service::builder().build();
+	let request = vec!["1".to_string(), "a".to_string()];
+	let mut stream = service.ready().await.unwrap().call(request).await.unwrap();
+	assert_eq!(stream.next().await.unwrap().unwrap(), 1);
+	assert!(stream.next().await.unwrap().is_err());
+	assert!(stream.next().await.is_none());
+}
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn sum_service(requests: impl futures::Stream<Item = usize>) -> usize {
+	use futures::StreamExt;
+
+	requests.fold(0, |sum, n| async move { sum + n }).await
+}
+
+#[tokio::test]
+async fn call_sum_service() {
+	use futures::StreamExt;
+	use micro_tower::api::stream::Requests;
+
+	let mut service = sum_service::builder().build();
+	let requests = Requests::new(futures::stream::iter(1..4));
+	let stream = service.ready().await.unwrap().call(requests).await.unwrap();
+	let items = stream.map(Result::unwrap).collect::<Vec<_>>().await;
+	assert_eq!(items, vec![6]);
+}
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn double_service(
+	requests: impl futures::Stream<Item = usize>,
+) -> impl futures::Stream<Item = usize> {
+	use futures::StreamExt;
+
+	requests.map(|n| n * 2)
+}
+
+#[tokio::test]
+async fn call_double_service() {
+	use futures::StreamExt;
+	use micro_tower::api::stream::Requests;
+
+	let mut service = double_service::builder().build();
+	let requests = Requests::new(futures::stream::iter(1..4));
+	let stream = service.ready().await.unwrap().call(requests).await.unwrap();
+	let items = stream.map(Result::unwrap).collect::<Vec<_>>().await;
+	assert_eq!(items, vec![2, 4, 6]);
+}
+
+struct Greeting(&'static str);
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn managed_service(_: (), greeting: Managed<Greeting>) -> &'static str {
+	greeting.0
+}
+
+#[tokio::test]
+async fn call_managed_service() {
+	let mut service = managed_service::builder()
+		.greeting(Managed::new(Greeting("Hello Managed")))
+		.build();
+
+	let response = service.ready().await.unwrap().call(()).await.unwrap();
+	assert_eq!(response, "Hello Managed");
+}
+
+#[tokio::test]
+async fn create_managed_service() {
+	// Resolve by argument name.
+	let registry = Arc::new(RwLock::new(registry::Type::default()));
+	registry
+		.write()
+		.unwrap()
+		.insert("greeting", Box::new(Managed::new(Greeting("by name"))));
+	let mut service = managed_service::with_registry(registry).unwrap().unwrap();
+	let response = service.ready().await.unwrap().call(()).await.unwrap();
+	assert_eq!(response, "by name");
+
+	// Resolve by type.
+	let registry = Arc::new(RwLock::new(registry::Type::default()));
+	registry
+		.write()
+		.unwrap()
+		.insert("other", Box::new(Managed::new(Greeting("by type"))));
+	let mut service = managed_service::with_registry(registry).unwrap().unwrap();
+	let response = service.ready().await.unwrap().call(()).await.unwrap();
+	assert_eq!(response, "by type");
+
+	let registry = Arc::new(RwLock::new(registry::Type::default()));
+	let result = managed_service::with_registry(registry);
+	assert!(matches!(result, Err(registry::Error::Missing(name)) if name == "greeting"));
+}
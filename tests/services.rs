@@ -0,0 +1,136 @@
+// `Ok(result?)` in the generated `call` is needless for services failing with `BoxError`
+#![allow(clippy::needless_question_mark)]
+
+use micro_tower::prelude::*;
+use micro_tower::runtime::services::Error;
+use micro_tower::runtime::Runtime;
+use micro_tower::service::{NotReady, Service};
+use micro_tower::util::{BoxError, BoxFuture};
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn hello(_request: ()) -> &'static str {
+	"Hello, World!"
+}
+
+#[micro_tower::codegen::service(buffer = 4)]
+async fn greet(_request: (), mut hello: Service<hello>) -> Result<&'static str, BoxError> {
+	hello.ready().await?.call(()).await
+}
+
+static STOPPED: AtomicBool = AtomicBool::new(false);
+
+async fn stop_tracked(
+	_registry: Arc<RwLock<micro_tower::runtime::registry::Type>>,
+) -> Result<(), Infallible> {
+	STOPPED.store(true, Ordering::SeqCst);
+	Ok(())
+}
+
+#[micro_tower::codegen::service(buffer = 1, on_stop = "stop_tracked")]
+async fn tracked(_request: ()) {}
+
+/// Replies with a fixed message after a delay.
+#[derive(Clone)]
+struct Reply(&'static str, Duration);
+
+impl tower::Service<()> for Reply {
+	type Response = &'static str;
+	type Error = BoxError;
+	type Future = BoxFuture<Result<&'static str, BoxError>>;
+
+	fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn call(&mut self, _req: ()) -> Self::Future {
+		let Self(message, delay) = *self;
+		Box::pin(async move {
+			tokio::time::sleep(delay).await;
+			Ok(message)
+		})
+	}
+}
+
+fn reply(message: &'static str, delay: Duration) -> Service<hello> {
+	Service::from(Box::new(Reply(message, delay)))
+}
+
+async fn call(service: &mut Service<greet>) -> Result<&'static str, BoxError> {
+	service.ready().await?.call(()).await
+}
+
+#[tokio::test]
+async fn replace_service() {
+	let runtime = Runtime::builder()
+		.service::<hello>()
+		.service::<greet>()
+		.build()
+		.await
+		.unwrap();
+	let services = runtime.services();
+	let mut greet = services.get::<greet>().unwrap().unwrap();
+	assert_eq!(call(&mut greet).await.unwrap(), "Hello, World!");
+
+	// Handles cloned before a replacement route new requests to the new implementation.
+	let mut hello = services.get::<hello>().unwrap().unwrap();
+	services
+		.replace::<hello>(reply("slow", Duration::from_millis(100)))
+		.unwrap();
+	let in_flight = hello.ready().await.unwrap().call(());
+	let in_flight = tokio::spawn(in_flight);
+	tokio::time::sleep(Duration::from_millis(20)).await;
+
+	services
+		.replace::<hello>(reply("fast", Duration::ZERO))
+		.unwrap();
+	assert_eq!(hello.ready().await.unwrap().call(()).await.unwrap(), "fast");
+	assert_eq!(call(&mut greet).await.unwrap(), "fast");
+	assert_eq!(in_flight.await.unwrap().unwrap(), "slow");
+
+	let result = services.replace::<greet>(services.get::<greet>().unwrap().unwrap());
+	assert!(result.is_ok());
+	let result = services.get::<tracked>();
+	assert!(matches!(result, Ok(None)));
+}
+
+#[tokio::test]
+async fn register_and_remove() {
+	let runtime = Runtime::builder().service::<hello>().build().await.unwrap();
+	let services = runtime.services();
+
+	services.register::<greet>().await.unwrap();
+	let result = services.register::<greet>().await;
+	assert!(matches!(result, Err(Error::Duplicate("greet"))));
+	let mut greet = services.get::<greet>().unwrap().unwrap();
+	assert_eq!(call(&mut greet).await.unwrap(), "Hello, World!");
+
+	services.remove::<hello>().await.unwrap();
+	assert!(!services.contains("hello").unwrap());
+	let err = call(&mut greet).await.unwrap_err();
+	assert!(err.downcast_ref::<NotReady>().is_some());
+	let result = services.remove::<hello>().await;
+	assert!(matches!(result, Err(Error::Missing("hello"))));
+
+	services.remove::<greet>().await.unwrap();
+	let result = services.register::<greet>().await;
+	assert!(matches!(result, Err(Error::Dependencies("greet"))));
+}
+
+#[tokio::test]
+async fn remove_stops_service() {
+	let runtime = Runtime::builder()
+		.service::<tracked>()
+		.build()
+		.await
+		.unwrap();
+	let services = runtime.services();
+	assert!(!STOPPED.load(Ordering::SeqCst));
+	services.remove::<tracked>().await.unwrap();
+	assert!(STOPPED.load(Ordering::SeqCst));
+	assert!(services.get::<tracked>().unwrap().is_none());
+}
@@ -0,0 +1,90 @@
+//! Signals are delivered to the whole process, so signal handling is tested in its own test binary
+//! and all at once.
+
+use micro_tower::runtime::health::Monitor;
+use micro_tower::runtime::Runtime;
+use micro_tower::shutdown::Controller;
+use std::time::Duration;
+
+#[micro_tower::codegen::service(buffer = 4, pool = 2)]
+async fn pooled(request: u32) -> u32 {
+	request + 1
+}
+
+const CONFIG: &str = r"
+[services.pooled]
+buffer = 8
+";
+
+const RELOADED: &str = r"
+[services.pooled]
+buffer = 16
+pool = 4
+";
+
+/// Waits until the pool of `pooled` reports `members` members.
+async fn wait_for_members(monitor: &Monitor, members: usize) {
+	let wait = async {
+		loop {
+			let pool = monitor
+				.checks()
+				.into_iter()
+				.find(|check| check.name == "pooled")
+				.and_then(|check| check.members);
+			if pool == Some(members) {
+				return;
+			}
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+	};
+	tokio::time::timeout(Duration::from_secs(5), wait)
+		.await
+		.unwrap();
+}
+
+/// Sends `signal` to the test process.
+fn raise(signal: libc::c_int) {
+	// SAFETY: `raise` has no preconditions and signals are handled by the spawned handler.
+	assert_eq!(unsafe { libc::raise(signal) }, 0);
+}
+
+#[tokio::test]
+async fn sighup() {
+	let controller = Controller::new();
+	let mut reloads = controller.subscribe_reload();
+	let handler = controller.clone().spawn_handler().unwrap();
+
+	let path = std::env::temp_dir().join(format!("micro-tower-{}-sighup.toml", std::process::id()));
+	std::fs::write(&path, CONFIG).unwrap();
+	let runtime = Runtime::builder()
+		.config_file(&path)
+		.service::<pooled>()
+		.build()
+		.await
+		.unwrap();
+	let monitor = runtime.monitor().clone();
+	let run = tokio::spawn(runtime.run());
+	wait_for_members(&monitor, 2).await;
+
+	std::fs::write(&path, RELOADED).unwrap();
+	raise(libc::SIGHUP);
+	tokio::time::timeout(Duration::from_secs(5), reloads.changed())
+		.await
+		.unwrap()
+		.unwrap();
+	wait_for_members(&monitor, 4).await;
+	assert!(!handler.is_finished());
+	assert!(!run.is_finished());
+
+	controller.reload();
+	assert_eq!(*reloads.borrow_and_update(), 2);
+
+	raise(libc::SIGTERM);
+	tokio::time::timeout(Duration::from_secs(5), run)
+		.await
+		.unwrap()
+		.unwrap();
+	controller.shutdown();
+	handler.await.unwrap();
+	std::fs::remove_file(path).unwrap();
+}
@@ -15,7 +15,7 @@ use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 struct Stream {
 	input: String,
 	ipos: usize,
-	output: BytesMut,
+	output: std::sync::Arc<std::sync::Mutex<BytesMut>>,
 }
 
 impl Stream {
@@ -23,7 +23,7 @@ impl Stream {
 		Self {
 			input: input.into(),
 			ipos: 0,
-			output: BytesMut::new(),
+			output: std::sync::Arc::default(),
 		}
 	}
 }
@@ -49,11 +49,11 @@ impl AsyncRead for Stream {
 }
 impl AsyncWrite for Stream {
 	fn poll_write(
-		mut self: Pin<&mut Self>,
+		self: Pin<&mut Self>,
 		_cx: &mut Context<'_>,
 		buf: &[u8],
 	) -> Poll<Result<usize, std::io::Error>> {
-		self.output.put_slice(buf);
+		self.output.lock().unwrap().put_slice(buf);
 		Poll::Ready(Ok(buf.len()))
 	}
 
@@ -80,7 +80,7 @@ async fn parse_stream_test() {
 	let service = ServiceBuilder::new()
 		.api::<String, codec::Json>()
 		.service(service);
-	let stream = Stream::from_input(r#""42""#);
+	let stream = Stream::from_input("\"42\"\n");
 	let controller = Controller::default();
 
 	if let Err(err) = micro_tower::session::stream::spawn_fut(stream, service, controller).await {
@@ -100,7 +100,7 @@ async fn parse_stream_err_test() {
 	let service = ServiceBuilder::new()
 		.api::<String, codec::Json>()
 		.service(service);
-	let stream = Stream::from_input(r#""test""#);
+	let stream = Stream::from_input("\"test\"\n");
 	let controller = Controller::default();
 
 	if let Err(err) = micro_tower::session::stream::spawn_fut(stream, service, controller).await {
@@ -120,7 +120,7 @@ async fn parse_stream_bad_request() {
 	let service = ServiceBuilder::new()
 		.api::<String, codec::Json>()
 		.service(service);
-	let stream = Stream::from_input(r#"test"#);
+	let stream = Stream::from_input("test\n");
 	let controller = Controller::default();
 
 	if let Err(err) = micro_tower::session::stream::spawn_fut(stream, service, controller).await {
@@ -133,3 +133,128 @@ async fn parse_stream_bad_request() {
 		}
 	}
 }
+
+#[tokio::test]
+async fn parse_stream_pipelined() {
+	let service = parse::builder().build();
+	let service = ServiceBuilder::new()
+		.api::<String, codec::Json>()
+		.service(service);
+	let input =
+		"\"1\"\ntraceparent: 00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01\n\"2\"\n";
+	let stream = Stream::from_input(input);
+	let output = stream.output.clone();
+	let controller = Controller::default();
+
+	if let Err(err) = micro_tower::session::stream::spawn_fut(stream, service, controller).await {
+		if let Some(err) = err.downcast_ref::<std::io::Error>() {
+			assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+		} else {
+			let report = micro_tower::report!(err.as_ref());
+			panic!("{report:?}")
+		}
+	}
+	let output = output.lock().unwrap();
+	assert_eq!(
+		String::from_utf8_lossy(&output[..]),
+		"{\"type\":\"ok\",\"data\":1}\n{\"type\":\"ok\",\"data\":2}\n"
+	);
+}
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn count(input: usize) -> impl futures::Stream<Item = usize> {
+	futures::stream::iter(0..input)
+}
+
+#[tokio::test]
+async fn count_stream_test() {
+	let service = count::builder().build();
+	let service = ServiceBuilder::new()
+		.api_stream::<usize, codec::Json>()
+		.service(service);
+	let stream = Stream::from_input("2\n");
+	let output = stream.output.clone();
+	let controller = Controller::default();
+
+	if let Err(err) =
+		micro_tower::session::stream::spawn_stream_fut(stream, service, controller).await
+	{
+		if let Some(err) = err.downcast_ref::<std::io::Error>() {
+			assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+		} else {
+			let report = micro_tower::report!(err.as_ref());
+			panic!("{report:?}")
+		}
+	}
+	let output = output.lock().unwrap();
+	assert_eq!(
+		String::from_utf8_lossy(&output[..]),
+		"{\"type\":\"ok\",\"data\":0}\n{\"type\":\"ok\",\"data\":1}\n{\"type\":\"end\"}\n"
+	);
+}
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn echo(
+	requests: impl futures::Stream<Item = String>,
+) -> impl futures::Stream<Item = String> {
+	requests
+}
+
+#[tokio::test]
+async fn echo_duplex_test() {
+	let service = echo::builder().build();
+	let service = ServiceBuilder::new()
+		.api_duplex::<String, codec::Json>()
+		.service(service);
+	let input =
+		"{\"type\":\"ok\",\"data\":\"a\"}\n{\"type\":\"ok\",\"data\":\"b\"}\n{\"type\":\"end\"}\n";
+	let stream = Stream::from_input(input);
+	let output = stream.output.clone();
+	let controller = Controller::default();
+
+	if let Err(err) =
+		micro_tower::session::stream::spawn_duplex_fut(stream, service, controller).await
+	{
+		let report = micro_tower::report!(err.as_ref());
+		panic!("{report:?}")
+	}
+	let output = output.lock().unwrap();
+	assert_eq!(
+		String::from_utf8_lossy(&output[..]),
+		"{\"type\":\"ok\",\"data\":\"a\"}\n{\"type\":\"ok\",\"data\":\"b\"}\n{\"type\":\"end\"}\n"
+	);
+}
+
+/// Service which is never ready.
+struct Unready;
+
+impl<R> tower::Service<R> for Unready {
+	type Response = micro_tower::util::BoxStream<Result<usize, micro_tower::util::BoxError>>;
+	type Error = micro_tower::util::BoxError;
+	type Future = micro_tower::util::BoxFuture<Result<Self::Response, Self::Error>>;
+
+	fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		Poll::Ready(Err("not ready".into()))
+	}
+
+	fn call(&mut self, _req: R) -> Self::Future {
+		unreachable!("never ready")
+	}
+}
+
+#[tokio::test]
+async fn unready_stream_test() {
+	use tower::ServiceExt;
+
+	let mut service = ServiceBuilder::new()
+		.api_stream::<usize, codec::Json>()
+		.service(Unready);
+	let err = service.ready().await.err().unwrap();
+	assert_eq!(String::from_utf8_lossy(&err.buf), "{\"type\":\"500\"}");
+
+	let mut service = ServiceBuilder::new()
+		.api_duplex::<usize, codec::Json>()
+		.service(Unready);
+	let err = service.ready().await.err().unwrap();
+	assert_eq!(String::from_utf8_lossy(&err.buf), "{\"type\":\"500\"}");
+}
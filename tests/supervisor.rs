@@ -0,0 +1,200 @@
+use micro_tower::prelude::*;
+use micro_tower::runtime::supervisor::Policy;
+use micro_tower::runtime::Runtime;
+use micro_tower::service::{Service, Status};
+use micro_tower::util::{BoxError, BoxFuture};
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Instance of a flaky service. Fails `poll_ready` unless `healthy`.
+#[derive(Clone)]
+struct Instance {
+	number: usize,
+	healthy: bool,
+}
+
+impl tower::Service<()> for Instance {
+	type Response = usize;
+	type Error = BoxError;
+	type Future = BoxFuture<Result<usize, BoxError>>;
+
+	fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		if self.healthy {
+			Poll::Ready(Ok(()))
+		} else {
+			Poll::Ready(Err("instance failed".into()))
+		}
+	}
+
+	fn call(&mut self, _req: ()) -> Self::Future {
+		let number = self.number;
+		Box::pin(async move { Ok(number) })
+	}
+}
+
+/// Declares service `$name` named `$service` whose first `$failures` instances fail.
+macro_rules! flaky {
+	($name:ident, $service:literal, $failures:expr) => {
+		struct $name;
+
+		impl $name {
+			fn created() -> &'static AtomicUsize {
+				static CREATED: AtomicUsize = AtomicUsize::new(0);
+				&CREATED
+			}
+
+			fn started() -> &'static AtomicUsize {
+				static STARTED: AtomicUsize = AtomicUsize::new(0);
+				&STARTED
+			}
+		}
+
+		impl micro_tower::service::Info for $name {
+			type Request = ();
+			type Response = usize;
+
+			fn name() -> &'static str {
+				$service
+			}
+		}
+
+		impl micro_tower::service::Create for $name {
+			type Error = Infallible;
+
+			fn with_registry(
+				_registry: Arc<RwLock<micro_tower::runtime::registry::Type>>,
+			) -> Result<Option<Service<Self>>, Self::Error> {
+				let number = Self::created().fetch_add(1, Ordering::SeqCst);
+				let healthy = number >= $failures;
+				Ok(Some(Service::from(Box::new(Instance { number, healthy }))))
+			}
+
+			fn on_start(
+				_registry: Arc<RwLock<micro_tower::runtime::registry::Type>>,
+			) -> BoxFuture<Result<(), BoxError>> {
+				Self::started().fetch_add(1, Ordering::SeqCst);
+				Box::pin(async { Ok(()) })
+			}
+		}
+	};
+}
+
+flaky!(Recovering, "recovering", 2);
+flaky!(Broken, "broken", usize::MAX);
+flaky!(Unsupervised, "unsupervised", 1);
+
+const POLICY: Policy = Policy {
+	initial_backoff: Duration::from_millis(5),
+	max_backoff: Duration::from_millis(20),
+	max_restarts: 3,
+	window: Duration::from_secs(10),
+};
+
+/// Calls `service` until it succeeds or `check` holds.
+async fn retry<S>(mut service: Service<S>, check: impl Fn() -> bool) -> Option<usize>
+where
+	S: micro_tower::service::Info<Request = (), Response = usize>,
+{
+	let retry = async {
+		loop {
+			if let Ok(number) = async { service.ready().await?.call(()).await }.await {
+				return Some(number);
+			}
+			if check() {
+				return None;
+			}
+			tokio::time::sleep(Duration::from_millis(5)).await;
+		}
+	};
+	tokio::time::timeout(Duration::from_secs(5), retry)
+		.await
+		.unwrap()
+}
+
+fn status(runtime: &Runtime, name: &str) -> Option<Status> {
+	runtime
+		.monitor()
+		.checks()
+		.into_iter()
+		.find(|check| check.name == name)
+		.map(|check| check.status)
+}
+
+#[test]
+fn backoff() {
+	assert_eq!(POLICY.backoff(0), Duration::from_millis(5));
+	assert_eq!(POLICY.backoff(1), Duration::from_millis(10));
+	assert_eq!(POLICY.backoff(2), Duration::from_millis(20));
+	assert_eq!(POLICY.backoff(3), Duration::from_millis(20));
+	assert_eq!(POLICY.backoff(u32::MAX), Duration::from_millis(20));
+}
+
+#[tokio::test]
+async fn restart_failed_service() {
+	let runtime = Runtime::builder()
+		.supervision(Some(POLICY))
+		.service::<Recovering>()
+		.build()
+		.await
+		.unwrap();
+	let service = runtime.services().get::<Recovering>().unwrap().unwrap();
+
+	let number = retry(service, || false).await;
+	assert_eq!(number, Some(2));
+	assert_eq!(Recovering::created().load(Ordering::SeqCst), 3);
+	assert_eq!(Recovering::started().load(Ordering::SeqCst), 3);
+	assert_eq!(status(&runtime, "recovering"), Some(Status::Healthy));
+	let restarts = micro_tower::metrics::global().counter(
+		"micro_tower_service_restarts_total",
+		"",
+		&[("service", "recovering")],
+	);
+	assert_eq!(restarts.get(), 2);
+}
+
+#[tokio::test]
+async fn give_up() {
+	let runtime = Runtime::builder()
+		.supervision(Some(POLICY))
+		.service::<Broken>()
+		.build()
+		.await
+		.unwrap();
+	let service = runtime.services().get::<Broken>().unwrap().unwrap();
+
+	let number = retry(service, || {
+		status(&runtime, "broken") == Some(Status::Unhealthy)
+	})
+	.await;
+	assert_eq!(number, None);
+	assert_eq!(
+		Broken::created().load(Ordering::SeqCst),
+		1 + POLICY.max_restarts
+	);
+	assert!(runtime
+		.errors()
+		.recent()
+		.iter()
+		.any(|entry| entry.source == "broken" && entry.message.contains("giving up")));
+}
+
+#[tokio::test]
+async fn without_supervision() {
+	let runtime = Runtime::builder()
+		.supervision(None)
+		.service::<Unsupervised>()
+		.build()
+		.await
+		.unwrap();
+	let mut service = runtime.services().get::<Unsupervised>().unwrap().unwrap();
+
+	for _ in 0..3 {
+		assert!(service.ready().await.is_err());
+		tokio::time::sleep(Duration::from_millis(20)).await;
+	}
+	assert_eq!(Unsupervised::created().load(Ordering::SeqCst), 1);
+	assert_eq!(Unsupervised::started().load(Ordering::SeqCst), 1);
+}
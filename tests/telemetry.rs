@@ -0,0 +1,165 @@
+use micro_tower::api::codec;
+use micro_tower::prelude::*;
+use micro_tower::runtime::Runtime;
+use micro_tower::session::tcp;
+use micro_tower::telemetry::{self, Context, Error, Format};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use tracing_subscriber::fmt::MakeWriter;
+
+#[micro_tower::codegen::service(buffer = 1)]
+async fn traced(request: u32) -> u32 {
+	tracing::info!("handled");
+	request + 1
+}
+
+/// Collects everything written by a subscriber.
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Buffer {
+	fn lines(&self) -> Vec<String> {
+		let buf = self.0.lock().unwrap();
+		String::from_utf8_lossy(&buf)
+			.lines()
+			.map(ToString::to_string)
+			.collect()
+	}
+}
+
+impl Write for Buffer {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		self.0.lock().unwrap().extend_from_slice(buf);
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
+
+impl<'a> MakeWriter<'a> for Buffer {
+	type Writer = Self;
+
+	fn make_writer(&'a self) -> Self::Writer {
+		self.clone()
+	}
+}
+
+#[test]
+fn parse_format() {
+	assert_eq!("pretty".parse::<Format>().unwrap(), Format::Pretty);
+	assert_eq!("compact".parse::<Format>().unwrap(), Format::Compact);
+	assert_eq!("json".parse::<Format>().unwrap(), Format::Json);
+	assert!(matches!("xml".parse::<Format>(), Err(Error::Format(_))));
+}
+
+#[test]
+fn invalid_filter() {
+	let result = telemetry::subscriber("app=loud", Format::Full, Buffer::default());
+	assert!(matches!(result, Err(Error::Filter { .. })));
+	// span and field directives are not supported
+	let result = telemetry::subscriber("app[request]=debug", Format::Full, Buffer::default());
+	assert!(matches!(result, Err(Error::Filter { .. })));
+}
+
+#[test]
+fn json_lines() {
+	let buffer = Buffer::default();
+	let subscriber = telemetry::subscriber("warn,app=debug", Format::Json, buffer.clone()).unwrap();
+	tracing::subscriber::with_default(subscriber, || {
+		let span = tracing::info_span!(target: "app", "request", id = 7);
+		let _guard = span.enter();
+		tracing::debug!(target: "app", count = 3, "handled");
+		tracing::info!(target: "other", "filtered");
+	});
+
+	let lines = buffer.lines();
+	assert_eq!(lines.len(), 1);
+	let event: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+	assert_eq!(event["level"], "DEBUG");
+	assert_eq!(event["target"], "app");
+	assert_eq!(event["fields"]["message"], "handled");
+	assert_eq!(event["fields"]["count"], 3);
+	assert_eq!(event["spans"][0]["name"], "request");
+	assert_eq!(event["spans"][0]["fields"], "id=7");
+}
+
+#[test]
+fn compact_filter() {
+	let buffer = Buffer::default();
+	let subscriber = telemetry::subscriber("info", Format::Compact, buffer.clone()).unwrap();
+	tracing::subscriber::with_default(subscriber, || {
+		tracing::info!("shown");
+		tracing::debug!("hidden");
+	});
+
+	let lines = buffer.lines();
+	assert_eq!(lines.len(), 1);
+	assert!(lines[0].contains("shown"));
+}
+
+#[test]
+fn traceparent() {
+	let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+	let context: Context = header.parse().unwrap();
+	assert_eq!(context.trace_id, 0x4bf9_2f35_77b3_4da6_a3ce_929d_0e0e_4736);
+	assert_eq!(context.span_id, 0x00f0_67aa_0ba9_02b7);
+	assert!(context.sampled);
+	assert_eq!(context.to_string(), header);
+
+	let child = context.child();
+	assert_eq!(child.trace_id, context.trace_id);
+	assert_ne!(child.span_id, context.span_id);
+
+	for invalid in [
+		"",
+		"00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+		"00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+		"00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+		"ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+		"00-4bf92f3577b34da6a3ce929d0e0e473x-00f067aa0ba902b7-01",
+	] {
+		assert!(matches!(
+			invalid.parse::<Context>(),
+			Err(Error::TraceParent(_))
+		));
+	}
+}
+
+#[tokio::test]
+async fn propagate_context() {
+	let buffer = Buffer::default();
+	let subscriber = telemetry::subscriber("info", Format::Json, buffer.clone()).unwrap();
+	let _default = tracing::subscriber::set_default(subscriber);
+
+	let session = tcp::Session::<codec::Json, _>::with_addr("127.0.0.1:0".parse().unwrap())
+		.await
+		.unwrap();
+	let addr = session.local_addr().unwrap();
+	let _runtime = Runtime::builder()
+		.bind_service::<traced, _>(session)
+		.build()
+		.await
+		.unwrap();
+
+	let span = tracing::info_span!("caller");
+	let caller = span.in_scope(Context::current).unwrap();
+	let mut client = tracedClient::<codec::Json>::connect(addr).await.unwrap();
+	let rep = async { client.ready().await.unwrap().call(41).await }
+		.instrument(span)
+		.await;
+	assert_eq!(rep.unwrap(), 42);
+
+	let lines = buffer.lines();
+	let event: serde_json::Value = lines
+		.iter()
+		.map(|line| serde_json::from_str(line).unwrap())
+		.find(|event: &serde_json::Value| event["fields"]["message"] == "handled")
+		.unwrap();
+	assert_eq!(event["trace_id"], format!("{:032x}", caller.trace_id));
+	assert_ne!(event["span_id"], format!("{:016x}", caller.span_id));
+	let request = &event["spans"][0];
+	assert_eq!(request["name"], "request");
+	assert_eq!(request["fields"], format!("traceparent={caller}"));
+}